@@ -0,0 +1,249 @@
+use crate::quaternion::Quaternion;
+use crate::ray::Ray;
+use crate::vector::{Point, Vector};
+use std::ops::Range;
+
+/// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Aabb {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Aabb {
+    /// Contains nothing, and is the identity for `union`
+    pub const EMPTY: Aabb = Aabb {
+        min: Vector {
+            x: f64::INFINITY,
+            y: f64::INFINITY,
+            z: f64::INFINITY,
+        },
+        max: Vector {
+            x: f64::NEG_INFINITY,
+            y: f64::NEG_INFINITY,
+            z: f64::NEG_INFINITY,
+        },
+    };
+
+    /// Contains everything, like the box around an infinite plane
+    pub const UNBOUNDED: Aabb = Aabb {
+        min: Vector {
+            x: f64::NEG_INFINITY,
+            y: f64::NEG_INFINITY,
+            z: f64::NEG_INFINITY,
+        },
+        max: Vector {
+            x: f64::INFINITY,
+            y: f64::INFINITY,
+            z: f64::INFINITY,
+        },
+    };
+
+    /// Flat boxes (e.g. around an axis-aligned quad) are padded so the slab test can still hit them
+    const MIN_THICKNESS: f64 = 1e-4;
+
+    /// The box spanning two opposite corners, in any order
+    pub fn new(a: Point, b: Point) -> Self {
+        let mut min = Point::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+        let mut max = Point::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+        for axis in 0..3 {
+            let thickness = component(&max, axis) - component(&min, axis);
+            if thickness < Self::MIN_THICKNESS {
+                let padding = (Self::MIN_THICKNESS - thickness) / 2.0;
+                *component_mut(&mut min, axis) -= padding;
+                *component_mut(&mut max, axis) += padding;
+            }
+        }
+        Aabb { min, max }
+    }
+
+    /// Whether the box contains no points at all, like `EMPTY`
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn centroid(&self) -> Point {
+        (self.min + self.max) / 2.0
+    }
+
+    /// Index of the axis (0 = x, 1 = y, 2 = z) the box is longest along
+    pub fn longest_axis(&self) -> usize {
+        let size = self.max - self.min;
+        if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// The box around this one after rotating it about the origin and then translating it. A box
+    /// that is infinite along any axis becomes infinite along all of them, since rotating its
+    /// corners would mix infinities of opposite signs into NaN.
+    pub fn transformed(&self, rotation: Quaternion, translation: Vector) -> Aabb {
+        if self.is_empty() {
+            return Aabb::EMPTY;
+        }
+        let finite = [self.min, self.max]
+            .iter()
+            .all(|corner| corner.x.is_finite() && corner.y.is_finite() && corner.z.is_finite());
+        if !finite {
+            return Aabb::UNBOUNDED;
+        }
+
+        let corners = (0..8).map(|i| {
+            Point::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            )
+        });
+        corners
+            .map(|corner| rotation.rotate_point(corner) + translation)
+            .fold(Aabb::EMPTY, |bounds, point| {
+                bounds.union(&Aabb {
+                    min: point,
+                    max: point,
+                })
+            })
+    }
+
+    /// Slab test: whether the ray passes through the box anywhere in the range `t`
+    pub fn hit(&self, ray: &Ray, t: Range<f64>) -> bool {
+        self.hit_range(ray, t).is_some()
+    }
+
+    /// The part of the range `t` during which the ray is inside the box, if any
+    pub fn hit_range(&self, ray: &Ray, t: Range<f64>) -> Option<Range<f64>> {
+        let (mut t_min, mut t_max) = (t.start, t.end);
+        for axis in 0..3 {
+            let inverse = 1.0 / component(&ray.direction, axis);
+            let origin = component(&ray.origin, axis);
+            let mut t0 = (component(&self.min, axis) - origin) * inverse;
+            let mut t1 = (component(&self.max, axis) - origin) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // f64::max/min ignore the NaN from a ray lying exactly on a slab boundary
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some(t_min..t_max)
+    }
+}
+
+pub fn component(vector: &Vector, axis: usize) -> f64 {
+    match axis {
+        0 => vector.x,
+        1 => vector.y,
+        _ => vector.z,
+    }
+}
+
+fn component_mut(vector: &mut Vector, axis: usize) -> &mut f64 {
+    match axis {
+        0 => &mut vector.x,
+        1 => &mut vector.y,
+        _ => &mut vector.z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn test_hit() {
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(unit_box().hit(&ray, 0.001..f64::INFINITY));
+        // the box is between t = 4 and t = 6
+        assert!(!unit_box().hit(&ray, 0.001..3.0));
+        assert!(!unit_box().hit(&ray, 7.0..f64::INFINITY));
+
+        let ray = Ray::new(Point::new(0.0, 2.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(!unit_box().hit(&ray, 0.001..f64::INFINITY));
+
+        let ray = Ray::new(Point::new(5.0, 5.0, 5.0), Vector::new(-1.0, -1.0, -1.0));
+        assert!(unit_box().hit(&ray, 0.001..f64::INFINITY));
+    }
+
+    #[test]
+    fn test_hit_range() {
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            unit_box().hit_range(&ray, 0.001..f64::INFINITY),
+            Some(4.0..6.0)
+        );
+        assert_eq!(unit_box().hit_range(&ray, 5.0..10.0), Some(5.0..6.0));
+        assert_eq!(unit_box().hit_range(&ray, 0.001..3.0), None);
+    }
+
+    #[test]
+    fn test_flat_box_is_padded() {
+        let flat = Aabb::new(Point::new(-1.0, 0.0, -1.0), Point::new(1.0, 0.0, 1.0));
+        assert!(flat.max.y > flat.min.y);
+
+        let ray = Ray::new(Point::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert!(flat.hit(&ray, 0.001..f64::INFINITY));
+    }
+
+    #[test]
+    fn test_union_and_longest_axis() {
+        let other = Aabb::new(Point::new(0.0, 0.0, 0.0), Point::new(5.0, 2.0, 1.0));
+        let union = unit_box().union(&other);
+        assert_eq!(union.min, Point::new(-1.0, -1.0, -1.0));
+        assert_eq!(union.max, Point::new(5.0, 2.0, 1.0));
+        assert_eq!(union.longest_axis(), 0);
+        assert_eq!(Aabb::EMPTY.union(&other), other);
+        assert!(Aabb::EMPTY.is_empty());
+        assert!(!union.is_empty());
+    }
+
+    #[test]
+    fn test_transformed() {
+        let quarter_turn = Quaternion::from_axis_angle(Vector::new(0.0, 0.0, 1.0), PI / 2.0);
+        let turned = Aabb::new(Point::new(0.0, 0.0, 0.0), Point::new(2.0, 1.0, 1.0))
+            .transformed(quarter_turn, Vector::new(0.0, 0.0, 3.0));
+        assert!((turned.max.x - turned.min.x - 1.0).abs() < 1e-12);
+        assert!((turned.max.y - turned.min.y - 2.0).abs() < 1e-12);
+        assert_eq!((turned.min.z, turned.max.z), (3.0, 4.0));
+
+        // an infinite plane's box would otherwise turn into NaN and be skipped as empty
+        let plane = Aabb::new(
+            Point::new(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
+            Point::new(f64::INFINITY, 0.0, f64::INFINITY),
+        );
+        assert_eq!(
+            plane.transformed(quarter_turn, Vector::ZERO),
+            Aabb::UNBOUNDED
+        );
+        assert!(Aabb::EMPTY
+            .transformed(quarter_turn, Vector::ZERO)
+            .is_empty());
+    }
+}
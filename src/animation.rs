@@ -0,0 +1,312 @@
+use crate::output::OutputFormat;
+use crate::quaternion::Quaternion;
+use crate::renderer::{render, RenderError, RenderOutput};
+use crate::settings::{CameraSettings, RenderSettings};
+use crate::vector::{Point, Vector};
+use crate::world::WorldCache;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, ImageFormat, RgbaImage};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Speed of the NeuQuant palette quantization for GIF frames, from 1 (best colors) to 30
+/// (fastest). 10 is the gif crate's own default and hardly differs from 1 on renders.
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+/// Renders `frames` images of `base_settings` into `out_dir`, named `frame_0001.png` and so on
+/// with the extension of the output format. Frame `i` is seen from `camera_path(i / frames)`, so
+/// the path is followed from 0 up to just short of 1 and a looping path doesn't repeat its
+/// first frame. With a seed set every frame uses it, otherwise the noise differs between frames.
+/// Depth and normal images go next to their frame, as `frame_0001.depth.png` and so on.
+/// `progress` is told how much of the whole animation is done. Returns the paths of the frames,
+/// in order.
+pub fn render_animation(
+    base_settings: RenderSettings,
+    frames: u32,
+    camera_path: impl Fn(f64) -> CameraSettings,
+    out_dir: &Path,
+    progress: Option<&(dyn Fn(f32) + Sync)>,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    fs::create_dir_all(out_dir)?;
+    let extension = if base_settings.exr_layers {
+        "exr"
+    } else {
+        base_settings.output_format.extension()
+    };
+
+    // every frame is the same scene, so it's only built once
+    let worlds = WorldCache::default();
+    let mut paths = Vec::with_capacity(frames as usize);
+    for frame in 0..frames {
+        let camera = camera_path(frame as f64 / frames as f64);
+        let settings = frame_settings(&base_settings, camera);
+        let output = render_frame(settings, &worlds, frame, frames, progress)?;
+        let name = format!("frame_{:04}", frame + 1);
+        for (aov, bytes) in [("depth", &output.depth), ("normal", &output.normal)] {
+            if let Some(bytes) = bytes {
+                fs::write(out_dir.join(format!("{}.{}.png", name, aov)), bytes)?;
+            }
+        }
+        let path = out_dir.join(format!("{}.{}", name, extension));
+        fs::write(&path, output.image)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Renders `frames` images like `render_animation`, as the frames of one endlessly looping
+/// animated GIF showing each for `delay`. Each frame gets its own palette, so a camera moving
+/// between differently colored parts of a scene doesn't band. There are no depth or normal
+/// images to go with it.
+pub fn render_animated_gif(
+    base_settings: RenderSettings,
+    frames: u32,
+    camera_path: impl Fn(f64) -> CameraSettings,
+    delay: Duration,
+    progress: Option<&(dyn Fn(f32) + Sync)>,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    // rendered losslessly, the palette is picked when the whole GIF is encoded
+    let base_settings = RenderSettings {
+        output_format: OutputFormat::Png,
+        exr_layers: false,
+        aov: false,
+        ..base_settings
+    };
+    let worlds = WorldCache::default();
+    let images = (0..frames)
+        .map(|frame| {
+            let camera = camera_path(frame as f64 / frames as f64);
+            let settings = frame_settings(&base_settings, camera);
+            let output = render_frame(settings, &worlds, frame, frames, progress)?;
+            Ok(decode_frame(&output.image)?)
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    Ok(encode_gif(images, delay)?)
+}
+
+/// Renders `frame` of `frames`, telling `progress` how much of the whole animation is done
+fn render_frame(
+    settings: RenderSettings,
+    worlds: &WorldCache,
+    frame: u32,
+    frames: u32,
+    progress: Option<&(dyn Fn(f32) + Sync)>,
+) -> Result<RenderOutput, RenderError> {
+    let Some(progress) = progress else {
+        return render(settings, worlds, None);
+    };
+    let frame_progress = |fraction: f32| progress((frame as f32 + fraction) / frames as f32);
+    render(settings, worlds, Some(&frame_progress))
+}
+
+/// Encodes `frames` as an endlessly looping animated GIF, showing each for `delay`. Transparent
+/// pixels stay transparent.
+pub fn encode_gif(frames: Vec<RgbaImage>, delay: Duration) -> image::ImageResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    {
+        // the trailer is written when the encoder is dropped
+        let mut encoder = GifEncoder::new_with_speed(&mut bytes, GIF_QUANTIZE_SPEED);
+        encoder.set_repeat(Repeat::Infinite)?;
+        let delay = Delay::from_saturating_duration(delay);
+        encoder.encode_frames(
+            frames
+                .into_iter()
+                .map(|image| Frame::from_parts(image, 0, 0, delay)),
+        )?;
+    }
+    Ok(bytes)
+}
+
+/// Pixels of a render's PNG image, to be made into a frame of an animation
+pub fn decode_frame(png: &[u8]) -> image::ImageResult<RgbaImage> {
+    Ok(image::load_from_memory_with_format(png, ImageFormat::Png)?.to_rgba8())
+}
+
+/// `base_settings` seen from `camera`
+fn frame_settings(base_settings: &RenderSettings, camera: CameraSettings) -> RenderSettings {
+    RenderSettings {
+        camera_position: camera.camera_position,
+        focus_point: camera.focus_point,
+        up: camera.up,
+        field_of_view: camera.field_of_view,
+        ..base_settings.clone()
+    }
+}
+
+/// Camera part way from `from` at `t = 0` to `to` at `t = 1`. The camera swings around the
+/// focus point at a constant rate instead of cutting across in a straight line, while its
+/// distance to the focus point, the focus point itself, `up` and the field of view change
+/// linearly.
+pub fn interpolate_camera(from: &CameraSettings, to: &CameraSettings, t: f64) -> CameraSettings {
+    let lerp = |a: Vector, b: Vector| (1.0 - t) * a + t * b;
+
+    let start = from.camera_position - from.focus_point;
+    let end = to.camera_position - to.focus_point;
+    let rotation = Quaternion::new(0.0, 0.0, 0.0, 1.0).slerp(rotation_between(&start, &end), t);
+    let distance = (1.0 - t) * start.length() + t * end.length();
+    let focus_point: Point = lerp(from.focus_point, to.focus_point);
+
+    CameraSettings {
+        camera_position: focus_point + distance * rotation.rotate_point(start.normalize()),
+        focus_point,
+        up: lerp(from.up.normalize(), to.up.normalize()).normalize(),
+        field_of_view: ((1.0 - t) * from.field_of_view as f64 + t * to.field_of_view as f64) as f32,
+    }
+}
+
+/// Shortest rotation turning the direction of `from` into the direction of `to`
+fn rotation_between(from: &Vector, to: &Vector) -> Quaternion {
+    let from = from.normalize();
+    let to = to.normalize();
+    let axis = to.cross(&from);
+    if axis.length() < 1e-9 {
+        if from.dot(&to) > 0.0 {
+            return Quaternion::new(0.0, 0.0, 0.0, 1.0);
+        }
+        // opposite directions, any axis at right angles to them does
+        let (tangent, _) = crate::material::tangent_frame(&from);
+        return Quaternion::from_axis_angle(tangent, std::f64::consts::PI);
+    }
+    let angle = from.dot(&to).clamp(-1.0, 1.0).acos();
+    Quaternion::from_axis_angle(axis.normalize(), angle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Size;
+    use crate::world::{get_scene_camera, Scene};
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+    use std::sync::Mutex;
+
+    fn assert_near(a: Vector, b: Vector) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_interpolate_camera_endpoints() {
+        let from = CameraSettings {
+            camera_position: Point::new(0.0, 1.0, 5.0),
+            focus_point: Point::new(0.0, 1.0, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
+            field_of_view: 40.0,
+        };
+        let to = CameraSettings {
+            camera_position: Point::new(6.0, 2.0, 1.0),
+            focus_point: Point::new(1.0, 0.0, 1.0),
+            up: Vector::new(0.0, 2.0, 0.0),
+            field_of_view: 60.0,
+        };
+
+        let start = interpolate_camera(&from, &to, 0.0);
+        assert_near(start.camera_position, from.camera_position);
+        assert_eq!(start.field_of_view, 40.0);
+        let end = interpolate_camera(&from, &to, 1.0);
+        assert_near(end.camera_position, to.camera_position);
+        assert_near(end.focus_point, to.focus_point);
+        assert_near(end.up, Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(end.field_of_view, 60.0);
+
+        // halfway round a quarter turn around the same focus point stays on the circle
+        let to = CameraSettings {
+            camera_position: Point::new(5.0, 1.0, 0.0),
+            ..from
+        };
+        let middle = interpolate_camera(&from, &to, 0.5);
+        let offset = 5.0 * std::f64::consts::FRAC_1_SQRT_2;
+        assert_near(middle.camera_position, Point::new(offset, 1.0, offset));
+    }
+
+    #[test]
+    fn test_render_animation_writes_frames() {
+        let settings = RenderSettings {
+            size: Size {
+                width: 32,
+                height: 18,
+            },
+            samples: 2,
+            max_depth: 4,
+            scene: Scene::OneSphere,
+            aov: true,
+            ..Default::default()
+        };
+        let out_dir =
+            std::env::temp_dir().join(format!("raytracing-animation-{}", std::process::id()));
+        let from = get_scene_camera(&Scene::OneSphere);
+        let to = CameraSettings {
+            camera_position: Point::new(3.0, 0.0, 0.0),
+            ..get_scene_camera(&Scene::OneSphere)
+        };
+
+        let reports = Mutex::new(Vec::new());
+        let progress = |fraction| reports.lock().unwrap().push(fraction);
+
+        let paths = render_animation(
+            settings,
+            2,
+            |t| interpolate_camera(&from, &to, t),
+            &out_dir,
+            Some(&progress),
+        )
+        .unwrap();
+
+        assert_eq!(
+            paths,
+            vec![
+                out_dir.join("frame_0001.png"),
+                out_dir.join("frame_0002.png")
+            ]
+        );
+        for path in &paths {
+            let image = image::open(path).unwrap();
+            assert_eq!((image.width(), image.height()), (32, 18));
+        }
+        assert!(out_dir.join("frame_0002.depth.png").exists());
+        assert!(out_dir.join("frame_0002.normal.png").exists());
+        // one progress bar for the whole animation
+        let reports = reports.into_inner().unwrap();
+        assert!(reports.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(reports.contains(&0.5));
+        assert_eq!(reports.last(), Some(&1.0));
+        fs::remove_dir_all(out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_render_animated_gif_has_every_frame() {
+        let settings = RenderSettings {
+            size: Size {
+                width: 24,
+                height: 16,
+            },
+            samples: 2,
+            max_depth: 4,
+            scene: Scene::OneSphere,
+            alpha: true,
+            ..Default::default()
+        };
+        let camera = get_scene_camera(&Scene::OneSphere);
+        let turn = |t: f64| CameraSettings {
+            camera_position: Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), t)
+                .rotate_point(camera.camera_position),
+            ..get_scene_camera(&Scene::OneSphere)
+        };
+
+        let gif = render_animated_gif(settings, 3, turn, Duration::from_millis(50), None).unwrap();
+
+        let frames = GifDecoder::new(gif.as_slice())
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 3);
+        for frame in &frames {
+            assert_eq!(frame.buffer().dimensions(), (24, 16));
+            assert_eq!(frame.delay().numer_denom_ms(), (50, 1));
+        }
+        // the background around the sphere stays transparent
+        assert_eq!(frames[0].buffer().get_pixel(0, 0)[3], 0);
+    }
+}
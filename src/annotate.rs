@@ -0,0 +1,177 @@
+use crate::settings::RenderSettings;
+use image::{Rgb, RgbImage};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const MARGIN: u32 = 4;
+
+const TEXT_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+const BACKDROP_COLOR: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Burns a short description of the render (scene, samples, date) into the bottom-left corner
+/// of the image. Does nothing unless `settings.annotate` is set.
+pub fn annotate_render(image: &mut RgbImage, settings: &RenderSettings) {
+    if !settings.annotate {
+        return;
+    }
+
+    let label = format!(
+        "{} - {} SAMPLES - {}",
+        settings.scene,
+        settings.samples,
+        current_date()
+    );
+    draw_label(image, &label);
+}
+
+fn draw_label(image: &mut RgbImage, text: &str) {
+    let scale = (image.height() / 540).max(1);
+    let advance = (GLYPH_WIDTH + 1) * scale;
+    let width = text.chars().count() as u32 * advance + MARGIN * scale;
+    let height = (GLYPH_HEIGHT + MARGIN) * scale;
+
+    let left = 0;
+    let top = image.height().saturating_sub(height);
+
+    fill_rect(image, left, top, width, height, BACKDROP_COLOR);
+
+    let mut x = left + MARGIN / 2 * scale;
+    let y = top + MARGIN / 2 * scale;
+    for c in text.chars() {
+        draw_glyph(image, x, y, scale, glyph(c));
+        x += advance;
+    }
+}
+
+fn fill_rect(image: &mut RgbImage, x: u32, y: u32, width: u32, height: u32, color: Rgb<u8>) {
+    for py in y..(y + height).min(image.height()) {
+        for px in x..(x + width).min(image.width()) {
+            image.put_pixel(px, py, color);
+        }
+    }
+}
+
+fn draw_glyph(image: &mut RgbImage, x: u32, y: u32, scale: u32, rows: [u8; 7]) {
+    for (row, bits) in rows.iter().enumerate() {
+        for column in 0..GLYPH_WIDTH {
+            if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                fill_rect(
+                    image,
+                    x + column * scale,
+                    y + row as u32 * scale,
+                    scale,
+                    scale,
+                    TEXT_COLOR,
+                );
+            }
+        }
+    }
+}
+
+/// Rows of a 5x7 bitmap font, top to bottom, with the leftmost pixel in the fifth bit
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        ' ' => [0x00; 7],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
+
+/// Today's date (UTC) as YYYY-MM-DD
+fn current_date() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86_400)
+        .unwrap_or(0) as i64;
+
+    // civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray_image() -> RgbImage {
+        RgbImage::from_pixel(320, 180, Rgb([128, 128, 128]))
+    }
+
+    #[test]
+    fn test_annotate_changes_corner() {
+        let settings = RenderSettings {
+            annotate: true,
+            ..Default::default()
+        };
+        let mut image = gray_image();
+        annotate_render(&mut image, &settings);
+
+        let corner = image.get_pixel(0, image.height() - 1);
+        assert_ne!(*corner, Rgb([128, 128, 128]));
+        assert_ne!(image, gray_image());
+    }
+
+    #[test]
+    fn test_annotate_disabled_leaves_image_untouched() {
+        let settings = RenderSettings {
+            annotate: false,
+            ..Default::default()
+        };
+        let mut image = gray_image();
+        annotate_render(&mut image, &settings);
+
+        assert_eq!(image, gray_image());
+    }
+}
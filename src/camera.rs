@@ -0,0 +1,259 @@
+use crate::data::Size;
+use crate::ray::Ray;
+use crate::rng;
+use crate::settings::RenderSettings;
+use crate::vector::{Point, Vector};
+
+/// Turns pixel coordinates into rays, following the camera and image settings of a render
+pub struct Camera {
+    position: Point,
+    origin_pixel: Point,
+    pixel_delta_u: Vector,
+    pixel_delta_v: Vector,
+    defocus_angle: f32,
+    defocus_u: Vector,
+    defocus_v: Vector,
+    cats_eye: f64,
+    /// Image center in pixels, and the distance from it to a corner
+    center: (f64, f64),
+    half_diagonal: f64,
+    u: Vector,
+    v: Vector,
+    w: Vector,
+}
+
+impl Camera {
+    pub fn new(settings: &RenderSettings) -> Self {
+        let focal_length = (settings.camera_position - settings.focus_point).length();
+        let theta = settings.field_of_view.to_radians();
+        let h = (theta / 2.0).tan();
+        let viewport_size = {
+            let height = 2.0 * h as f64 * focal_length;
+            Size {
+                height,
+                width: height * settings.size.width as f64 / settings.size.height as f64,
+            }
+        };
+
+        let (u, v, w) = camera_basis(settings.camera_position, settings.focus_point, settings.up);
+
+        let viewport_u = u * viewport_size.width;
+        let viewport_v = -v * viewport_size.height;
+
+        let pixel_delta_u = viewport_u / settings.size.width as f64;
+        let pixel_delta_v = viewport_v / settings.size.height as f64;
+
+        let viewport_origin =
+            settings.camera_position - focal_length * w - viewport_u / 2.0 - viewport_v / 2.0;
+        let origin_pixel = viewport_origin + (pixel_delta_u + pixel_delta_v) / 2.0;
+
+        let defocus_radius = settings.focus_distance as f64
+            * (settings.defocus_angle as f64 / 2.0).to_radians().tan();
+
+        Camera {
+            position: settings.camera_position,
+            origin_pixel,
+            pixel_delta_u,
+            pixel_delta_v,
+            defocus_angle: settings.defocus_angle,
+            defocus_u: u * defocus_radius,
+            defocus_v: v * defocus_radius,
+            cats_eye: settings.cats_eye.clamp(0.0, 1.0) as f64,
+            center: (
+                settings.size.width as f64 / 2.0,
+                settings.size.height as f64 / 2.0,
+            ),
+            half_diagonal: (settings.size.width as f64).hypot(settings.size.height as f64) / 2.0,
+            u,
+            v,
+            w,
+        }
+    }
+
+    /// Right, up, and backwards (from the focus point towards the camera) directions
+    pub fn basis(&self) -> (Vector, Vector, Vector) {
+        (self.u, self.v, self.w)
+    }
+
+    /// A ray through a random point within the pixel, starting from a random point on the
+    /// defocus disk
+    pub fn get_ray(&self, x: u32, y: u32) -> Ray {
+        let pixel_center =
+            self.origin_pixel + (x as f64 * self.pixel_delta_u) + (y as f64 * self.pixel_delta_v);
+        let pixel_sample = pixel_center + self.pixel_sample_square();
+
+        let ray_origin = if self.defocus_angle > 0.0 {
+            self.defocus_disk_sample(x, y)
+        } else {
+            self.position
+        };
+        let ray_direction = pixel_sample - ray_origin;
+        Ray {
+            time: rng::random(),
+            ..Ray::new(ray_origin, ray_direction)
+        }
+    }
+
+    /// A ray from the camera position through the exact center of the pixel, at time 0
+    pub fn get_center_ray(&self, x: u32, y: u32) -> Ray {
+        let pixel_center =
+            self.origin_pixel + (x as f64 * self.pixel_delta_u) + (y as f64 * self.pixel_delta_v);
+        Ray::new(self.position, pixel_center - self.position)
+    }
+
+    fn pixel_sample_square(&self) -> Vector {
+        let px = -0.5 + rng::random::<f64>();
+        let py = -0.5 + rng::random::<f64>();
+        px * self.pixel_delta_u + py * self.pixel_delta_v
+    }
+
+    fn defocus_disk_sample(&self, x: u32, y: u32) -> Point {
+        let p = self.aperture_sample(x, y);
+        self.position + (p.x * self.defocus_u) + (p.y * self.defocus_v)
+    }
+
+    /// A random point on the unit aperture disk as seen from pixel (x, y). With cat's-eye
+    /// vignetting, the lens barrel hides part of the aperture from off-center pixels, which is
+    /// modelled as a second unit disk shifted outwards towards the pixel.
+    fn aperture_sample(&self, x: u32, y: u32) -> Vector {
+        let clip_center = self.cats_eye_offset(x, y);
+        loop {
+            let p = Vector::random_in_unit_disk();
+            if (p - clip_center).length_squared() < 1.0 {
+                return p;
+            }
+        }
+    }
+
+    /// Center of the clipping disk, which moves out to `cats_eye` in the corners
+    fn cats_eye_offset(&self, x: u32, y: u32) -> Vector {
+        if self.cats_eye == 0.0 {
+            return Vector::ZERO;
+        }
+        let dx = (x as f64 + 0.5 - self.center.0) / self.half_diagonal;
+        // pixel rows go down, but the aperture's v axis goes up
+        let dy = (self.center.1 - y as f64 - 0.5) / self.half_diagonal;
+        Vector::new(dx, dy, 0.0) * self.cats_eye
+    }
+}
+
+/// Orthonormal camera basis: `u` points right, `v` up, and `w` back from the focus point towards
+/// the camera. `v` is as close to `up` as it can be while staying perpendicular to the view.
+///
+/// Looking straight along `up` leaves no way to tell which way is up, so the first of -z, +x, and
+/// +y that isn't close to the view direction stands in for it. A camera looking straight down
+/// then has -z at the top of the image.
+fn camera_basis(
+    camera_position: Point,
+    focus_point: Point,
+    up: Vector,
+) -> (Vector, Vector, Vector) {
+    let w = (camera_position - focus_point).normalize();
+    let mut u = up.cross(&w);
+    if u.length_squared() <= 1e-12 * up.length_squared() {
+        let fallback = [
+            Vector::new(0.0, 0.0, -1.0),
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        ]
+        .into_iter()
+        .find(|axis| axis.dot(&w).abs() < 0.9)
+        .unwrap();
+        u = fallback.cross(&w);
+    }
+    let u = u.normalize();
+    let v = w.cross(&u);
+    (u, v, w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Size;
+
+    fn vignetted_camera() -> Camera {
+        Camera::new(&RenderSettings {
+            size: Size {
+                width: 101,
+                height: 101,
+            },
+            defocus_angle: 2.0,
+            cats_eye: 1.0,
+            ..Default::default()
+        })
+    }
+
+    fn assert_near(a: Vector, b: Vector) {
+        assert!((a - b).is_near_zero(), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_rolled_camera_basis() {
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let forward = Point::new(0.0, 0.0, -1.0);
+        let (u, v, w) = camera_basis(origin, forward, Vector::new(0.0, 1.0, 0.0));
+        assert_near(u, Vector::new(1.0, 0.0, 0.0));
+        assert_near(v, Vector::new(0.0, 1.0, 0.0));
+        assert_near(w, Vector::new(0.0, 0.0, 1.0));
+
+        // tipping up 45 degrees to the left rolls the camera counterclockwise, so right turns
+        // upwards by the same angle; the length of up doesn't matter
+        let s = std::f64::consts::FRAC_1_SQRT_2;
+        let (u, v, w) = camera_basis(origin, forward, Vector::new(-2.0, 2.0, 0.0));
+        assert_near(u, Vector::new(s, s, 0.0));
+        assert_near(v, Vector::new(-s, s, 0.0));
+        assert_near(w, Vector::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_top_down_camera_basis() {
+        let above = Point::new(0.0, 10.0, 0.0);
+        let (u, v, w) = camera_basis(above, Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        assert_near(u, Vector::new(1.0, 0.0, 0.0));
+        assert_near(v, Vector::new(0.0, 0.0, -1.0));
+        assert_near(w, Vector::new(0.0, 1.0, 0.0));
+
+        // rays still spread out over the image instead of all being NaN
+        let camera = Camera::new(&RenderSettings {
+            camera_position: above,
+            focus_point: Point::new(0.0, 0.0, 0.0),
+            ..Default::default()
+        });
+        let ray = camera.get_center_ray(0, 0);
+        assert!(ray.direction.x.is_finite() && ray.direction.x < 0.0);
+        assert!(ray.direction.z < 0.0);
+    }
+
+    #[test]
+    fn test_cats_eye_center_uses_full_aperture() {
+        let camera = vignetted_camera();
+        let samples = (0..2000)
+            .map(|_| camera.aperture_sample(50, 50))
+            .collect::<Vec<_>>();
+
+        assert!(samples.iter().all(|p| p.length_squared() < 1.0));
+        // reaches out to every side of the disk
+        assert!(samples.iter().any(|p| p.x < -0.8));
+        assert!(samples.iter().any(|p| p.x > 0.8));
+        assert!(samples.iter().any(|p| p.y < -0.8));
+        assert!(samples.iter().any(|p| p.y > 0.8));
+    }
+
+    #[test]
+    fn test_cats_eye_clips_aperture_at_edge() {
+        let camera = vignetted_camera();
+        let clip_center = camera.cats_eye_offset(100, 50);
+        assert!(clip_center.x > 0.6 && clip_center.y.abs() < 1e-12);
+
+        let samples = (0..2000)
+            .map(|_| camera.aperture_sample(100, 50))
+            .collect::<Vec<_>>();
+        for p in &samples {
+            assert!(p.length_squared() < 1.0);
+            assert!((*p - clip_center).length_squared() < 1.0);
+        }
+        // the side of the aperture facing away from the edge is cut off, but not the middle
+        assert!(samples.iter().all(|p| p.x > clip_center.x - 1.0));
+        assert!(samples.iter().any(|p| p.y > 0.6));
+    }
+}
@@ -1,15 +1,47 @@
+use crate::rng;
 use crate::vector::Vector;
 use rand::Rng;
+use std::fmt;
 use std::ops::Range;
+use std::str::FromStr;
+
+/// The golden ratio. `std::f64::consts::PHI` is still behind the nightly `more_float_constants`
+/// feature, so it is defined here to keep the crate building on stable.
+const PHI: f64 = 1.618_033_988_749_895;
 
 pub trait GammaCorrect {
-    fn gamma_correct(self) -> Self;
+    /// Raises each channel to `1 / gamma`
+    fn gamma_correct(self, gamma: f64) -> Self;
 }
 
 pub trait Clamp {
     fn clamp(self, min: f64, max: f64) -> Self;
 }
 
+/// Why a string couldn't be read as a color
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorParseError {
+    /// The number of hex digits, which should be 3, 4, 6 or 8
+    InvalidLength(usize),
+    /// A character that isn't a hex digit
+    InvalidDigit(char),
+}
+
+impl fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorParseError::InvalidLength(length) => write!(
+                f,
+                "Expected 3, 4, 6 or 8 hex digits in a color, found {}",
+                length
+            ),
+            ColorParseError::InvalidDigit(c) => write!(f, "'{}' is not a hex digit", c),
+        }
+    }
+}
+
+impl std::error::Error for ColorParseError {}
+
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct Color {
@@ -57,10 +89,87 @@ impl Color {
         }
     }
 
+    pub fn is_finite(&self) -> bool {
+        self.r.is_finite() && self.g.is_finite() && self.b.is_finite()
+    }
+
+    /// False color for heatmaps: blue at 0, through cyan, green, and yellow, to red at 1
+    pub fn heat(t: f64) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        Self::hsv_to_rgb((1.0 - t) * 2.0 / 3.0, 1.0, 1.0)
+    }
+
+    /// Relative luminance of a linear Rec. 709 color
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    /// Reinhard tone mapping, `c / (c + 1)` per channel, which squeezes any brightness below 1.0
+    pub fn reinhard(self) -> Self {
+        let map = |c: f64| c / (c + 1.0);
+        Self::new(map(self.r), map(self.g), map(self.b))
+    }
+
+    /// Krzysztof Narkowicz's fit of the ACES filmic tone curve, with a gentle toe in the shadows
+    /// and a soft shoulder into white
+    pub fn aces_filmic(self) -> Self {
+        let map = |c: f64| {
+            let c = c.max(0.0);
+            ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0.0, 1.0)
+        };
+        Self::new(map(self.r), map(self.g), map(self.b))
+    }
+
+    /// Parses a hex color like `#A50D0D`, or the short form `#RGB`, where each digit is doubled.
+    /// A trailing alpha component (`#RGBA` or `#RRGGBBAA`) is accepted and ignored, and so is a
+    /// missing `#`. The components are sRGB, as in color pickers, and are decoded to linear light.
+    pub fn from_hex(hex: &str) -> Result<Self, ColorParseError> {
+        let digits = hex
+            .strip_prefix('#')
+            .unwrap_or(hex)
+            .chars()
+            .map(|c| match c.to_digit(16) {
+                Some(digit) => Ok(digit as u8),
+                None => Err(ColorParseError::InvalidDigit(c)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let bytes: [u8; 3] = match digits.len() {
+            3 | 4 => [0, 1, 2].map(|i| digits[i] * 17),
+            6 | 8 => [0, 1, 2].map(|i| digits[2 * i] * 16 + digits[2 * i + 1]),
+            length => return Err(ColorParseError::InvalidLength(length)),
+        };
+        Ok(Color::from(bytes).srgb_decode())
+    }
+
+    /// Encodes linear light with the sRGB transfer function, for display
+    pub fn srgb_encode(self) -> Self {
+        let map = |c: f64| {
+            if c <= 0.0031308 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        };
+        Self::new(map(self.r), map(self.g), map(self.b))
+    }
+
+    /// Decodes sRGB values, like the pixels of most 8-bit images, back to linear light
+    pub fn srgb_decode(self) -> Self {
+        let map = |c: f64| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Self::new(map(self.r), map(self.g), map(self.b))
+    }
+
     pub fn random() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = rng::thread_rng();
 
-        const INV_PHI: f64 = 1.0 / std::f64::consts::PHI;
+        const INV_PHI: f64 = 1.0 / PHI;
         const SATURATION: f64 = 0.75;
         const VALUE: f64 = 0.95;
 
@@ -73,7 +182,7 @@ impl Color {
     }
 
     pub fn random_with_range(range: Range<f64>) -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = rng::thread_rng();
         Self {
             r: rng.gen_range(range.clone()),
             g: rng.gen_range(range.clone()),
@@ -82,13 +191,19 @@ impl Color {
     }
 }
 
+impl FromStr for Color {
+    type Err = ColorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Color::from_hex(s)
+    }
+}
+
 impl From<Color> for [u8; 3] {
     fn from(color: Color) -> [u8; 3] {
-        [
-            (color.r * 255.0) as u8,
-            (color.g * 255.0) as u8,
-            (color.b * 255.0) as u8,
-        ]
+        // round rather than truncate, so this is the inverse of `From<[u8; 3]>`
+        let to_byte = |channel: f64| (channel * 255.0).round().clamp(0.0, 255.0) as u8;
+        [to_byte(color.r), to_byte(color.g), to_byte(color.b)]
     }
 }
 
@@ -145,12 +260,17 @@ impl From<Vec<Color>> for Color {
 }
 
 impl GammaCorrect for Color {
-    fn gamma_correct(self) -> Self {
-        Self {
-            r: self.r.sqrt(),
-            g: self.g.sqrt(),
-            b: self.b.sqrt(),
+    fn gamma_correct(self, gamma: f64) -> Self {
+        // a square root is much cheaper than the general power
+        if gamma == 2.0 {
+            return Self::new(self.r.sqrt(), self.g.sqrt(), self.b.sqrt());
         }
+        let exponent = 1.0 / gamma;
+        Self::new(
+            self.r.powf(exponent),
+            self.g.powf(exponent),
+            self.b.powf(exponent),
+        )
     }
 }
 
@@ -176,6 +296,48 @@ impl std::ops::Add for Color {
     }
 }
 
+impl std::ops::AddAssign for Color {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::Sub for Color {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            r: self.r - rhs.r,
+            g: self.g - rhs.g,
+            b: self.b - rhs.b,
+        }
+    }
+}
+
+impl std::ops::Sub<f64> for Color {
+    type Output = Self;
+
+    fn sub(self, rhs: f64) -> Self {
+        Self {
+            r: self.r - rhs,
+            g: self.g - rhs,
+            b: self.b - rhs,
+        }
+    }
+}
+
+impl std::ops::Sub<Color> for f64 {
+    type Output = Color;
+
+    fn sub(self, rhs: Color) -> Color {
+        Color {
+            r: self - rhs.r,
+            g: self - rhs.g,
+            b: self - rhs.b,
+        }
+    }
+}
+
 impl std::ops::Mul for Color {
     type Output = Self;
 
@@ -237,6 +399,21 @@ mod tests {
     use super::*;
     use test_case::test_case;
 
+    #[test]
+    fn test_phi() {
+        assert!((PHI * PHI - PHI - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_random_in_range() {
+        for _ in 0..1000 {
+            let color = Color::random();
+            for channel in [color.r, color.g, color.b] {
+                assert!((0.0..=1.0).contains(&channel), "{:?}", color);
+            }
+        }
+    }
+
     #[test]
     fn test_new() {
         let color = Color::new(0.1, 0.2, 0.3);
@@ -259,7 +436,36 @@ mod tests {
     fn test_from_color_for_u8_array() {
         let color = Color::new(0.0, 0.5, 1.0);
         let u8_array: [u8; 3] = color.into();
-        assert_eq!(u8_array, [0, 127, 255]);
+        assert_eq!(u8_array, [0, 128, 255]);
+
+        let out_of_range = Color::new(-0.2, 1.5, 0.999);
+        let u8_array: [u8; 3] = out_of_range.into();
+        assert_eq!(u8_array, [0, 255, 255]);
+    }
+
+    #[test]
+    fn test_tone_maps() {
+        for tone_map in [Color::reinhard, Color::aces_filmic] {
+            assert_eq!(tone_map(Color::BLACK), Color::BLACK);
+
+            let bright = tone_map(Color::new(1000.0, 50.0, 4.0));
+            for channel in [bright.r, bright.g, bright.b] {
+                assert!(channel > 0.5 && channel <= 1.0, "{:?}", bright);
+            }
+            // brighter stays brighter
+            assert!(bright.r >= bright.g && bright.g > bright.b);
+        }
+        assert!(Color::reinhard(Color::new(1000.0, 1000.0, 1000.0)).r < 1.0);
+        assert_eq!(Color::reinhard(Color::WHITE), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_u8_round_trip() {
+        for value in 0..=255 {
+            let color = Color::from([value, value, value]);
+            let u8_array: [u8; 3] = color.into();
+            assert_eq!(u8_array, [value, value, value]);
+        }
     }
 
     #[test]
@@ -298,11 +504,72 @@ mod tests {
         assert_eq!(color, Color::new(0.25, 0.75, 1.0));
     }
 
+    #[test]
+    fn test_from_hex() {
+        let red = Color::from_hex("#A50D0D").unwrap();
+        assert_eq!(red, Color::from([0xa5, 0x0d, 0x0d]).srgb_decode());
+        assert!((red.r - 0.3763).abs() < 1e-4 && (red.g - 0.0040).abs() < 1e-4);
+
+        assert_eq!(Color::from_hex("#fff").unwrap(), Color::WHITE);
+        assert_eq!(Color::from_hex("000").unwrap(), Color::BLACK);
+        assert_eq!(Color::from_hex("#abc"), Color::from_hex("#AABBCC"));
+        assert_eq!(Color::from_hex("#abc8"), Color::from_hex("#abc"));
+        assert_eq!(Color::from_hex("#A50D0D80"), Ok(red));
+        assert_eq!("#A50D0D".parse::<Color>(), Ok(red));
+    }
+
+    #[test]
+    fn test_from_malformed_hex() {
+        use ColorParseError::*;
+
+        assert_eq!(Color::from_hex(""), Err(InvalidLength(0)));
+        assert_eq!(Color::from_hex("#"), Err(InvalidLength(0)));
+        assert_eq!(Color::from_hex("#12345"), Err(InvalidLength(5)));
+        assert_eq!(Color::from_hex("#A50D0D8"), Err(InvalidLength(7)));
+        assert_eq!(Color::from_hex("#GG0000"), Err(InvalidDigit('G')));
+        assert_eq!(Color::from_hex("##fff"), Err(InvalidDigit('#')));
+        assert_eq!(Color::from_hex(" #fff"), Err(InvalidDigit(' ')));
+        assert_eq!(Color::from_hex("#ÿff"), Err(InvalidDigit('ÿ')));
+    }
+
     #[test]
     fn test_gamma_correct() {
         let color = Color::new(0.0, 0.25, 1.0);
-        let gamma_corrected = color.gamma_correct();
+        let gamma_corrected = color.gamma_correct(2.0);
         assert_eq!(gamma_corrected, Color::new(0.0, 0.5, 1.0));
+
+        let gamma_corrected = Color::new(0.0, 0.2, 1.0).gamma_correct(2.2);
+        assert!((gamma_corrected.g - 0.2_f64.powf(1.0 / 2.2)).abs() < 1e-12);
+        assert_eq!(gamma_corrected.b, 1.0);
+
+        let linear = Color::new(0.1, 0.25, 0.9);
+        assert_eq!(linear.gamma_correct(1.0), linear);
+    }
+
+    #[test]
+    fn test_srgb_reference_values() {
+        let encoded = Color::new(0.0, 0.0031308, 0.5).srgb_encode();
+        assert!((encoded.g - 0.0404500).abs() < 1e-6, "{:?}", encoded);
+        // 50% linear grey is 188 in 8 bits
+        assert!((encoded.b - 0.7353570).abs() < 1e-6, "{:?}", encoded);
+        assert_eq!(encoded.r, 0.0);
+        assert!((Color::new(1.0, 1.0, 1.0).srgb_encode().r - 1.0).abs() < 1e-12);
+
+        // 8-bit 128 is about 21.6% linear
+        let decoded = Color::new(128.0 / 255.0, 0.02, 1.0).srgb_decode();
+        assert!((decoded.r - 0.2158605).abs() < 1e-6, "{:?}", decoded);
+        assert!((decoded.g - 0.02 / 12.92).abs() < 1e-12, "{:?}", decoded);
+        assert!((decoded.b - 1.0).abs() < 1e-12, "{:?}", decoded);
+
+        for c in [0.0, 0.001, 0.0031308, 0.05, 0.2, 0.7, 1.0] {
+            let round_trip = Color::new(c, c, c).srgb_encode().srgb_decode();
+            assert!(
+                (round_trip.r - c).abs() < 1e-12,
+                "{} != {}",
+                round_trip.r,
+                c
+            );
+        }
     }
 
     #[test]
@@ -320,6 +587,35 @@ mod tests {
         assert_eq!(added, Color::new(0.5, 1.0, 2.0));
     }
 
+    #[test]
+    fn test_add_assign() {
+        let mut color = Color::new(0.0, 0.25, 1.0);
+        color += Color::new(0.5, 0.75, 1.0);
+        assert_eq!(color, Color::new(0.5, 1.0, 2.0));
+    }
+
+    #[test]
+    fn test_sub() {
+        let color1 = Color::new(0.5, 0.75, 1.0);
+        let color2 = Color::new(0.0, 0.25, 1.0);
+        let subtracted = color1 - color2;
+        assert_eq!(subtracted, Color::new(0.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn test_sub_f64_for_color() {
+        let color = Color::new(0.5, 0.75, 1.0);
+        let subtracted = color - 0.25;
+        assert_eq!(subtracted, Color::new(0.25, 0.5, 0.75));
+    }
+
+    #[test]
+    fn test_sub_color_for_f64() {
+        let color = Color::new(0.5, 0.75, 1.0);
+        let subtracted = 1.0 - color;
+        assert_eq!(subtracted, Color::new(0.5, 0.25, 0.0));
+    }
+
     #[test]
     fn test_mul() {
         let color1 = Color::new(0.0, 0.25, 1.0);
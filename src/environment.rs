@@ -0,0 +1,245 @@
+use crate::color::Color;
+use crate::pdf::Pdf;
+use crate::rng;
+use crate::texture::Image;
+use crate::vector::Vector;
+use std::f64::consts::PI;
+
+/// Importance sampler for an equirectangular environment image, which picks pixels in proportion
+/// to their luminance so bright regions like the sun are sampled far more often than dark sky.
+///
+/// Rows are weighted by `sin(theta)` so the poles, which cover less solid angle than their pixel
+/// count suggests, aren't oversampled. As a `Pdf` it picks directions uniformly inside the
+/// chosen pixel, so diffuse bounces can send rays straight towards the bright parts of the sky.
+#[derive(Debug)]
+pub struct EnvironmentSampler {
+    width: u32,
+    height: u32,
+    /// Cumulative distribution over rows, `height + 1` entries from 0 to 1
+    marginal: Vec<f64>,
+    /// Cumulative distribution over columns for each row, `width + 1` entries per row
+    conditional: Vec<f64>,
+}
+
+impl EnvironmentSampler {
+    pub fn new(pixels: &[Color], width: u32, height: u32) -> Self {
+        debug_assert_eq!(pixels.len(), (width * height) as usize);
+
+        let (w, h) = (width as usize, height as usize);
+        let mut marginal = Vec::with_capacity(h + 1);
+        let mut conditional = Vec::with_capacity(h * (w + 1));
+        marginal.push(0.0);
+
+        for row in pixels.chunks(w) {
+            let y = conditional.len() / (w + 1);
+            let sin_theta = ((y as f64 + 0.5) / height as f64 * PI).sin();
+
+            let weights = row
+                .iter()
+                .map(|color| color.luminance().max(0.0) * sin_theta);
+            let row_total = push_cdf(&mut conditional, weights);
+            marginal.push(marginal[y] + row_total);
+        }
+        normalize_cdf(&mut marginal);
+
+        Self {
+            width,
+            height,
+            marginal,
+            conditional,
+        }
+    }
+
+    /// Sampler for an equirectangular environment image, laid out the way
+    /// `Background::Environment` wraps it around the scene
+    pub fn for_image(image: &Image) -> Self {
+        Self::new(&image.data, image.width, image.height)
+    }
+
+    /// Picks a pixel from two uniform random numbers in `[0, 1)`
+    pub fn sample(&self, u: f64, v: f64) -> (u32, u32) {
+        let y = find_interval(&self.marginal, u);
+        let x = find_interval(self.row_cdf(y), v);
+        (x as u32, y as u32)
+    }
+
+    /// Probability that `sample` returns the pixel at `(x, y)`
+    pub fn probability(&self, x: u32, y: u32) -> f64 {
+        let (x, y) = (x as usize, y as usize);
+        let row = self.row_cdf(y);
+        (self.marginal[y + 1] - self.marginal[y]) * (row[x + 1] - row[x])
+    }
+
+    /// Pixel of the image that `direction` points into, with +y straight up
+    fn pixel_at(&self, direction: &Vector) -> (u32, u32) {
+        let direction = direction.normalize();
+        let theta = direction.y.clamp(-1.0, 1.0).acos();
+        let phi = direction.z.atan2(direction.x).rem_euclid(2.0 * PI);
+        let x = (phi / (2.0 * PI) * self.width as f64) as u32;
+        let y = (theta / PI * self.height as f64) as u32;
+        (x.min(self.width - 1), y.min(self.height - 1))
+    }
+
+    fn row_cdf(&self, y: usize) -> &[f64] {
+        let stride = self.width as usize + 1;
+        &self.conditional[y * stride..(y + 1) * stride]
+    }
+}
+
+impl Pdf for EnvironmentSampler {
+    fn value(&self, direction: &Vector) -> f64 {
+        let sin_theta = (1.0 - direction.normalize().y.powi(2)).max(0.0).sqrt();
+        // a pixel spans π / height of polar angle and 2π / width of azimuth
+        let pixel_area = 2.0 * PI * PI / (self.width as f64 * self.height as f64);
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let (x, y) = self.pixel_at(direction);
+        self.probability(x, y) / (pixel_area * sin_theta)
+    }
+
+    fn generate(&self) -> Vector {
+        let (x, y) = self.sample(rng::random(), rng::random());
+        let theta = (y as f64 + rng::random::<f64>()) / self.height as f64 * PI;
+        let phi = (x as f64 + rng::random::<f64>()) / self.width as f64 * 2.0 * PI;
+        Vector::new(
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin(),
+        )
+    }
+}
+
+/// Appends the normalized cumulative distribution of `weights` to `cdf` and returns their total
+fn push_cdf(cdf: &mut Vec<f64>, weights: impl Iterator<Item = f64>) -> f64 {
+    let start = cdf.len();
+    cdf.push(0.0);
+    for weight in weights {
+        let last = cdf[cdf.len() - 1];
+        cdf.push(last + weight);
+    }
+    let total = cdf[cdf.len() - 1];
+    normalize_cdf(&mut cdf[start..]);
+    total
+}
+
+/// Scales a cumulative distribution to end at 1, falling back to uniform if everything is zero
+fn normalize_cdf(cdf: &mut [f64]) {
+    let total = cdf[cdf.len() - 1];
+    let count = (cdf.len() - 1) as f64;
+    for (i, value) in cdf.iter_mut().enumerate() {
+        *value = if total > 0.0 {
+            *value / total
+        } else {
+            i as f64 / count
+        };
+    }
+}
+
+/// Index of the interval `[cdf[i], cdf[i + 1])` containing `u`, skipping zero-width intervals
+fn find_interval(cdf: &[f64], u: f64) -> usize {
+    let index = cdf.partition_point(|&value| value <= u);
+    index.clamp(1, cdf.len() - 1) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dark_environment_with_sun(width: u32, height: u32, sun: (u32, u32)) -> Vec<Color> {
+        let mut pixels = vec![Color::new(0.01, 0.01, 0.01); (width * height) as usize];
+        pixels[(sun.1 * width + sun.0) as usize] = Color::new(1000.0, 1000.0, 1000.0);
+        pixels
+    }
+
+    #[test]
+    fn test_sampling_concentrates_on_bright_pixel() {
+        let (width, height, sun) = (32, 16, (20, 5));
+        let sampler = EnvironmentSampler::new(
+            &dark_environment_with_sun(width, height, sun),
+            width,
+            height,
+        );
+
+        assert!(sampler.probability(sun.0, sun.1) > 0.9);
+        assert!(sampler.probability(0, 8) < 0.001);
+
+        let samples = 1000;
+        let hits = (0..samples)
+            .map(|i| {
+                let u = (i as f64 + 0.5) / samples as f64;
+                sampler.sample(u, (u * 7.0) % 1.0)
+            })
+            .filter(|&pixel| pixel == sun)
+            .count();
+        assert!(hits > 900, "{} of {} samples hit the sun", hits, samples);
+    }
+
+    #[test]
+    fn test_probabilities_sum_to_one() {
+        let (width, height) = (8, 4);
+        let sampler = EnvironmentSampler::new(
+            &dark_environment_with_sun(width, height, (3, 1)),
+            width,
+            height,
+        );
+
+        let total: f64 = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| sampler.probability(x, y))
+            .sum();
+        assert!((total - 1.0).abs() < 1e-9, "{}", total);
+    }
+
+    #[test]
+    fn test_black_environment_samples_uniformly() {
+        let sampler = EnvironmentSampler::new(&[Color::BLACK; 8], 4, 2);
+
+        assert!((sampler.probability(0, 0) - 0.125).abs() < 1e-12);
+        assert_eq!(sampler.sample(0.99, 0.99), (3, 1));
+    }
+
+    #[test]
+    fn test_pdf_matches_generated_directions() {
+        let (width, height, sun) = (32, 16, (20, 5));
+        let pixels = dark_environment_with_sun(width, height, sun);
+        let sampler = EnvironmentSampler::new(&pixels, width, height);
+
+        // luminance over pdf averages out to the integral of the luminance over the sphere
+        let luminance = |direction: &Vector| {
+            let (x, y) = sampler.pixel_at(direction);
+            pixels[(y * width + x) as usize].luminance()
+        };
+        let exact: f64 = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let band = ((y as f64 / height as f64 * PI).cos()
+                    - ((y + 1) as f64 / height as f64 * PI).cos())
+                    * 2.0
+                    * PI
+                    / width as f64;
+                pixels[(y * width + x) as usize].luminance() * band
+            })
+            .sum();
+        let samples = 20_000;
+        let estimate = rng::with_seed(3, || {
+            (0..samples)
+                .map(|_| {
+                    let direction = sampler.generate();
+                    luminance(&direction) / sampler.value(&direction)
+                })
+                .sum::<f64>()
+                / samples as f64
+        });
+        assert!(
+            (estimate - exact).abs() < 0.02 * exact,
+            "{} != {}",
+            estimate,
+            exact
+        );
+
+        // the sun's pixel is where the generated directions point
+        let direction = rng::with_seed(4, || sampler.generate());
+        assert_eq!(sampler.pixel_at(&direction), sun);
+    }
+}
@@ -0,0 +1,23 @@
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
+pub mod aabb;
+pub mod animation;
+pub mod annotate;
+pub mod camera;
+pub mod color;
+pub mod data;
+pub mod environment;
+pub mod material;
+pub mod mesh;
+pub mod object;
+pub mod output;
+pub mod pdf;
+pub mod perlin;
+pub mod quaternion;
+pub mod ray;
+pub mod renderer;
+pub mod rng;
+pub mod settings;
+pub mod texture;
+pub mod vector;
+pub mod world;
@@ -1,21 +1,7 @@
-#![feature(more_float_constants)]
-
-mod color;
-mod data;
-mod material;
-mod object;
-mod perlin;
-mod ray;
-mod renderer;
-mod settings;
-mod texture;
-mod vector;
-mod world;
-mod quaternion;
-
 use humanize_duration::prelude::DurationExt;
 use humanize_duration::Truncate;
 use std::error::Error;
+use std::time::Duration;
 
 #[cfg(not(feature = "gui"))]
 use clap::error::ErrorKind;
@@ -23,29 +9,54 @@ use clap::error::ErrorKind;
 use clap::{CommandFactory, Parser};
 #[cfg(not(feature = "gui"))]
 use regex::Regex;
+#[cfg(not(feature = "gui"))]
+use std::io::IsTerminal;
 
 #[cfg(feature = "gui")]
 use eframe::egui;
 #[cfg(feature = "gui")]
-use log::{info, warn};
+use log::{error, info, warn};
 #[cfg(feature = "gui")]
 use single_value_channel::{Receiver, Updater};
 #[cfg(feature = "gui")]
-use std::thread::JoinHandle;
+use std::sync::Arc;
 #[cfg(feature = "gui")]
-use std::time::Duration;
+use std::thread::JoinHandle;
 #[cfg(feature = "gui")]
 use uuid::Uuid;
 
-use crate::renderer::render;
-use crate::settings::RenderSettings;
-use crate::world::{get_scene_camera, Scene};
+use raytracing::renderer::{render, ColorSpace, NormalSpace, RenderPass, ToneMap};
+use raytracing::settings::RenderSettings;
+use raytracing::world::{get_scene_camera, Scene, WorldCache};
 
 #[cfg(feature = "gui")]
-use crate::settings::{load_settings, save_settings};
+use raytracing::camera::Camera;
+#[cfg(feature = "gui")]
+use raytracing::color::Color;
+#[cfg(feature = "gui")]
+use raytracing::material::{Dielectric, Lambertian, Light, Material, Metal};
+#[cfg(feature = "gui")]
+use raytracing::renderer::RenderError;
+#[cfg(feature = "gui")]
+use raytracing::settings::{load_settings, save_settings};
+#[cfg(feature = "gui")]
+use raytracing::texture::Texture;
 
 #[cfg(not(feature = "gui"))]
-use crate::vector::Point;
+use raytracing::animation::{render_animated_gif, render_animation};
+#[cfg(not(feature = "gui"))]
+use raytracing::output::{write_output, OutputFormat, STDOUT_TARGET};
+#[cfg(not(feature = "gui"))]
+use raytracing::quaternion::Quaternion;
+#[cfg(not(feature = "gui"))]
+use raytracing::renderer::RenderOutput;
+#[cfg(not(feature = "gui"))]
+use raytracing::settings::CameraSettings;
+#[cfg(not(feature = "gui"))]
+use raytracing::vector::Point;
+
+/// Exponents the gamma color space accepts
+const GAMMA_RANGE: std::ops::RangeInclusive<f64> = 0.1..=5.0;
 
 /// Software raytracer
 #[cfg(not(feature = "gui"))]
@@ -53,7 +64,7 @@ use crate::vector::Point;
 #[command(version, about, long_about = None)]
 struct Args {
     /// Scene to render
-    #[arg(short, long, default_value = "cornell-box-empty")]
+    #[arg(long, default_value = "cornell-box-empty")]
     scene: Scene,
 
     /// Camera position
@@ -64,8 +75,20 @@ struct Args {
     #[arg(short, long)]
     focus_point: Option<String>,
 
+    /// Vertical field of view in degrees [default: the scene's]
+    #[arg(long)]
+    fov: Option<f32>,
+
+    /// Angle in degrees of the cone of rays through each pixel, for depth of field (0 is off)
+    #[arg(long)]
+    defocus_angle: Option<f32>,
+
+    /// Distance from the camera to the plane that's in perfect focus
+    #[arg(long)]
+    focus_distance: Option<f32>,
+
     /// Render height
-    #[arg(short, long)]
+    #[arg(long)]
     height: Option<u32>,
 
     /// Render width
@@ -76,10 +99,86 @@ struct Args {
     #[arg(short, long)]
     samples: Option<u32>,
 
-    /// Output file
+    /// Brightness multiplier for the background/sky light
+    #[arg(long)]
+    environment_intensity: Option<f32>,
+
+    /// Seed for the random numbers, so the same settings always give the same image
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Only render the pixels from x0,y0 up to x1,y1, leaving the rest of the image black
+    #[arg(long, value_name = "X0,Y0,X1,Y1", value_parser = parse_region)]
+    region: Option<(u32, u32, u32, u32)>,
+
+    /// Output file, or - to write the image to stdout
     #[arg(short, long, default_value = "render.png")]
     output: String,
 
+    /// Output format [default: from the output file's extension, or png]
+    #[arg(long)]
+    format: Option<OutputFormat>,
+
+    /// Quality of JPEG output, from 1 to 100
+    #[arg(long)]
+    jpeg_quality: Option<u8>,
+
+    /// Render pass
+    #[arg(long, default_value = "beauty")]
+    pass: RenderPass,
+
+    /// Coordinate system for the normal pass
+    #[arg(long, default_value = "world")]
+    normal_space: NormalSpace,
+
+    /// Write the beauty, normal, depth, and albedo passes as layers of one OpenEXR file
+    #[arg(long)]
+    exr_layers: bool,
+
+    /// Burn the scene name, sample count, and date into a corner of the image
+    #[arg(long)]
+    annotate: bool,
+
+    /// Dither the 8-bit output to reduce banding in smooth gradients
+    #[arg(long)]
+    dither: bool,
+
+    /// Make the background transparent in PNG output, for compositing
+    #[arg(long)]
+    alpha: bool,
+
+    /// Also write normalized depth and normal images of the first hits, next to the output as
+    /// NAME.depth.png and NAME.normal.png
+    #[arg(long)]
+    aov: bool,
+
+    /// Write color passes without gamma correction, for renders used as data
+    #[arg(long, alias = "no-gamma")]
+    linear: bool,
+
+    /// Tone mapping for bright color passes in 8-bit images
+    #[arg(long, default_value = "none")]
+    tone_map: ToneMap,
+
+    /// Gamma correction for color passes in 8-bit images: the exact sRGB curve, or a power
+    /// curve with the --gamma exponent [default: gamma with --gamma, or srgb]
+    #[arg(long)]
+    color_space: Option<ColorSpace>,
+
+    /// Exponent of the gamma color space, from 0.1 to 5, to match a particular display. Implies
+    /// --color-space gamma.
+    #[arg(long, value_parser = parse_gamma)]
+    gamma: Option<f64>,
+
+    /// Render this many frames orbiting the camera once around the focus point, as numbered
+    /// files in a directory named after the output file, or as one animated file for GIF output
+    #[arg(long, visible_alias = "frames", value_parser = clap::value_parser!(u32).range(1..))]
+    turntable: Option<u32>,
+
+    /// Milliseconds each frame of an animated GIF is shown for
+    #[arg(long, default_value_t = 40)]
+    frame_delay: u64,
+
     /// Print settings
     /// Print the settings and exit
     #[arg(short, long)]
@@ -111,14 +210,193 @@ fn main() -> Result<(), Box<dyn Error>> {
 #[cfg(not(feature = "gui"))]
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
+    let settings = settings_from_args(&args)?;
+
+    if args.print_settings {
+        println!("{:#?}", settings);
+        return Ok(());
+    }
+
+    if args.aov && args.output == STDOUT_TARGET {
+        let mut cmd = Args::command();
+        cmd.error(
+            ErrorKind::ArgumentConflict,
+            "Depth and normal images need an output file to be written next to",
+        )
+        .exit()
+    }
+
+    let start = std::time::Instant::now();
+    if let Some(frames) = args.turntable {
+        let animated = settings.output_format == OutputFormat::Gif && !settings.exr_layers;
+        if args.output == STDOUT_TARGET && !animated {
+            let mut cmd = Args::command();
+            cmd.error(
+                ErrorKind::ArgumentConflict,
+                "A turntable needs an output file to number the frames after",
+            )
+            .exit()
+        }
+
+        if args.aov && animated {
+            let mut cmd = Args::command();
+            cmd.error(
+                ErrorKind::ArgumentConflict,
+                "An animated GIF has no depth and normal images to go with it",
+            )
+            .exit()
+        }
+
+        let camera_path = |t| CameraSettings {
+            camera_position: turntable_camera(&settings, t),
+            focus_point: settings.focus_point,
+            up: settings.up,
+            field_of_view: settings.field_of_view,
+        };
+        let progress = progress_bar(format!("{} frames ", frames));
+        if animated {
+            let delay = Duration::from_millis(args.frame_delay);
+            let progress = terminal_progress(&progress);
+            let gif = render_animated_gif(settings.clone(), frames, camera_path, delay, progress)?;
+            write_output(&args.output, &gif, std::io::stdout().lock())?;
+        } else {
+            let out_dir = std::path::Path::new(&args.output).with_extension("");
+            let progress = terminal_progress(&progress);
+            render_animation(settings.clone(), frames, camera_path, &out_dir, progress)?;
+        }
+    } else {
+        let progress = progress_bar(String::new());
+        let rendered = render(
+            settings,
+            &WorldCache::default(),
+            terminal_progress(&progress),
+        )?;
+        write_render(&args.output, &rendered)?;
+    }
+    let duration = start.elapsed();
+    if args.output == STDOUT_TARGET {
+        // stdout carries the image, so keep it clean
+        eprintln!("Render time: {}", duration.human(Truncate::Millis));
+    } else {
+        println!("Render time: {}", duration.human(Truncate::Millis));
+    }
+    Ok(())
+}
+
+/// Redraws a progress bar with the time left on stderr for each update of a render
+#[cfg(not(feature = "gui"))]
+fn progress_bar(label: String) -> impl Fn(f32) + Sync {
+    let start = std::time::Instant::now();
+    move |fraction| {
+        eprint!("\r{}", format_progress(&label, fraction, start.elapsed()));
+        if fraction >= 1.0 {
+            eprintln!();
+        }
+    }
+}
+
+/// The progress bar, only when stderr is a terminal to draw it on rather than a log file
+#[cfg(not(feature = "gui"))]
+fn terminal_progress(progress: &(dyn Fn(f32) + Sync)) -> Option<&(dyn Fn(f32) + Sync)> {
+    std::io::stderr().is_terminal().then_some(progress)
+}
+
+/// One line of the progress bar. The time left assumes the rest of the image renders as fast as
+/// what is already done.
+#[cfg(not(feature = "gui"))]
+fn format_progress(label: &str, fraction: f32, elapsed: Duration) -> String {
+    const WIDTH: usize = 30;
+
+    let fraction = fraction.clamp(0.0, 1.0);
+    let filled = (fraction * WIDTH as f32).round() as usize;
+    let eta = match fraction > 0.0 {
+        true => elapsed
+            .mul_f32((1.0 - fraction) / fraction)
+            .human(Truncate::Second)
+            .to_string(),
+        false => "?".to_string(),
+    };
+    format!(
+        "{}[{}{}] {:>3}% ETA {}",
+        label,
+        "#".repeat(filled),
+        " ".repeat(WIDTH - filled),
+        (fraction * 100.0).floor(),
+        eta
+    )
+}
+
+/// Writes the render to `output`, and any depth and normal images next to it
+#[cfg(not(feature = "gui"))]
+fn write_render(output: &str, rendered: &RenderOutput) -> std::io::Result<()> {
+    write_output(output, &rendered.image, std::io::stdout().lock())?;
+    for (aov, bytes) in [("depth", &rendered.depth), ("normal", &rendered.normal)] {
+        if let Some(bytes) = bytes {
+            write_output(&aov_output(output, aov), bytes, std::io::stdout().lock())?;
+        }
+    }
+    Ok(())
+}
+
+/// `render.png` becomes `render.depth.png` for the depth image
+#[cfg(not(feature = "gui"))]
+fn aov_output(output: &str, aov: &str) -> String {
+    let path = std::path::Path::new(output);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}.png", stem, aov))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Camera position `t` of the way around a turntable, turned about the camera's up axis through
+/// the focus point, keeping its distance and height
+#[cfg(not(feature = "gui"))]
+fn turntable_camera(settings: &RenderSettings, t: f64) -> Point {
+    let angle = t * std::f64::consts::TAU;
+    let rotation = Quaternion::from_axis_angle(settings.up.normalize(), angle);
+    settings.focus_point + rotation.rotate_point(settings.camera_position - settings.focus_point)
+}
+
+/// Parses `--region` as four comma-separated pixel coordinates, `x0,y0,x1,y1`
+#[cfg(not(feature = "gui"))]
+fn parse_region(region: &str) -> Result<(u32, u32, u32, u32), String> {
+    let parts = region
+        .split(',')
+        .map(|part| part.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    match parts[..] {
+        [x0, y0, x1, y1] => Ok((x0, y0, x1, y1)),
+        _ => Err(format!("expected 4 coordinates, got {}", parts.len())),
+    }
+}
+
+/// Parses `--gamma`, keeping it within `GAMMA_RANGE`
+#[cfg(not(feature = "gui"))]
+fn parse_gamma(gamma: &str) -> Result<f64, String> {
+    let gamma: f64 = gamma.trim().parse().map_err(|e| format!("{}", e))?;
+    if !GAMMA_RANGE.contains(&gamma) {
+        return Err(format!(
+            "must be between {} and {}",
+            GAMMA_RANGE.start(),
+            GAMMA_RANGE.end()
+        ));
+    }
+    Ok(gamma)
+}
+
+/// The scene's default camera and settings, overridden by whatever was given on the command line
+#[cfg(not(feature = "gui"))]
+fn settings_from_args(args: &Args) -> Result<RenderSettings, Box<dyn Error>> {
     let mut settings = RenderSettings {
-        scene: args.scene,
+        scene: args.scene.clone(),
         ..Default::default()
     };
 
     let scene_camera = get_scene_camera(&settings.scene);
     settings.camera_position = scene_camera.camera_position;
     settings.focus_point = scene_camera.focus_point;
+    settings.up = scene_camera.up;
     settings.field_of_view = scene_camera.field_of_view;
 
     let point_re = Regex::new(r"\(?(?:\d+(?:\.\d+)?,\s?){2}(?:\d+(?:\.\d+)?)\)?")?;
@@ -136,7 +414,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    if let Some(focus_point) = args.camera_position.as_deref() {
+    if let Some(focus_point) = args.focus_point.as_deref() {
         if point_re.is_match(focus_point) {
             let mut parts = focus_point.split(',');
             let x = parts.next().unwrap().parse::<f64>()?;
@@ -150,6 +428,18 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
+    if let Some(fov) = args.fov {
+        settings.field_of_view = fov;
+    }
+
+    if let Some(defocus_angle) = args.defocus_angle {
+        settings.defocus_angle = defocus_angle;
+    }
+
+    if let Some(focus_distance) = args.focus_distance {
+        settings.focus_distance = focus_distance;
+    }
+
     if let Some(height) = args.height {
         settings.size.height = height;
     }
@@ -162,29 +452,63 @@ fn main() -> Result<(), Box<dyn Error>> {
         settings.samples = samples;
     }
 
-    if args.print_settings {
-        println!("{:#?}", settings);
-        return Ok(());
+    if let Some(environment_intensity) = args.environment_intensity {
+        settings.environment_intensity = environment_intensity;
     }
 
-    let start = std::time::Instant::now();
-    let image = render(settings);
-    let duration = start.elapsed();
-    std::fs::write(args.output, &image)?;
-    println!("Render time: {}", duration.human(Truncate::Millis));
-    Ok(())
+    settings.seed = args.seed;
+    settings.region = args.region;
+    settings.annotate = args.annotate;
+    settings.dither = args.dither;
+    settings.alpha = args.alpha;
+    settings.aov = args.aov;
+    settings.linear = args.linear;
+    settings.tone_map = args.tone_map;
+    settings.color_space = match (args.color_space, args.gamma) {
+        (Some(ColorSpace::Srgb), Some(_)) => {
+            return Err("--gamma only applies to --color-space gamma".into())
+        }
+        (Some(color_space), _) => color_space,
+        (None, Some(_)) => ColorSpace::Gamma,
+        (None, None) => ColorSpace::Srgb,
+    };
+    if let Some(gamma) = args.gamma {
+        settings.gamma = gamma;
+    }
+    settings.output_format = args
+        .format
+        .or_else(|| OutputFormat::from_extension(&args.output))
+        .unwrap_or_default();
+    if let Some(jpeg_quality) = args.jpeg_quality {
+        settings.jpeg_quality = jpeg_quality;
+    }
+    settings.pass = args.pass;
+    settings.normal_space = args.normal_space;
+    settings.exr_layers = args.exr_layers;
+
+    Ok(settings)
 }
 
+/// The encoded image and how long it took to render, or why the render failed
+#[cfg(feature = "gui")]
+type RenderResult = Result<(Vec<u8>, Duration), RenderError>;
+
 #[cfg(feature = "gui")]
 #[cfg(not(tarpaulin_include))]
 struct RaytracerApp {
     image: Vec<u8>,
     image_id: Uuid,
     render_settings: RenderSettings,
-    render_handle: Option<JoinHandle<(Vec<u8>, Duration)>>,
+    render_handle: Option<JoinHandle<RenderResult>>,
     duration: Option<Duration>,
+    /// Why the last render failed, shown in place of its render time
+    render_error: Option<String>,
     progress_updater: Updater<f32>,
     progress: Receiver<f32>,
+    worlds: Arc<WorldCache>,
+    /// Settings of the image on screen, to find out which shape was clicked
+    rendered_settings: Option<RenderSettings>,
+    material_editor: Option<MaterialEditor>,
 }
 
 #[cfg(feature = "gui")]
@@ -198,10 +522,30 @@ impl RaytracerApp {
             render_settings: settings,
             render_handle: None,
             duration: None,
+            render_error: None,
             progress_updater: updater,
             progress: receiver,
+            worlds: Arc::new(WorldCache::default()),
+            rendered_settings: None,
+            material_editor: None,
         }
     }
+
+    /// Opens the material editor for the shape under `pos`, a point on the image shown in `rect`
+    fn pick(&mut self, pos: egui::Pos2, rect: egui::Rect) {
+        let Some(settings) = &self.rendered_settings else {
+            return;
+        };
+        let (width, height) = (settings.size.width, settings.size.height);
+        let x = ((pos.x - rect.min.x) / rect.width() * width as f32) as u32;
+        let y = ((pos.y - rect.min.y) / rect.height() * height as f32) as u32;
+        let ray = Camera::new(settings).get_center_ray(x.min(width - 1), y.min(height - 1));
+
+        let world = self.worlds.get(&settings.scene);
+        self.material_editor = world.pick(&ray).map(|index| {
+            MaterialEditor::new(settings.scene.clone(), index, world.materials()[index])
+        });
+    }
 }
 
 #[cfg(feature = "gui")]
@@ -216,10 +560,206 @@ impl Default for RaytracerApp {
             render_settings: RenderSettings::default(),
             render_handle: None,
             duration: None,
+            render_error: None,
             progress_updater: updater,
             progress: receiver,
+            worlds: Arc::new(WorldCache::default()),
+            rendered_settings: None,
+            material_editor: None,
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, Copy, PartialEq, strum_macros::Display)]
+enum MaterialKind {
+    Lambertian,
+    Metal,
+    Dielectric,
+    Light,
+}
+
+/// The material of the last shape clicked in the render, as edited in the material panel
+#[cfg(feature = "gui")]
+#[cfg(not(tarpaulin_include))]
+struct MaterialEditor {
+    /// Scene the shape was picked in, since `index` means a different shape in any other
+    scene: Scene,
+    index: usize,
+    kind: MaterialKind,
+    color: [f32; 3],
+    fuzz: f32,
+    anisotropy: f32,
+    refraction_index: f32,
+    /// Absorption coefficients of tinted glass, which can go past the color picker's 1.0
+    absorption: Option<[f32; 3]>,
+    /// Lights multiply their color by this, since the color picker stops at 1.0
+    brightness: f32,
+}
+
+#[cfg(feature = "gui")]
+#[cfg(not(tarpaulin_include))]
+impl MaterialEditor {
+    fn new(scene: Scene, index: usize, material: &Material) -> Self {
+        let rgb = |color: Color| [color.r as f32, color.g as f32, color.b as f32];
+        let mut editor = MaterialEditor {
+            scene,
+            index,
+            kind: MaterialKind::Lambertian,
+            color: [0.5, 0.5, 0.5],
+            fuzz: 0.0,
+            anisotropy: 0.0,
+            refraction_index: 1.5,
+            absorption: None,
+            brightness: 1.0,
+        };
+        match material {
+            Material::Lambertian(lambertian) => editor.color = rgb(lambertian.albedo),
+            Material::Metal(metal) => {
+                editor.kind = MaterialKind::Metal;
+                editor.color = rgb(metal.albedo);
+                editor.fuzz = metal.fuzz as f32;
+                editor.anisotropy = metal.anisotropy as f32;
+            }
+            Material::Dielectric(dielectric) => {
+                editor.kind = MaterialKind::Dielectric;
+                editor.refraction_index = dielectric.refraction_index as f32;
+                editor.absorption = dielectric.absorption.map(rgb);
+                editor.fuzz = dielectric.fuzz as f32;
+            }
+            Material::Light(light) => {
+                // textured lights start out white
+                let color = match &light.texture {
+                    Texture::Solid(solid) => solid.color,
+                    _ => Color::WHITE,
+                } * light.intensity;
+                let brightness = color.r.max(color.g).max(color.b);
+                editor.kind = MaterialKind::Light;
+                if brightness > 0.0 {
+                    editor.color = rgb(color / brightness);
+                    editor.brightness = brightness as f32;
+                }
+            }
+            // textured materials and media start out as plain gray
+            _ => {}
+        }
+        editor
+    }
+
+    fn material(&self) -> Material {
+        let [r, g, b] = self.color.map(f64::from);
+        let color = Color::new(r, g, b);
+        match self.kind {
+            MaterialKind::Lambertian => Material::Lambertian(Lambertian { albedo: color }),
+            MaterialKind::Metal => Material::Metal(Metal {
+                albedo: color,
+                fuzz: self.fuzz as f64,
+                anisotropy: self.anisotropy as f64,
+            }),
+            MaterialKind::Dielectric => Material::Dielectric(Dielectric {
+                refraction_index: self.refraction_index as f64,
+                absorption: self
+                    .absorption
+                    .map(|[r, g, b]| Color::new(r as f64, g as f64, b as f64)),
+                fuzz: self.fuzz as f64,
+            }),
+            MaterialKind::Light => Material::Light(Light::new(color * self.brightness as f64)),
         }
     }
+
+    fn show(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Shape {}", self.index));
+        egui::Grid::new("material_editor")
+            .num_columns(2)
+            .spacing([40.0, 4.0])
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Type");
+                egui::ComboBox::from_id_source("material_kind")
+                    .selected_text(self.kind.to_string())
+                    .show_ui(ui, |ui| {
+                        for kind in [
+                            MaterialKind::Lambertian,
+                            MaterialKind::Metal,
+                            MaterialKind::Dielectric,
+                            MaterialKind::Light,
+                        ] {
+                            ui.selectable_value(&mut self.kind, kind, kind.to_string());
+                        }
+                    });
+                ui.end_row();
+
+                match self.kind {
+                    MaterialKind::Lambertian | MaterialKind::Metal => {
+                        ui.label("Albedo");
+                        ui.color_edit_button_rgb(&mut self.color);
+                        ui.end_row();
+                    }
+                    MaterialKind::Dielectric => {
+                        ui.label("Refraction index");
+                        ui.add(
+                            egui::DragValue::new(&mut self.refraction_index)
+                                .clamp_range(1.0..=3.0)
+                                .speed(0.01),
+                        );
+                        ui.end_row();
+
+                        ui.label("Absorption");
+                        ui.horizontal(|ui| {
+                            let mut tinted = self.absorption.is_some();
+                            if ui.checkbox(&mut tinted, "").changed() {
+                                self.absorption = tinted.then_some([0.0; 3]);
+                            }
+                            if let Some(absorption) = self.absorption.as_mut() {
+                                for (label, value) in ["R:", "G:", "B:"].into_iter().zip(absorption)
+                                {
+                                    ui.label(label);
+                                    ui.add(
+                                        egui::DragValue::new(value)
+                                            .clamp_range(0.0..=f32::MAX)
+                                            .speed(0.01),
+                                    );
+                                }
+                            }
+                        });
+                        ui.end_row();
+                    }
+                    MaterialKind::Light => {
+                        ui.label("Color");
+                        ui.color_edit_button_rgb(&mut self.color);
+                        ui.end_row();
+
+                        ui.label("Brightness");
+                        ui.add(
+                            egui::DragValue::new(&mut self.brightness)
+                                .clamp_range(0.0..=f32::MAX)
+                                .speed(0.1),
+                        );
+                        ui.end_row();
+                    }
+                }
+
+                if matches!(self.kind, MaterialKind::Metal | MaterialKind::Dielectric) {
+                    ui.label("Fuzz");
+                    ui.add(
+                        egui::DragValue::new(&mut self.fuzz)
+                            .clamp_range(0.0..=1.0)
+                            .speed(0.01),
+                    );
+                    ui.end_row();
+                }
+
+                if self.kind == MaterialKind::Metal {
+                    ui.label("Anisotropy");
+                    ui.add(
+                        egui::DragValue::new(&mut self.anisotropy)
+                            .clamp_range(-1.0..=1.0)
+                            .speed(0.01),
+                    );
+                    ui.end_row();
+                }
+            });
+    }
 }
 
 #[cfg(feature = "gui")]
@@ -227,12 +767,29 @@ impl Default for RaytracerApp {
 impl eframe::App for RaytracerApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         if self.render_handle.is_some() && self.render_handle.as_ref().unwrap().is_finished() {
-            let render_result = self.render_handle.take().unwrap().join().unwrap();
-            self.image = render_result.0;
-            self.duration = Some(render_result.1);
+            // panics inside tiles are caught by the renderer, but building the world or encoding
+            // the image can still take the thread down, and that shouldn't close the app
+            match self.render_handle.take().unwrap().join() {
+                Ok(Ok((image, duration))) => {
+                    self.image = image;
+                    self.duration = Some(duration);
+                    info!("Render complete");
+                }
+                Ok(Err(e)) => {
+                    self.rendered_settings = None;
+                    self.material_editor = None;
+                    self.duration = None;
+                    error!("Render failed: {}", e);
+                    self.render_error = Some(e.to_string());
+                }
+                Err(_) => {
+                    self.rendered_settings = None;
+                    self.material_editor = None;
+                    self.duration = None;
+                    error!("Render failed, see the panic message above");
+                }
+            }
             self.image_id = Uuid::new_v4();
-            self.render_handle = None;
-            info!("Render complete");
             ctx.request_repaint();
         }
 
@@ -248,10 +805,51 @@ impl eframe::App for RaytracerApp {
             })
             .show(ctx, |ui| {
                 if !self.image.is_empty() {
-                    ui.add(egui::Image::new(image_source));
+                    let image = egui::Image::new(image_source).sense(egui::Sense::click());
+                    let response = ui.add(image);
+                    if let Some(pos) = response.interact_pointer_pos() {
+                        if response.clicked() {
+                            self.pick(pos, response.rect);
+                        }
+                    }
                 }
             });
 
+        if let Some(editor) = &mut self.material_editor {
+            let mut open = true;
+            let mut apply = false;
+            egui::Window::new("Material")
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    editor.show(ui);
+                    // the world can't change while a render is reading it
+                    let idle = self.render_handle.is_none();
+                    apply = ui.add_enabled(idle, egui::Button::new("Apply")).clicked();
+                });
+
+            if apply {
+                if let Some(settings) = &self.rendered_settings {
+                    let material = editor.material();
+                    if settings.scene != editor.scene {
+                        warn!(
+                            "Shape {} was picked in the {} scene, not this one",
+                            editor.index, editor.scene
+                        );
+                    } else if self
+                        .worlds
+                        .set_material(&editor.scene, editor.index, material)
+                    {
+                        info!("Changed the material of shape {}", editor.index);
+                    } else {
+                        warn!("Couldn't change the material of shape {}", editor.index);
+                    }
+                }
+            }
+            if !open {
+                self.material_editor = None;
+            }
+        }
+
         egui::Window::new("Render settings").show(ctx, |ui| {
             if ui.button("Reset").clicked() {
                 let scene = self.render_settings.scene.clone();
@@ -283,6 +881,21 @@ impl eframe::App for RaytracerApp {
                                 Scene::CornellBoxTwoBoxes,
                                 Scene::CornellBoxTwoBoxes.to_string(),
                             );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::CornellBoxMirrorGlass,
+                                Scene::CornellBoxMirrorGlass.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::CornellBoxSmoke,
+                                Scene::CornellBoxSmoke.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::CornellBoxSpotlight,
+                                Scene::CornellBoxSpotlight.to_string(),
+                            );
                             ui.selectable_value(
                                 &mut self.render_settings.scene,
                                 Scene::OneSphere,
@@ -318,16 +931,31 @@ impl eframe::App for RaytracerApp {
                                 Scene::ManySpheres,
                                 Scene::ManySpheres.to_string(),
                             );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::BouncingSpheres,
+                                Scene::BouncingSpheres.to_string(),
+                            );
                             ui.selectable_value(
                                 &mut self.render_settings.scene,
                                 Scene::Earth,
                                 Scene::Earth.to_string(),
                             );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::Teapot,
+                                Scene::Teapot.to_string(),
+                            );
                             ui.selectable_value(
                                 &mut self.render_settings.scene,
                                 Scene::TwoPerlinSpheres,
                                 Scene::TwoPerlinSpheres.to_string(),
                             );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::MarbleSpheres,
+                                Scene::MarbleSpheres.to_string(),
+                            );
                             ui.selectable_value(
                                 &mut self.render_settings.scene,
                                 Scene::Quads,
@@ -338,11 +966,47 @@ impl eframe::App for RaytracerApp {
                                 Scene::SimpleLight,
                                 Scene::SimpleLight.to_string(),
                             );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::DiskLight,
+                                Scene::DiskLight.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::Skybox,
+                                Scene::Skybox.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::ColoredGlass,
+                                Scene::ColoredGlass.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::NormalMap,
+                                Scene::NormalMap.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::GlowingPicture,
+                                Scene::GlowingPicture.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::Sunlit,
+                                Scene::Sunlit.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.scene,
+                                Scene::Empty,
+                                Scene::Empty.to_string(),
+                            );
                         });
                     if ui.button("Reset camera").clicked() {
                         let cam_settings = get_scene_camera(&self.render_settings.scene);
                         self.render_settings.camera_position = cam_settings.camera_position;
                         self.render_settings.focus_point = cam_settings.focus_point;
+                        self.render_settings.up = cam_settings.up;
                         self.render_settings.field_of_view = cam_settings.field_of_view;
                     }
                     ui.end_row();
@@ -407,6 +1071,17 @@ impl eframe::App for RaytracerApp {
                     });
                     ui.end_row();
 
+                    ui.label("Up");
+                    ui.horizontal(|ui| {
+                        ui.label("X:");
+                        ui.add(egui::DragValue::new(&mut self.render_settings.up.x).speed(0.01));
+                        ui.label("Y:");
+                        ui.add(egui::DragValue::new(&mut self.render_settings.up.y).speed(0.01));
+                        ui.label("Z:");
+                        ui.add(egui::DragValue::new(&mut self.render_settings.up.z).speed(0.01));
+                    });
+                    ui.end_row();
+
                     ui.label("Field of View");
                     ui.add(
                         egui::DragValue::new(&mut self.render_settings.field_of_view).speed(0.1),
@@ -424,25 +1099,205 @@ impl eframe::App for RaytracerApp {
                         egui::DragValue::new(&mut self.render_settings.focus_distance).speed(0.1),
                     );
                     ui.end_row();
+
+                    ui.label("Cat's Eye");
+                    ui.add(
+                        egui::DragValue::new(&mut self.render_settings.cats_eye)
+                            .clamp_range(0.0..=1.0)
+                            .speed(0.01),
+                    );
+                    ui.end_row();
+
+                    ui.label("Environment Intensity");
+                    ui.add(
+                        egui::DragValue::new(&mut self.render_settings.environment_intensity)
+                            .clamp_range(0.0..=f32::MAX)
+                            .speed(0.01),
+                    );
+                    ui.end_row();
+
+                    ui.label("Pass");
+                    egui::ComboBox::from_id_source("render_pass")
+                        .selected_text(self.render_settings.pass.to_string())
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.render_settings.pass,
+                                RenderPass::Beauty,
+                                RenderPass::Beauty.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.pass,
+                                RenderPass::Reflection,
+                                RenderPass::Reflection.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.pass,
+                                RenderPass::Normal,
+                                RenderPass::Normal.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.pass,
+                                RenderPass::Depth,
+                                RenderPass::Depth.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.pass,
+                                RenderPass::Albedo,
+                                RenderPass::Albedo.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.pass,
+                                RenderPass::CostHeatmap,
+                                RenderPass::CostHeatmap.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.pass,
+                                RenderPass::Alpha,
+                                RenderPass::Alpha.to_string(),
+                            );
+                        });
+                    ui.end_row();
+
+                    ui.label("Tone Map");
+                    egui::ComboBox::from_id_source("tone_map")
+                        .selected_text(self.render_settings.tone_map.to_string())
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.render_settings.tone_map,
+                                ToneMap::None,
+                                ToneMap::None.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.tone_map,
+                                ToneMap::Reinhard,
+                                ToneMap::Reinhard.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.tone_map,
+                                ToneMap::AcesFilmic,
+                                ToneMap::AcesFilmic.to_string(),
+                            );
+                        });
+                    ui.end_row();
+
+                    ui.label("Color Space");
+                    egui::ComboBox::from_id_source("color_space")
+                        .selected_text(self.render_settings.color_space.to_string())
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.render_settings.color_space,
+                                ColorSpace::Srgb,
+                                ColorSpace::Srgb.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.color_space,
+                                ColorSpace::Gamma,
+                                ColorSpace::Gamma.to_string(),
+                            );
+                        });
+                    ui.end_row();
+
+                    ui.label("Gamma");
+                    ui.add_enabled(
+                        self.render_settings.color_space == ColorSpace::Gamma,
+                        egui::DragValue::new(&mut self.render_settings.gamma)
+                            .clamp_range(GAMMA_RANGE)
+                            .speed(0.01),
+                    );
+                    ui.end_row();
+
+                    ui.label("Normal Space");
+                    egui::ComboBox::from_id_source("normal_space")
+                        .selected_text(self.render_settings.normal_space.to_string())
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.render_settings.normal_space,
+                                NormalSpace::World,
+                                NormalSpace::World.to_string(),
+                            );
+                            ui.selectable_value(
+                                &mut self.render_settings.normal_space,
+                                NormalSpace::View,
+                                NormalSpace::View.to_string(),
+                            );
+                        });
+                    ui.end_row();
+
+                    ui.label("Seed");
+                    ui.horizontal(|ui| {
+                        let mut seeded = self.render_settings.seed.is_some();
+                        if ui.checkbox(&mut seeded, "").changed() {
+                            self.render_settings.seed = seeded.then_some(0);
+                        }
+                        if let Some(seed) = self.render_settings.seed.as_mut() {
+                            ui.add(egui::DragValue::new(seed).speed(1.0));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Region");
+                    ui.horizontal(|ui| {
+                        let mut cropped = self.render_settings.region.is_some();
+                        if ui.checkbox(&mut cropped, "").changed() {
+                            let size = &self.render_settings.size;
+                            self.render_settings.region =
+                                cropped.then_some((0, 0, size.width, size.height));
+                        }
+                        if let Some((x0, y0, x1, y1)) = self.render_settings.region.as_mut() {
+                            for (label, value) in
+                                [("X0:", x0), ("Y0:", y0), ("X1:", x1), ("Y1:", y1)]
+                            {
+                                ui.label(label);
+                                ui.add(egui::DragValue::new(value).speed(1.0));
+                            }
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Annotate");
+                    ui.checkbox(&mut self.render_settings.annotate, "");
+                    ui.end_row();
+
+                    ui.label("Dither");
+                    ui.checkbox(&mut self.render_settings.dither, "");
+                    ui.end_row();
+
+                    ui.label("Alpha");
+                    ui.checkbox(&mut self.render_settings.alpha, "");
+                    ui.end_row();
+
+                    ui.label("Linear");
+                    ui.checkbox(&mut self.render_settings.linear, "");
+                    ui.end_row();
                 });
 
             if self.render_handle.is_none() {
                 if ui.button("Render").clicked() {
                     self.image = vec![];
+                    self.render_error = None;
                     let render_settings = self.render_settings.clone();
+                    self.rendered_settings = Some(render_settings.clone());
                     let sender = self.progress_updater.clone();
-                    let mut context = ctx.clone();
+                    let worlds = self.worlds.clone();
+                    let context = ctx.clone();
                     self.render_handle = Some(std::thread::spawn(move || {
                         let start = std::time::Instant::now();
-                        let ret = render(render_settings, sender, &mut context);
+                        let progress = |fraction| {
+                            let _ = sender.update(fraction);
+                            context.request_repaint();
+                        };
+                        let ret = render(render_settings, &worlds, Some(&progress));
                         let duration = start.elapsed();
                         context.request_repaint();
-                        (ret, duration)
+                        ret.map(|output| (output.image, duration))
                     }));
                 }
                 if let Some(duration) = self.duration {
                     ui.label(format!("Render time: {}", duration.human(Truncate::Millis)));
                 }
+                if let Some(e) = &self.render_error {
+                    ui.colored_label(ui.visuals().error_fg_color, e);
+                }
             } else {
                 ui.add_enabled(false, egui::Button::new("Render"));
                 ui.end_row();
@@ -457,3 +1312,192 @@ impl eframe::App for RaytracerApp {
         }
     }
 }
+
+#[cfg(all(test, not(feature = "gui")))]
+mod tests {
+    use super::*;
+    use raytracing::vector::Vector;
+
+    fn parse(args: &[&str]) -> RenderSettings {
+        let args = Args::try_parse_from([&["raytracing"], args].concat()).unwrap();
+        settings_from_args(&args).unwrap()
+    }
+
+    #[test]
+    fn test_color_space() {
+        assert_eq!(parse(&[]).color_space, ColorSpace::Srgb);
+        assert_eq!(parse(&[]).gamma, 2.2);
+        let settings = parse(&["--color-space", "gamma", "--gamma", "1.8"]);
+        assert_eq!(settings.color_space, ColorSpace::Gamma);
+        assert_eq!(settings.gamma, 1.8);
+        assert_eq!(parse(&["--color-space", "gamma"]).gamma, 2.2);
+
+        // an exponent on its own picks the gamma color space
+        let settings = parse(&["--gamma", "2.4"]);
+        assert_eq!(settings.color_space, ColorSpace::Gamma);
+        assert_eq!(settings.gamma, 2.4);
+
+        let args = |args: &[&str]| Args::try_parse_from([&["raytracing"], args].concat());
+        let srgb = args(&["--color-space", "srgb", "--gamma", "1.8"]).unwrap();
+        assert!(settings_from_args(&srgb).is_err());
+        assert!(args(&["--gamma", "0"]).is_err());
+        assert!(args(&["--gamma", "-2"]).is_err());
+        assert!(args(&["--gamma", "6"]).is_err());
+    }
+
+    #[test]
+    fn test_linear_data_pass() {
+        let settings = parse(&["--pass", "normal", "--linear"]);
+        assert_eq!(settings.pass, RenderPass::Normal);
+        assert!(settings.linear);
+
+        let settings = parse(&["--pass", "albedo", "--no-gamma"]);
+        assert_eq!(settings.pass, RenderPass::Albedo);
+        assert!(settings.linear);
+
+        let settings = parse(&[]);
+        assert_eq!(settings.pass, RenderPass::Beauty);
+        assert!(!settings.linear);
+    }
+
+    #[test]
+    fn test_camera_overrides() {
+        let scene_camera = get_scene_camera(&Scene::ManySpheres);
+        let settings = parse(&["--scene", "many-spheres"]);
+        assert_eq!(settings.field_of_view, scene_camera.field_of_view);
+        assert_ne!(scene_camera.field_of_view, 35.0);
+
+        let settings = parse(&[
+            "--scene",
+            "many-spheres",
+            "--fov",
+            "35",
+            "--defocus-angle",
+            "0.6",
+            "--focus-distance",
+            "10",
+        ]);
+        assert_eq!(settings.field_of_view, 35.0);
+        assert_eq!(settings.defocus_angle, 0.6);
+        assert_eq!(settings.focus_distance, 10.0);
+        assert!((settings.camera_position - scene_camera.camera_position).is_near_zero());
+    }
+
+    #[test]
+    fn test_focus_point_is_separate_from_camera_position() {
+        let settings = parse(&["-c", "1,2,3", "-f", "4,5,6"]);
+        assert!((settings.camera_position - Point::new(1.0, 2.0, 3.0)).is_near_zero());
+        assert!((settings.focus_point - Point::new(4.0, 5.0, 6.0)).is_near_zero());
+    }
+
+    #[test]
+    fn test_turntable_orbits_focus_point() {
+        let settings = parse(&["--scene", "many-spheres"]);
+        let offset = |position: Point| position - settings.focus_point;
+        let first = offset(turntable_camera(&settings, 0.0));
+        assert!((first - offset(settings.camera_position)).is_near_zero());
+
+        for frame in 1..12 {
+            let camera = offset(turntable_camera(&settings, frame as f64 / 12.0));
+            // same distance and height, turned about the vertical axis
+            assert!((camera.length() - first.length()).abs() < 1e-9);
+            assert!((camera.y - first.y).abs() < 1e-9);
+            let turned = (camera.z.atan2(camera.x) - first.z.atan2(first.x))
+                .rem_euclid(std::f64::consts::TAU);
+            let expected = (frame as f64 / 12.0 * 360.0).to_radians();
+            assert!(
+                (turned - expected).abs() < 1e-9,
+                "frame {}: {}",
+                frame,
+                turned
+            );
+        }
+    }
+
+    #[test]
+    fn test_turntable_turns_about_up() {
+        let settings = RenderSettings {
+            camera_position: Point::new(3.0, 1.0, 2.0),
+            focus_point: Point::new(0.0, 1.0, 0.0),
+            up: Vector::new(0.0, 0.0, 2.0),
+            ..Default::default()
+        };
+        // a quarter turn about the z axis, keeping the height along z
+        let camera = turntable_camera(&settings, 0.25);
+        assert!(
+            (camera - Point::new(0.0, -2.0, 2.0)).is_near_zero(),
+            "{:?}",
+            camera
+        );
+    }
+
+    #[test]
+    fn test_turntable_needs_frames() {
+        assert!(Args::try_parse_from(["raytracing", "--turntable", "0"]).is_err());
+        assert!(Args::try_parse_from(["raytracing", "--frames", "1"]).is_ok());
+    }
+
+    #[test]
+    fn test_aov_output_names() {
+        assert_eq!(aov_output("render.png", "depth"), "render.depth.png");
+        assert_eq!(aov_output("out/a.exr", "normal"), "out/a.normal.png");
+        assert_eq!(aov_output("frames", "depth"), "frames.depth.png");
+    }
+
+    #[test]
+    fn test_format_progress() {
+        let line = format_progress("Frame 2/4 ", 0.25, Duration::from_secs(10));
+        let bar = format!("[{}{}]", "#".repeat(8), " ".repeat(22));
+        assert_eq!(line, format!("Frame 2/4 {}  25% ETA 30s", bar));
+        let done = format_progress("", 1.0, Duration::from_secs(10));
+        assert!(done.starts_with(&format!("[{}] 100% ETA", "#".repeat(30))));
+        assert!(format_progress("", 0.0, Duration::ZERO).ends_with("ETA ?"));
+    }
+
+    #[test]
+    fn test_format_from_output_extension() {
+        assert_eq!(parse(&[]).output_format, OutputFormat::Png);
+        assert_eq!(parse(&["-o", "a.jpg"]).output_format, OutputFormat::Jpeg);
+        assert_eq!(parse(&["-o", "-"]).output_format, OutputFormat::Png);
+        // an explicit format wins over the extension
+        let settings = parse(&["-o", "a.jpg", "--format", "bmp"]);
+        assert_eq!(settings.output_format, OutputFormat::Bmp);
+    }
+
+    #[test]
+    fn test_animated_gif_args() {
+        let args =
+            Args::try_parse_from(["raytracing", "--output", "turntable.gif", "--frames", "60"])
+                .unwrap();
+        assert_eq!(args.turntable, Some(60));
+        assert_eq!(args.frame_delay, 40);
+        assert_eq!(
+            settings_from_args(&args).unwrap().output_format,
+            OutputFormat::Gif
+        );
+    }
+
+    #[test]
+    fn test_short_flags() {
+        assert_eq!(parse(&["-s", "7"]).samples, 7);
+        let help = Args::try_parse_from(["raytracing", "-h"]).unwrap_err();
+        assert_eq!(help.kind(), ErrorKind::DisplayHelp);
+    }
+
+    #[test]
+    fn test_seed() {
+        assert_eq!(parse(&["--seed", "42"]).seed, Some(42));
+        assert_eq!(parse(&[]).seed, None);
+    }
+
+    #[test]
+    fn test_region() {
+        assert_eq!(
+            parse(&["--region", "10,20,30,40"]).region,
+            Some((10, 20, 30, 40))
+        );
+        assert_eq!(parse(&[]).region, None);
+        assert!(parse_region("1,2,3").is_err());
+        assert!(parse_region("1,2,3,-4").is_err());
+    }
+}
@@ -1,13 +1,23 @@
 use crate::color::Color;
 use crate::object::{Collision, Facing};
-use crate::ray::Ray;
-use crate::texture::{ColorAt, Texture};
+use crate::ray::{Media, Ray};
+use crate::rng;
+use crate::texture::{ColorAt, Solid, Texture};
 use crate::vector::Vector;
 use enum_dispatch::enum_dispatch;
+use log::warn;
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 pub struct Deflection {
     pub attenuation: Color,
     pub ray: Ray,
+    /// Whether the ray was mirrored or refracted rather than scattered diffusely
+    pub specular: bool,
+    /// Probability density, per unit solid angle, of having picked this direction. Specular
+    /// deflections come from a delta distribution with no density to speak of, so they report
+    /// 1.0 and leave the whole weight in `attenuation`; check `specular` before using it.
+    pub pdf: f64,
 }
 
 #[enum_dispatch]
@@ -18,6 +28,33 @@ pub enum Material {
     Dielectric,
     Simple,
     Light,
+    Subsurface,
+    Isotropic,
+    NormalMapped,
+}
+
+impl Material {
+    /// Bytes allocated by the material outside of itself, e.g. image texture data
+    pub fn heap_size(&self) -> usize {
+        match self {
+            Material::Simple(simple) => simple.texture.heap_size(),
+            Material::Light(light) => light.texture.heap_size(),
+            Material::Isotropic(isotropic) => isotropic.texture.heap_size(),
+            Material::NormalMapped(mapped) => {
+                mapped.normal_map.heap_size() + mapped.material.heap_size()
+            }
+            _ => 0,
+        }
+    }
+
+    /// Whether the material scatters cosine-weighted around the hit's own normal, so a scattered
+    /// direction can be picked from another distribution and weighted by `cos θ / π` instead
+    pub fn scatters_cosine(&self) -> bool {
+        matches!(
+            self,
+            Material::Lambertian(_) | Material::Simple(_) | Material::Subsurface(_)
+        )
+    }
 }
 
 #[enum_dispatch(Material)]
@@ -32,13 +69,20 @@ pub trait Emit {
     }
 }
 
+/// Density of the cosine-weighted directions diffuse materials scatter into, `cos θ / π` for the
+/// angle θ between the direction and the surface normal
+fn cosine_pdf(normal: &Vector, direction: &Vector) -> f64 {
+    let cos_theta = normal.normalize().dot(&direction.normalize());
+    cos_theta.max(0.0) / PI
+}
+
 #[derive(Clone)]
 pub struct Lambertian {
     pub albedo: Color,
 }
 
 impl Deflect for Lambertian {
-    fn deflect(&self, _ray: &Ray, hit: &Collision) -> Option<Deflection> {
+    fn deflect(&self, ray: &Ray, hit: &Collision) -> Option<Deflection> {
         let mut scatter_direction = hit.normal + Vector::random_unit_vector();
         if scatter_direction.is_near_zero() {
             scatter_direction = hit.normal;
@@ -47,10 +91,14 @@ impl Deflect for Lambertian {
         let scattered = Ray {
             origin: hit.point,
             direction: scatter_direction,
+            media: ray.media,
+            time: ray.time,
         };
         Some(Deflection {
             attenuation: self.albedo,
             ray: scattered,
+            specular: false,
+            pdf: cosine_pdf(&hit.normal, &scatter_direction),
         })
     }
 }
@@ -61,59 +109,114 @@ impl Emit for Lambertian {}
 pub struct Metal {
     pub albedo: Color,
     pub fuzz: f64,
+    /// Stretches the fuzz along one tangent of the surface, like brushed metal. From -1 to 1:
+    /// positive values spread reflections along the tangent around the world's vertical axis,
+    /// negative values along the bitangent from pole to pole, and 0.0 spreads them evenly.
+    pub anisotropy: f64,
 }
 
 impl Deflect for Metal {
     fn deflect(&self, ray: &Ray, hit: &Collision) -> Option<Deflection> {
         let reflected = ray.direction.normalize().reflect(&hit.normal);
+        let perturbation = if self.anisotropy == 0.0 {
+            Vector::random_unit_vector()
+        } else {
+            self.anisotropic_perturbation(&hit.normal)
+        };
         let scattered = Ray {
             origin: hit.point,
-            direction: reflected + self.fuzz * Vector::random_unit_vector(),
+            direction: reflected + self.fuzz * perturbation,
+            media: ray.media,
+            time: ray.time,
         };
         Some(Deflection {
             attenuation: self.albedo,
             ray: scattered,
+            specular: true,
+            pdf: 1.0,
         })
     }
 }
 
 impl Emit for Metal {}
 
+impl Metal {
+    /// A random unit vector with its tangent component scaled by `1 + anisotropy` and its
+    /// bitangent component by `1 - anisotropy`
+    fn anisotropic_perturbation(&self, normal: &Vector) -> Vector {
+        let anisotropy = self.anisotropy.clamp(-1.0, 1.0);
+        let (tangent, bitangent) = tangent_frame(normal);
+        let random = Vector::random_unit_vector();
+        random + (random.dot(&tangent) * anisotropy) * tangent
+            - (random.dot(&bitangent) * anisotropy) * bitangent
+    }
+}
+
+/// Unit tangent and bitangent perpendicular to `normal`. The tangent runs horizontally, around
+/// the world's vertical axis, unless the normal is close to vertical itself, when it runs along
+/// the z axis instead.
+pub(crate) fn tangent_frame(normal: &Vector) -> (Vector, Vector) {
+    let normal = normal.normalize();
+    let reference = if normal.y.abs() > 0.999 {
+        Vector::new(1.0, 0.0, 0.0)
+    } else {
+        Vector::new(0.0, 1.0, 0.0)
+    };
+    let tangent = reference.cross(&normal).normalize();
+    let bitangent = normal.cross(&tangent);
+    (tangent, bitangent)
+}
+
 #[derive(Clone)]
 pub struct Dielectric {
     pub refraction_index: f64,
-    // TODO: add fuzz
+    /// How much of each color is absorbed per unit of distance traveled inside, following the
+    /// Beer–Lambert law. `None` is perfectly clear.
+    pub absorption: Option<Color>,
+    /// Roughness of frosted glass, scattering light that goes through or reflects off it around
+    /// the sharp direction. 0.0 is clear.
+    pub fuzz: f64,
 }
 
 impl Deflect for Dielectric {
     fn deflect(&self, ray: &Ray, hit: &Collision) -> Option<Deflection> {
-        let attenuation = Color::WHITE;
-        let refraction_ratio = match hit.facing {
-            Facing::Inward => 1.0 / self.refraction_index,
-            Facing::Outward => self.refraction_index,
+        // a ray hitting the surface from inside has come through the interior to get there
+        let attenuation = match (self.absorption, &hit.facing) {
+            (Some(absorption), Facing::Outward) => {
+                Self::transmittance(absorption, hit.t * ray.direction.length())
+            }
+            _ => Color::WHITE,
         };
+        let (refraction_ratio, refracted_media) = self.refraction(&ray.media, &hit.facing);
 
         let unit_direction = ray.direction.normalize();
         let cos_theta = (-unit_direction).dot(&hit.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
-
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
 
-        let deflected =
-            if cannot_refract || Self::reflectance(cos_theta, refraction_ratio) > rand::random() {
-                unit_direction.reflect(&hit.normal)
-            } else {
-                unit_direction.refract(&hit.normal, refraction_ratio)
-            };
+        let refracted = unit_direction
+            .refract(&hit.normal, refraction_ratio)
+            .filter(|_| Self::reflectance(cos_theta, refraction_ratio) <= rng::random());
+        let (deflected, media) = match refracted {
+            Some(refracted) => (refracted, refracted_media),
+            None => (unit_direction.reflect(&hit.normal), ray.media),
+        };
+        let deflected = if self.fuzz > 0.0 {
+            Self::fuzzed(deflected, &hit.normal, self.fuzz)
+        } else {
+            deflected
+        };
 
         let scattered = Ray {
             origin: hit.point,
             direction: deflected,
+            media,
+            time: ray.time,
         };
 
         Some(Deflection {
             attenuation,
             ray: scattered,
+            specular: true,
+            pdf: 1.0,
         })
     }
 }
@@ -121,6 +224,49 @@ impl Deflect for Dielectric {
 impl Emit for Dielectric {}
 
 impl Dielectric {
+    /// Ratio of the refraction indices on either side of the surface, and the media the ray will
+    /// be in if it refracts through it. Tracking the media lets nested dielectrics (e.g. water in
+    /// a glass) use the index of the medium around them rather than assuming air.
+    fn refraction(&self, media: &Media, facing: &Facing) -> (f64, Media) {
+        match facing {
+            Facing::Inward => (
+                media.current() / self.refraction_index,
+                media.enter(self.refraction_index),
+            ),
+            Facing::Outward => {
+                let outside = media.exit(self.refraction_index);
+                (self.refraction_index / outside.current(), outside)
+            }
+        }
+    }
+
+    /// Perturbs `direction` by up to `fuzz`, keeping it on the same side of the surface so that
+    /// fuzz can't turn a reflection into a refraction or the other way round
+    fn fuzzed(direction: Vector, normal: &Vector, fuzz: f64) -> Vector {
+        let fuzzed = direction + fuzz * Vector::random_unit_vector();
+        if fuzzed.is_near_zero() {
+            return direction;
+        }
+        let fuzzed = fuzzed.normalize();
+        let crossing = fuzzed.dot(normal);
+        if crossing * direction.dot(normal) < 0.0 {
+            // mirror it back across the surface
+            fuzzed - 2.0 * crossing * *normal
+        } else {
+            fuzzed
+        }
+    }
+
+    /// Fraction of each color left after traveling `distance` through a medium with the given
+    /// absorption coefficients
+    fn transmittance(absorption: Color, distance: f64) -> Color {
+        Color::new(
+            (-absorption.r * distance).exp(),
+            (-absorption.g * distance).exp(),
+            (-absorption.b * distance).exp(),
+        )
+    }
+
     fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
         let r0 = ((1.0 - refraction_index) / (1.0 + refraction_index)).powi(2);
         r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
@@ -133,7 +279,7 @@ pub struct Simple {
 }
 
 impl Deflect for Simple {
-    fn deflect(&self, _ray: &Ray, hit: &Collision) -> Option<Deflection> {
+    fn deflect(&self, ray: &Ray, hit: &Collision) -> Option<Deflection> {
         let mut scatter_direction = hit.normal + Vector::random_unit_vector();
         if scatter_direction.is_near_zero() {
             scatter_direction = hit.normal;
@@ -142,19 +288,36 @@ impl Deflect for Simple {
         let scattered = Ray {
             origin: hit.point,
             direction: scatter_direction,
+            media: ray.media,
+            time: ray.time,
         };
         Some(Deflection {
             attenuation: self.texture.color_at(hit.u, hit.v, &hit.point),
             ray: scattered,
+            specular: false,
+            pdf: cosine_pdf(&hit.normal, &scatter_direction),
         })
     }
 }
 
 impl Emit for Simple {}
 
+/// Gives off the light of its texture, scaled by `intensity` so that textures limited to 1.0,
+/// like most images, can still light a scene
 #[derive(Clone)]
 pub struct Light {
-    pub color: Color,
+    pub texture: Texture,
+    pub intensity: f64,
+}
+
+impl Light {
+    /// A light of one color all over
+    pub fn new(color: Color) -> Self {
+        Light {
+            texture: Texture::Solid(Solid { color }),
+            intensity: 1.0,
+        }
+    }
 }
 
 impl Deflect for Light {
@@ -164,7 +327,494 @@ impl Deflect for Light {
 }
 
 impl Emit for Light {
-    fn emit(&self, _u: f64, _v: f64, _point: &Vector) -> Color {
-        self.color
+    fn emit(&self, u: f64, v: f64, point: &Vector) -> Color {
+        finite_emission(self.texture.color_at(u, v, point) * self.intensity)
+    }
+}
+
+/// Replaces NaN or infinite emission with black, since a single one would spread through the
+/// accumulated color and ruin the whole pixel. Warns once per process.
+pub fn finite_emission(color: Color) -> Color {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+
+    if color.is_finite() {
+        return color;
+    }
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        warn!("Emission {:?} is not finite, treating it as black", color);
+    }
+    Color::BLACK
+}
+
+/// Phase function for participating media like smoke and fog: scatters in any direction with
+/// equal probability
+#[derive(Clone)]
+pub struct Isotropic {
+    pub texture: Texture,
+}
+
+impl Deflect for Isotropic {
+    fn deflect(&self, ray: &Ray, hit: &Collision) -> Option<Deflection> {
+        let scattered = Ray {
+            origin: hit.point,
+            direction: Vector::random_unit_vector(),
+            media: ray.media,
+            time: ray.time,
+        };
+        Some(Deflection {
+            attenuation: self.texture.color_at(hit.u, hit.v, &hit.point),
+            ray: scattered,
+            specular: false,
+            pdf: 1.0 / (4.0 * PI),
+        })
+    }
+}
+
+impl Emit for Isotropic {}
+
+/// Adds surface detail to another material by bending the normal it sees. The normal map holds
+/// tangent-space normals as colors, each component scaled from -1..1 to 0..1, with x along the
+/// surface's tangent, y along the bitangent, and z out of the surface, so a flat map is
+/// (0.5, 0.5, 1.0).
+#[derive(Clone)]
+pub struct NormalMapped {
+    pub material: Box<Material>,
+    pub normal_map: Texture,
+}
+
+impl Deflect for NormalMapped {
+    fn deflect(&self, ray: &Ray, hit: &Collision) -> Option<Deflection> {
+        let hit = Collision {
+            normal: self.shading_normal(ray, hit),
+            ..*hit
+        };
+        self.material.deflect(ray, &hit)
+    }
+}
+
+impl Emit for NormalMapped {
+    fn emit(&self, u: f64, v: f64, point: &Vector) -> Color {
+        self.material.emit(u, v, point)
+    }
+}
+
+impl NormalMapped {
+    /// The hit's normal bent by the normal map. A mapped normal facing away from the ray would
+    /// let light through the surface, so the unbent normal is used instead.
+    pub fn shading_normal(&self, ray: &Ray, hit: &Collision) -> Vector {
+        let color = self.normal_map.color_at(hit.u, hit.v, &hit.point);
+        let local = Vector::new(
+            2.0 * color.r - 1.0,
+            2.0 * color.g - 1.0,
+            2.0 * color.b - 1.0,
+        );
+
+        let normal = hit.normal.normalize();
+        let tangent = hit.tangent - hit.tangent.dot(&normal) * normal;
+        let (tangent, bitangent) = if tangent.is_near_zero() {
+            tangent_frame(&normal)
+        } else {
+            let tangent = tangent.normalize();
+            (tangent, normal.cross(&tangent))
+        };
+
+        let mapped = local.x * tangent + local.y * bitangent + local.z * normal;
+        if mapped.is_near_zero() || mapped.dot(&ray.direction) >= 0.0 {
+            return normal;
+        }
+        mapped.normalize()
+    }
+}
+
+/// A cheap stand-in for subsurface scattering on waxy or skin-like surfaces. Light enters at the
+/// hit point, diffuses through the object, and leaves diffusely from a nearby point on the
+/// tangent plane, picked from an exponential falloff cut off at `radius`.
+#[derive(Clone)]
+pub struct Subsurface {
+    pub albedo: Color,
+    pub radius: f64,
+}
+
+impl Deflect for Subsurface {
+    fn deflect(&self, ray: &Ray, hit: &Collision) -> Option<Deflection> {
+        let origin = hit.point + self.exit_offset(&hit.normal);
+
+        let mut scatter_direction = hit.normal + Vector::random_unit_vector();
+        if scatter_direction.is_near_zero() {
+            scatter_direction = hit.normal;
+        }
+
+        let scattered = Ray {
+            origin,
+            direction: scatter_direction,
+            media: ray.media,
+            time: ray.time,
+        };
+        Some(Deflection {
+            attenuation: self.albedo,
+            ray: scattered,
+            specular: false,
+            pdf: cosine_pdf(&hit.normal, &scatter_direction),
+        })
+    }
+}
+
+impl Emit for Subsurface {}
+
+impl Subsurface {
+    /// Most of the light re-emerges within a third of the radius
+    const FALLOFF: f64 = 3.0;
+
+    /// Offset in the tangent plane from where the light entered to where it leaves
+    fn exit_offset(&self, normal: &Vector) -> Vector {
+        if self.radius <= 0.0 {
+            return Vector::ZERO;
+        }
+
+        // sample the distance from an exponential falloff truncated to [0, radius]
+        let u: f64 = rng::random();
+        let truncation = 1.0 - (-Self::FALLOFF).exp();
+        let distance = -(self.radius / Self::FALLOFF) * (1.0 - u * truncation).ln();
+        let angle = rng::random::<f64>() * 2.0 * std::f64::consts::PI;
+
+        let (tangent, bitangent) = tangent_frame(normal);
+        distance * (angle.cos() * tangent + angle.sin() * bitangent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_dielectric_refraction_ratios() {
+        let glass = Dielectric {
+            refraction_index: 1.5,
+            absorption: None,
+            fuzz: 0.0,
+        };
+        let water = Dielectric {
+            refraction_index: 1.33,
+            absorption: None,
+            fuzz: 0.0,
+        };
+
+        // straight through a glass shell filled with water
+        let media = Media::default();
+        let (ratio, media) = glass.refraction(&media, &Facing::Inward);
+        assert_eq!(ratio, 1.0 / 1.5);
+        let (ratio, media) = water.refraction(&media, &Facing::Inward);
+        assert_eq!(ratio, 1.5 / 1.33);
+        let (ratio, media) = water.refraction(&media, &Facing::Outward);
+        assert_eq!(ratio, 1.33 / 1.5);
+        let (ratio, media) = glass.refraction(&media, &Facing::Outward);
+        assert_eq!(ratio, 1.5 / 1.0);
+        assert_eq!(media.current(), 1.0);
+    }
+
+    #[test]
+    fn test_hollow_glass_refraction_ratios() {
+        // the inner surface of a hollow sphere faces inwards, so the ray sees it as leaving glass
+        let glass = Dielectric {
+            refraction_index: 1.5,
+            absorption: None,
+            fuzz: 0.0,
+        };
+
+        let media = Media::default();
+        let (ratio, media) = glass.refraction(&media, &Facing::Inward);
+        assert_eq!(ratio, 1.0 / 1.5);
+        let (ratio, media) = glass.refraction(&media, &Facing::Outward);
+        assert_eq!(ratio, 1.5);
+        let (ratio, media) = glass.refraction(&media, &Facing::Inward);
+        assert_eq!(ratio, 1.0 / 1.5);
+        let (ratio, _) = glass.refraction(&media, &Facing::Outward);
+        assert_eq!(ratio, 1.5);
+    }
+
+    fn hit_on_plane(material: &Material) -> Collision<'_> {
+        Collision {
+            point: Vector::new(1.0, 2.0, 3.0),
+            normal: Vector::new(0.0, 1.0, 0.0),
+            tangent: Vector::new(1.0, 0.0, 0.0),
+            t: 1.0,
+            u: 0.0,
+            v: 0.0,
+            facing: Facing::Inward,
+            material,
+        }
+    }
+
+    #[test]
+    fn test_absorbing_glass_darkens_with_distance() {
+        let green = Material::Dielectric(Dielectric {
+            refraction_index: 1.5,
+            absorption: Some(Color::new(1.0, 0.1, 1.0)),
+            fuzz: 0.0,
+        });
+        let attenuation = |facing, t| {
+            let ray = Ray::new(Vector::new(1.0, 2.0 + t, 3.0), Vector::new(0.0, -1.0, 0.0));
+            let hit = Collision {
+                facing,
+                t,
+                ..hit_on_plane(&green)
+            };
+            green.deflect(&ray, &hit).unwrap().attenuation
+        };
+
+        // entering the glass hasn't gone through any of it yet
+        assert_eq!(attenuation(Facing::Inward, 2.0), Color::WHITE);
+
+        let short = attenuation(Facing::Outward, 0.5);
+        let long = attenuation(Facing::Outward, 2.0);
+        assert!(long.r < short.r && long.g < short.g && long.b < short.b);
+        assert!((long.r - (-2.0_f64).exp()).abs() < 1e-12);
+        // green gets through far better than red and blue
+        assert!(long.g > 0.8 && long.r < 0.2 && long.b < 0.2);
+
+        let clear = Material::Dielectric(Dielectric {
+            refraction_index: 1.5,
+            absorption: None,
+            fuzz: 0.0,
+        });
+        let ray = Ray::new(Vector::new(1.0, 4.0, 3.0), Vector::new(0.0, -1.0, 0.0));
+        let hit = Collision {
+            facing: Facing::Outward,
+            t: 2.0,
+            ..hit_on_plane(&clear)
+        };
+        assert_eq!(clear.deflect(&ray, &hit).unwrap().attenuation, Color::WHITE);
+    }
+
+    #[test]
+    fn test_frosted_glass_scatters() {
+        // with matching indices light goes straight through, so only the fuzz can bend it
+        let frosted = |fuzz| {
+            Material::Dielectric(Dielectric {
+                refraction_index: 1.0,
+                absorption: None,
+                fuzz,
+            })
+        };
+        let ray = Ray::new(Vector::new(1.0, 3.0, 3.0), Vector::new(0.0, -1.0, 0.0));
+        let directions = |material: &Material| {
+            (0..50)
+                .map(|_| material.deflect(&ray, &hit_on_plane(material)).unwrap())
+                .map(|deflection| deflection.ray.direction)
+                .collect::<Vec<_>>()
+        };
+
+        for direction in directions(&frosted(0.0)) {
+            assert_eq!(direction, Vector::new(0.0, -1.0, 0.0));
+        }
+
+        let fuzzy = directions(&frosted(0.9));
+        assert!(fuzzy.iter().any(|d| (*d - fuzzy[0]).length() > 1e-3));
+        for direction in fuzzy {
+            assert!((direction.length() - 1.0).abs() < 1e-9);
+            assert!(
+                direction.y < 0.0,
+                "{:?} went back through the surface",
+                direction
+            );
+        }
+    }
+
+    #[test]
+    fn test_anisotropic_metal_spreads_along_tangent() {
+        let brushed = |anisotropy| {
+            Material::Metal(Metal {
+                albedo: Color::WHITE,
+                fuzz: 0.3,
+                anisotropy,
+            })
+        };
+        // a wall facing +z, so the tangent is x and the bitangent y
+        let normal = Vector::new(0.0, 0.0, 1.0);
+        let (tangent, bitangent) = tangent_frame(&normal);
+        assert!((tangent - Vector::new(1.0, 0.0, 0.0)).is_near_zero());
+        assert!((bitangent - Vector::new(0.0, 1.0, 0.0)).is_near_zero());
+
+        let ray = Ray::new(Vector::new(0.0, 0.0, 1.0), Vector::new(0.0, 0.0, -1.0));
+        let spread = |material: &Material| {
+            let hit = Collision {
+                normal,
+                ..hit_on_plane(material)
+            };
+            let (mut along_tangent, mut along_bitangent) = (0.0, 0.0);
+            for _ in 0..2000 {
+                let direction = material.deflect(&ray, &hit).unwrap().ray.direction;
+                along_tangent += direction.dot(&tangent).powi(2);
+                along_bitangent += direction.dot(&bitangent).powi(2);
+            }
+            (along_tangent, along_bitangent)
+        };
+
+        let (tangent_spread, bitangent_spread) = spread(&brushed(0.9));
+        assert!(tangent_spread > 10.0 * bitangent_spread);
+        let (tangent_spread, bitangent_spread) = spread(&brushed(-0.9));
+        assert!(bitangent_spread > 10.0 * tangent_spread);
+        let (tangent_spread, bitangent_spread) = spread(&brushed(0.0));
+        assert!((0.7..1.4).contains(&(tangent_spread / bitangent_spread)));
+    }
+
+    fn normal_mapped(color: Color) -> NormalMapped {
+        NormalMapped {
+            material: Box::new(Material::Lambertian(Lambertian {
+                albedo: Color::WHITE,
+            })),
+            normal_map: Texture::Solid(crate::texture::Solid { color }),
+        }
+    }
+
+    #[test]
+    fn test_flat_normal_map_keeps_normal() {
+        let flat = normal_mapped(Color::new(0.5, 0.5, 1.0));
+        let material = Material::NormalMapped(flat.clone());
+        let ray = Ray::new(Vector::new(1.0, 3.0, 3.0), Vector::new(0.3, -1.0, 0.2));
+
+        for tangent in [
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(2.0, 0.5, -1.0),
+            Vector::ZERO,
+        ] {
+            let hit = Collision {
+                tangent,
+                ..hit_on_plane(&material)
+            };
+            let normal = flat.shading_normal(&ray, &hit);
+            assert!((normal - hit.normal).is_near_zero(), "{:?}", normal);
+        }
+    }
+
+    #[test]
+    fn test_normal_map_tilts_along_tangent() {
+        let ray = Ray::new(Vector::new(1.0, 3.0, 3.0), Vector::new(0.0, -1.0, 0.0));
+        let material = Material::Lambertian(Lambertian {
+            albedo: Color::WHITE,
+        });
+        let hit = Collision {
+            tangent: Vector::new(0.0, 0.0, 2.0),
+            ..hit_on_plane(&material)
+        };
+        let s = std::f64::consts::FRAC_1_SQRT_2;
+
+        // leaning 45 degrees towards +tangent, then towards +bitangent = normal x tangent
+        let towards_tangent = normal_mapped(Color::new(0.5 + s / 2.0, 0.5, 0.5 + s / 2.0));
+        let normal = towards_tangent.shading_normal(&ray, &hit);
+        assert!(
+            (normal - Vector::new(0.0, s, s)).is_near_zero(),
+            "{:?}",
+            normal
+        );
+        let towards_bitangent = normal_mapped(Color::new(0.5, 0.5 + s / 2.0, 0.5 + s / 2.0));
+        let normal = towards_bitangent.shading_normal(&ray, &hit);
+        assert!(
+            (normal - Vector::new(s, s, 0.0)).is_near_zero(),
+            "{:?}",
+            normal
+        );
+
+        // pointing into the surface, which the ray would see from behind
+        let inverted = normal_mapped(Color::new(0.5, 0.5, 0.0));
+        assert!((inverted.shading_normal(&ray, &hit) - hit.normal).is_near_zero());
+    }
+
+    #[test]
+    fn test_checker_light_varies_across_surface() {
+        let checker = crate::texture::Checker::new(Color::WHITE, Color::new(0.2, 0.4, 0.6), 0.5)
+            .with_mode(crate::texture::CheckerMode::Surface);
+        let light = Light {
+            texture: Texture::Checker(checker),
+            intensity: 5.0,
+        };
+        let point = Vector::new(1.0, 2.0, 3.0);
+
+        let bright = light.emit(0.25, 0.25, &point);
+        let dim = light.emit(0.75, 0.25, &point);
+        assert_eq!(bright, Color::new(5.0, 5.0, 5.0));
+        assert_eq!(dim, Color::new(1.0, 2.0, 3.0));
+        assert_eq!(light.emit(0.75, 0.75, &point), bright);
+
+        assert_eq!(
+            Light::new(Color::new(4.0, 2.0, 1.0)).emit(0.1, 0.9, &point),
+            Color::new(4.0, 2.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_lambertian_pdf_is_cosine_over_pi() {
+        let material = Material::Lambertian(Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5),
+        });
+        let hit = hit_on_plane(&material);
+        let ray = Ray::new(Vector::new(1.0, 3.0, 3.0), Vector::new(0.0, -1.0, 0.0));
+
+        for _ in 0..100 {
+            let deflection = material.deflect(&ray, &hit).unwrap();
+            let cos_theta = deflection.ray.direction.normalize().dot(&hit.normal);
+            assert!((deflection.pdf - cos_theta / PI).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_delta_and_isotropic_pdfs() {
+        let ray = Ray::new(Vector::new(1.0, 3.0, 3.0), Vector::new(0.0, -1.0, 0.0));
+
+        let mirror = Material::Metal(Metal {
+            albedo: Color::WHITE,
+            fuzz: 0.0,
+            anisotropy: 0.0,
+        });
+        let deflection = mirror.deflect(&ray, &hit_on_plane(&mirror)).unwrap();
+        assert!(deflection.specular);
+        assert_eq!(deflection.pdf, 1.0);
+
+        let fog = Material::Isotropic(Isotropic {
+            texture: Texture::Solid(crate::texture::Solid {
+                color: Color::WHITE,
+            }),
+        });
+        let deflection = fog.deflect(&ray, &hit_on_plane(&fog)).unwrap();
+        assert!(!deflection.specular);
+        assert_eq!(deflection.pdf, 1.0 / (4.0 * PI));
+    }
+
+    #[test]
+    fn test_subsurface_without_radius_is_lambertian() {
+        let material = Material::Subsurface(Subsurface {
+            albedo: Color::new(0.9, 0.5, 0.4),
+            radius: 0.0,
+        });
+        let hit = hit_on_plane(&material);
+        let ray = Ray::new(Vector::new(1.0, 3.0, 3.0), Vector::new(0.0, -1.0, 0.0));
+
+        for _ in 0..100 {
+            let deflection = material.deflect(&ray, &hit).unwrap();
+            assert_eq!(deflection.ray.origin, hit.point);
+            assert_eq!(deflection.attenuation, Color::new(0.9, 0.5, 0.4));
+            assert!(deflection.ray.direction.dot(&hit.normal) >= 0.0);
+            assert!(!deflection.specular);
+        }
+    }
+
+    #[test]
+    fn test_subsurface_displaces_within_radius() {
+        let material = Material::Subsurface(Subsurface {
+            albedo: Color::new(0.9, 0.5, 0.4),
+            radius: 0.25,
+        });
+        let hit = hit_on_plane(&material);
+        let ray = Ray::new(Vector::new(1.0, 3.0, 3.0), Vector::new(0.0, -1.0, 0.0));
+
+        let distances: Vec<f64> = (0..1000)
+            .map(|_| {
+                let offset = material.deflect(&ray, &hit).unwrap().ray.origin - hit.point;
+                assert!(offset.dot(&hit.normal).abs() < 1e-12, "{:?}", offset);
+                offset.length()
+            })
+            .collect();
+        assert!(distances.iter().all(|&d| d <= 0.25), "{:?}", distances);
+        assert!(distances.iter().any(|&d| d > 0.01));
     }
 }
@@ -0,0 +1,217 @@
+use crate::material::Material;
+use crate::object::{Collection, Object, Triangle};
+use crate::vector::{Point, Vector};
+use log::info;
+use std::error::Error;
+use std::path::PathBuf;
+
+/// Triangle meshes loaded from Wavefront OBJ files
+pub struct Mesh;
+
+#[derive(Debug)]
+struct MeshError {
+    line: usize,
+    message: String,
+}
+
+impl Error for MeshError {}
+
+impl std::fmt::Display for MeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Invalid OBJ file, line {}: {}", self.line, self.message)
+    }
+}
+
+impl Mesh {
+    /// Loads every face of the OBJ file as triangles with the given material
+    pub fn load(path: PathBuf, material: Material) -> Result<Collection, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)?;
+        Mesh::parse(&contents, material)
+    }
+
+    /// Reads the `v`, `vn`, and `f` records of an OBJ file. Faces with more than three vertices
+    /// are split into a fan of triangles, and records for anything else (texture coordinates,
+    /// groups, materials, ...) are skipped.
+    pub fn parse(contents: &str, material: Material) -> Result<Collection, Box<dyn Error>> {
+        let mut vertices = vec![];
+        let mut normals = vec![];
+        let mut objects = vec![];
+        let mut skipped = 0;
+
+        for (index, line) in contents.lines().enumerate() {
+            let error = |message: String| MeshError {
+                line: index + 1,
+                message,
+            };
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("v") => vertices.push(parse_vector(fields).map_err(error)?),
+                Some("vn") => normals.push(parse_vector(fields).map_err(error)?),
+                Some("f") => {
+                    let corners = fields
+                        .map(|corner| parse_corner(corner, &vertices, &normals))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(error)?;
+                    if corners.len() < 3 {
+                        return Err(Box::new(error("face has fewer than 3 vertices".into())));
+                    }
+
+                    for i in 1..corners.len() - 1 {
+                        let [a, b, c] = [corners[0], corners[i], corners[i + 1]];
+                        let triangle = Triangle::new(a.0, b.0, c.0, material.clone());
+                        let triangle = match (a.1, b.1, c.1) {
+                            (Some(n_a), Some(n_b), Some(n_c)) => {
+                                triangle.with_normals([n_a, n_b, n_c])
+                            }
+                            _ => triangle,
+                        };
+                        objects.push(Object::Triangle(triangle));
+                    }
+                }
+                None => {}
+                Some(comment) if comment.starts_with('#') => {}
+                Some(_) => skipped += 1,
+            }
+        }
+
+        if skipped > 0 {
+            info!("Skipped {} unsupported OBJ records", skipped);
+        }
+        Ok(Collection::with_bvh(objects))
+    }
+}
+
+fn parse_vector<'a>(mut fields: impl Iterator<Item = &'a str>) -> Result<Vector, String> {
+    let mut component = || -> Result<f64, String> {
+        let field = fields.next().ok_or("expected 3 coordinates")?;
+        field
+            .parse()
+            .map_err(|_| format!("invalid coordinate `{}`", field))
+    };
+    Ok(Vector::new(component()?, component()?, component()?))
+}
+
+/// A face corner is `v`, `v/vt`, `v//vn`, or `v/vt/vn`, with 1-based indices, or negative ones
+/// counting back from the latest record
+fn parse_corner(
+    corner: &str,
+    vertices: &[Point],
+    normals: &[Vector],
+) -> Result<(Point, Option<Vector>), String> {
+    let mut indices = corner.split('/');
+    let vertex = lookup(indices.next().unwrap_or_default(), vertices)?;
+    let normal = match indices.nth(1) {
+        Some(index) if !index.is_empty() => Some(lookup(index, normals)?),
+        _ => None,
+    };
+    Ok((vertex, normal))
+}
+
+fn lookup(index: &str, items: &[Vector]) -> Result<Vector, String> {
+    let parsed: i64 = index
+        .parse()
+        .map_err(|_| format!("invalid index `{}`", index))?;
+    let position = match parsed {
+        1.. => parsed - 1,
+        ..=-1 => items.len() as i64 + parsed,
+        0 => -1,
+    };
+    usize::try_from(position)
+        .ok()
+        .and_then(|position| items.get(position))
+        .copied()
+        .ok_or_else(|| format!("index {} out of range", parsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::object::Hit;
+    use crate::ray::Ray;
+
+    fn gray() -> Material {
+        Material::Lambertian(Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5),
+        })
+    }
+
+    fn count_triangles(object: &Object) -> usize {
+        match object {
+            Object::Triangle(_) => 1,
+            Object::Collection(collection) => collection.objects.iter().map(count_triangles).sum(),
+            Object::BvhNode(node) => node.children().into_iter().map(count_triangles).sum(),
+            _ => 0,
+        }
+    }
+
+    fn triangles(collection: &Collection) -> usize {
+        collection.objects.iter().map(count_triangles).sum()
+    }
+
+    const SQUARE: &str = "\
+# a unit square in the z = -1 plane
+mtllib square.mtl
+o square
+v 0 0 -1
+v 1 0 -1
+v 1 1 -1
+v 0 1 -1
+vn 0 0 1
+vt 0 0
+usemtl gray
+f 1//1 2//1 3//1 4//1
+";
+
+    #[test]
+    fn test_parse_quad_as_two_triangles() {
+        let mesh = Mesh::parse(SQUARE, gray()).unwrap();
+        assert_eq!(triangles(&mesh), 2);
+
+        let ray = Ray::new(Point::new(0.25, 0.75, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = mesh.hit(&ray, 0.001..f64::INFINITY).unwrap();
+        assert!((hit.t - 1.0).abs() < 1e-12);
+        assert_eq!(hit.normal, Vector::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_parse_face_formats() {
+        let contents = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vn 0 0 1
+f 1 2 3
+f 1/1 2/1 3/1
+f 1/1/1 2/1/1 3/1/1
+f -3//-1 -2//-1 -1//-1
+";
+        let mesh = Mesh::parse(contents, gray()).unwrap();
+        assert_eq!(triangles(&mesh), 4);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = Mesh::parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n", gray())
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Invalid OBJ file, line 3: index 3 out of range"
+        );
+
+        let error = Mesh::parse("v 0 zero 0\n", gray()).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Invalid OBJ file, line 1: invalid coordinate `zero`"
+        );
+    }
+
+    #[test]
+    fn test_load_bundled_teapot() {
+        let mesh = Mesh::load("res/teapot.obj".into(), gray()).unwrap();
+        assert!(triangles(&mesh) > 1000);
+    }
+}
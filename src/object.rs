@@ -1,11 +1,15 @@
-use crate::material::Material;
+use crate::aabb::{component, Aabb};
+use crate::material::{Isotropic, Material};
+use crate::quaternion::Quaternion;
 use crate::ray::Ray;
+use crate::rng;
+use crate::texture::Texture;
 use crate::vector::{Point, Vector};
 use enum_dispatch::enum_dispatch;
+use std::cell::Cell;
 use std::ops::Range;
-use crate::quaternion::Quaternion;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[cfg_attr(test, derive(PartialEq))]
 pub enum Facing {
     Inward,
@@ -16,6 +20,10 @@ pub enum Facing {
 pub struct Collision<'a> {
     pub point: Point,
     pub normal: Point,
+    /// Direction along the surface in which `u` increases. It isn't normalized or necessarily
+    /// perpendicular to `normal`, and is zero where there's no such direction, like the poles of a
+    /// sphere.
+    pub tangent: Vector,
     pub t: f64,
     pub u: f64,
     pub v: f64,
@@ -34,34 +42,200 @@ pub fn set_facing(ray: &Ray, normal: Vector) -> (Vector, Facing) {
 pub enum Object {
     Sphere,
     Quad,
+    Plane,
+    Disk,
+    Triangle,
     Collection,
+    GridCollection,
+    Group,
+    Translate,
+    Rotate,
+    BvhNode,
+    ConstantMedium,
+}
+
+impl Object {
+    /// The material of a single shape, or the phase function of a medium. Objects made of other
+    /// objects have none of their own.
+    pub fn material(&self) -> Option<&Material> {
+        match self {
+            Object::Sphere(sphere) => Some(&sphere.material),
+            Object::Quad(quad) => Some(&quad.material),
+            Object::Plane(plane) => Some(&plane.material),
+            Object::Disk(disk) => Some(&disk.material),
+            Object::Triangle(triangle) => Some(&triangle.material),
+            Object::ConstantMedium(medium) => Some(&medium.phase_function),
+            _ => None,
+        }
+    }
+
+    pub fn material_mut(&mut self) -> Option<&mut Material> {
+        match self {
+            Object::Sphere(sphere) => Some(&mut sphere.material),
+            Object::Quad(quad) => Some(&mut quad.material),
+            Object::Plane(plane) => Some(&mut plane.material),
+            Object::Disk(disk) => Some(&mut disk.material),
+            Object::Triangle(triangle) => Some(&mut triangle.material),
+            Object::ConstantMedium(medium) => Some(&mut medium.phase_function),
+            _ => None,
+        }
+    }
+
+    /// The objects directly inside a collection, transform, or acceleration structure. A medium's
+    /// boundary only shapes it and is never shaded, so it doesn't count.
+    pub fn children(&self) -> Vec<&Object> {
+        match self {
+            Object::Collection(collection) => collection.objects.iter().collect(),
+            Object::GridCollection(grid) => grid.objects.iter().chain(&grid.unbounded).collect(),
+            Object::Group(group) => group.children.iter().collect(),
+            Object::Translate(translate) => vec![&translate.object],
+            Object::Rotate(rotate) => vec![&rotate.object],
+            Object::BvhNode(node) => vec![&node.left, &node.right],
+            _ => vec![],
+        }
+    }
+
+    pub fn children_mut(&mut self) -> Vec<&mut Object> {
+        match self {
+            Object::Collection(collection) => collection.objects.iter_mut().collect(),
+            Object::GridCollection(grid) => {
+                grid.objects.iter_mut().chain(&mut grid.unbounded).collect()
+            }
+            Object::Group(group) => group.children.iter_mut().collect(),
+            Object::Translate(translate) => vec![&mut translate.object],
+            Object::Rotate(rotate) => vec![&mut rotate.object],
+            Object::BvhNode(node) => vec![&mut node.left, &mut node.right],
+            _ => vec![],
+        }
+    }
+}
+
+thread_local! {
+    static INTERSECTION_TESTS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Runs `f` and also returns how many shapes this thread tested rays against while it ran.
+/// Bounding boxes don't count, only the shapes inside them.
+pub fn count_intersection_tests<T>(f: impl FnOnce() -> T) -> (T, u64) {
+    let before = INTERSECTION_TESTS.with(Cell::get);
+    let result = f();
+    (result, INTERSECTION_TESTS.with(Cell::get) - before)
+}
+
+fn count_intersection_test() {
+    INTERSECTION_TESTS.with(|count| count.set(count.get() + 1));
 }
 
 #[enum_dispatch(Object)]
 pub trait Hit {
-    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision>;
+    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision<'_>>;
+}
+
+#[enum_dispatch(Object)]
+pub trait Bounded {
+    /// Box containing every point the object can be hit at
+    fn bounding_box(&self) -> Aabb;
+}
+
+#[enum_dispatch(Object)]
+pub trait MemoryEstimate {
+    /// Approximate number of bytes used by the object, including everything it owns
+    fn memory_estimate(&self) -> usize;
 }
 
 pub struct Sphere {
-    pub center: Point,
-    pub radius: f64,
-    pub material: Material,
+    center: Point,
+    radius: f64,
+    material: Material,
+    orientation: Option<Quaternion>,
+    /// Where the center is when the shutter closes, for spheres that move during the exposure
+    center_end: Option<Point>,
 }
 
 impl Sphere {
-    fn uv(&self, point: &Point) -> (f64, f64) {
-        let p = (*point - self.center) / self.radius;
+    pub fn new(center: Point, radius: f64, material: Material) -> Self {
+        Sphere {
+            center,
+            radius,
+            material,
+            orientation: None,
+            center_end: None,
+        }
+    }
+
+    /// Moves the sphere in a straight line from its center to `center_end` while the shutter is
+    /// open, blurring it along the way
+    pub fn with_motion(mut self, center_end: Point) -> Self {
+        self.center_end = Some(center_end);
+        self
+    }
+
+    /// Rotates the texture mapping on the sphere without moving the geometry
+    pub fn with_orientation(mut self, orientation: Quaternion) -> Self {
+        self.orientation = Some(orientation);
+        self
+    }
+
+    pub fn center(&self) -> Point {
+        self.center
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn orientation(&self) -> Option<Quaternion> {
+        self.orientation
+    }
+
+    pub fn center_end(&self) -> Option<Point> {
+        self.center_end
+    }
+
+    /// Center of the sphere at `time`, between 0 (shutter open) and 1 (shutter closed)
+    pub fn center_at(&self, time: f64) -> Point {
+        match self.center_end {
+            Some(center_end) => self.center + time * (center_end - self.center),
+            None => self.center,
+        }
+    }
+
+    fn uv(&self, point: &Point, center: Point) -> (f64, f64) {
+        let p = (*point - center) / self.radius;
+        // UVs are computed in the sphere's local frame
+        let p = match self.orientation {
+            Some(orientation) => orientation.inverse().rotate_point(p),
+            None => p,
+        };
         let phi = p.z.atan2(p.x);
         let theta = p.y.asin();
         let u = 1.0 - (phi + std::f64::consts::PI) / (2.0 * std::f64::consts::PI);
         let v = (theta + std::f64::consts::PI / 2.0) / std::f64::consts::PI;
         (u, v)
     }
+
+    /// `u` runs around the local y axis, clockwise seen from above
+    fn tangent(&self, point: &Point, center: Point) -> Vector {
+        let p = *point - center;
+        match self.orientation {
+            Some(orientation) => {
+                let p = orientation.inverse().rotate_point(p);
+                orientation.rotate_point(Vector::new(p.z, 0.0, -p.x))
+            }
+            None => Vector::new(p.z, 0.0, -p.x),
+        }
+    }
 }
 
 impl Hit for Sphere {
-    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision> {
-        let oc = ray.origin - self.center;
+    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision<'_>> {
+        count_intersection_test();
+        let center = self.center_at(ray.time);
+        let oc = ray.origin - center;
         let a = ray.direction.length_squared();
         let half_b = oc.dot(&ray.direction);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -83,13 +257,14 @@ impl Hit for Sphere {
 
         let t = root;
         let point = ray.at(t);
-        let normal = (point - self.center) / self.radius;
+        let normal = (point - center) / self.radius;
         let (normal, facing) = set_facing(ray, normal);
-        let (u, v) = self.uv(&point);
+        let (u, v) = self.uv(&point, center);
 
         Some(Collision {
             point,
             normal,
+            tangent: self.tangent(&point, center),
             t,
             u,
             v,
@@ -99,6 +274,23 @@ impl Hit for Sphere {
     }
 }
 
+impl Bounded for Sphere {
+    fn bounding_box(&self) -> Aabb {
+        let extent = Vector::new(self.radius, self.radius, self.radius);
+        let start = Aabb::new(self.center - extent, self.center + extent);
+        match self.center_end {
+            Some(end) => start.union(&Aabb::new(end - extent, end + extent)),
+            None => start,
+        }
+    }
+}
+
+impl MemoryEstimate for Sphere {
+    fn memory_estimate(&self) -> usize {
+        std::mem::size_of::<Self>() + self.material.heap_size()
+    }
+}
+
 pub struct Quad {
     q: Point,
     u: Vector,
@@ -126,8 +318,48 @@ impl Quad {
         }
     }
 
+    /// Corner the quad's edges start from
+    pub fn origin(&self) -> Point {
+        self.q
+    }
+
+    pub fn edge_u(&self) -> Vector {
+        self.u
+    }
+
+    pub fn edge_v(&self) -> Vector {
+        self.v
+    }
+
+    pub fn normal(&self) -> Vector {
+        self.normal
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Density, per unit solid angle, of `random_towards` picking `direction` from `origin`.
+    /// Zero for directions that miss the quad.
+    pub fn pdf_value(&self, origin: &Point, direction: &Vector) -> f64 {
+        let ray = Ray::new(*origin, *direction);
+        let Some(hit) = self.hit(&ray, 0.001..f64::INFINITY) else {
+            return 0.0;
+        };
+
+        let area = self.u.cross(&self.v).length();
+        let distance_squared = hit.t * hit.t * direction.length_squared();
+        let cosine = (direction.dot(&self.normal) / direction.length()).abs();
+        distance_squared / (cosine * area)
+    }
+
+    /// Direction from `origin` to a point picked uniformly on the quad
+    pub fn random_towards(&self, origin: &Point) -> Vector {
+        self.q + rng::random::<f64>() * self.u + rng::random::<f64>() * self.v - *origin
+    }
+
     fn uv(alpha: f64, beta: f64) -> Option<(f64, f64)> {
-        if alpha < 0.0 || 1.0 < alpha || beta < 0.0 || 1.0 < beta {
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
             None
         } else {
             Some((alpha, beta))
@@ -136,7 +368,8 @@ impl Quad {
 }
 
 impl Hit for Quad {
-    fn hit(&self, ray: &Ray, ray_t: Range<f64>) -> Option<Collision> {
+    fn hit(&self, ray: &Ray, ray_t: Range<f64>) -> Option<Collision<'_>> {
+        count_intersection_test();
         let denominator = self.normal.dot(&ray.direction);
 
         if denominator.abs() < 1e-8 {
@@ -153,15 +386,208 @@ impl Hit for Quad {
         let alpha = self.w.dot(&planar_hit_vector.cross(&self.v));
         let beta = self.w.dot(&self.u.cross(&planar_hit_vector));
 
-        let Some((u, v)) = Quad::uv(alpha, beta) else {
+        let (u, v) = Quad::uv(alpha, beta)?;
+
+        let (normal, facing) = set_facing(ray, self.normal);
+
+        Some(Collision {
+            point: intersection,
+            normal,
+            tangent: self.u,
+            t,
+            u,
+            v,
+            facing,
+            material: &self.material,
+        })
+    }
+}
+
+impl Bounded for Quad {
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(self.q, self.q + self.u + self.v)
+            .union(&Aabb::new(self.q + self.u, self.q + self.v))
+    }
+}
+
+impl MemoryEstimate for Quad {
+    fn memory_estimate(&self) -> usize {
+        std::mem::size_of::<Self>() + self.material.heap_size()
+    }
+}
+
+/// Infinite plane through `point`, e.g. for a ground that doesn't curve away like a huge sphere
+pub struct Plane {
+    point: Point,
+    normal: Vector,
+    /// Orthonormal in-plane axes, u and v are the hit point's coordinates along them
+    axis_u: Vector,
+    axis_v: Vector,
+    material: Material,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vector, material: Material) -> Self {
+        let normal = normal.normalize();
+        // any direction that isn't parallel to the normal gives a valid basis
+        let helper = if normal.x.abs() > 0.9 {
+            Vector::new(0.0, 1.0, 0.0)
+        } else {
+            Vector::new(1.0, 0.0, 0.0)
+        };
+        let axis_u = helper.cross(&normal).normalize();
+        let axis_v = normal.cross(&axis_u);
+        Plane {
+            point,
+            normal,
+            axis_u,
+            axis_v,
+            material,
+        }
+    }
+
+    pub fn point(&self) -> Point {
+        self.point
+    }
+
+    pub fn normal(&self) -> Vector {
+        self.normal
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+impl Hit for Plane {
+    fn hit(&self, ray: &Ray, ray_t: Range<f64>) -> Option<Collision<'_>> {
+        count_intersection_test();
+        let denominator = self.normal.dot(&ray.direction);
+
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = self.normal.dot(&(self.point - ray.origin)) / denominator;
+        if !ray_t.contains(&t) {
             return None;
+        }
+
+        let intersection = ray.at(t);
+        let offset = intersection - self.point;
+        let (normal, facing) = set_facing(ray, self.normal);
+
+        Some(Collision {
+            point: intersection,
+            normal,
+            tangent: self.axis_u,
+            t,
+            u: offset.dot(&self.axis_u),
+            v: offset.dot(&self.axis_v),
+            facing,
+            material: &self.material,
+        })
+    }
+}
+
+impl Bounded for Plane {
+    /// Unbounded, except along the normal when it lines up with an axis
+    fn bounding_box(&self) -> Aabb {
+        let extent = |axis| match component(&self.normal, axis).abs() == 1.0 {
+            true => (component(&self.point, axis), component(&self.point, axis)),
+            false => (f64::NEG_INFINITY, f64::INFINITY),
+        };
+        let [x, y, z] = [0, 1, 2].map(extent);
+        Aabb::new(Point::new(x.0, y.0, z.0), Point::new(x.1, y.1, z.1))
+    }
+}
+
+impl MemoryEstimate for Plane {
+    fn memory_estimate(&self) -> usize {
+        std::mem::size_of::<Self>() + self.material.heap_size()
+    }
+}
+
+/// Flat circle, for round lights and table tops
+pub struct Disk {
+    center: Point,
+    normal: Vector,
+    radius: f64,
+    /// In-plane axes the polar angle is measured from and towards
+    axis_u: Vector,
+    axis_v: Vector,
+    material: Material,
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vector, radius: f64, material: Material) -> Self {
+        let normal = normal.normalize();
+        let helper = if normal.x.abs() > 0.9 {
+            Vector::new(0.0, 1.0, 0.0)
+        } else {
+            Vector::new(1.0, 0.0, 0.0)
         };
+        let axis_u = helper.cross(&normal).normalize();
+        let axis_v = normal.cross(&axis_u);
+        Disk {
+            center,
+            normal,
+            radius,
+            axis_u,
+            axis_v,
+            material,
+        }
+    }
+
+    pub fn center(&self) -> Point {
+        self.center
+    }
+
+    pub fn normal(&self) -> Vector {
+        self.normal
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+impl Hit for Disk {
+    fn hit(&self, ray: &Ray, ray_t: Range<f64>) -> Option<Collision<'_>> {
+        count_intersection_test();
+        let denominator = self.normal.dot(&ray.direction);
+
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = self.normal.dot(&(self.center - ray.origin)) / denominator;
+        if !ray_t.contains(&t) {
+            return None;
+        }
+
+        let intersection = ray.at(t);
+        let offset = intersection - self.center;
+        let distance = offset.length();
+        if distance > self.radius {
+            return None;
+        }
+
+        // u goes once around the disk, v out from the center to the rim
+        let angle = offset.dot(&self.axis_v).atan2(offset.dot(&self.axis_u));
+        let u = (angle + std::f64::consts::PI) / (2.0 * std::f64::consts::PI);
+        let v = distance / self.radius;
 
         let (normal, facing) = set_facing(ray, self.normal);
 
         Some(Collision {
             point: intersection,
             normal,
+            tangent: self.normal.cross(&offset),
             t,
             u,
             v,
@@ -171,6 +597,124 @@ impl Hit for Quad {
     }
 }
 
+impl Bounded for Disk {
+    fn bounding_box(&self) -> Aabb {
+        // a disk reaches less far along the axes its normal leans towards
+        let n = self.normal;
+        let extent = Vector::new(
+            (1.0 - n.x * n.x).max(0.0).sqrt(),
+            (1.0 - n.y * n.y).max(0.0).sqrt(),
+            (1.0 - n.z * n.z).max(0.0).sqrt(),
+        ) * self.radius;
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+}
+
+impl MemoryEstimate for Disk {
+    fn memory_estimate(&self) -> usize {
+        std::mem::size_of::<Self>() + self.material.heap_size()
+    }
+}
+
+pub struct Triangle {
+    vertices: [Point; 3],
+    /// Per-vertex normals for smooth shading, or `None` to use the flat face normal
+    normals: Option<[Vector; 3]>,
+    material: Material,
+}
+
+impl Triangle {
+    pub fn new(a: Point, b: Point, c: Point, material: Material) -> Self {
+        Triangle {
+            vertices: [a, b, c],
+            normals: None,
+            material,
+        }
+    }
+
+    /// Interpolates the given vertex normals across the face instead of using the flat normal
+    pub fn with_normals(mut self, normals: [Vector; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn vertices(&self) -> [Point; 3] {
+        self.vertices
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+impl Hit for Triangle {
+    /// Möller–Trumbore intersection. `u` and `v` are the barycentric weights of the second and
+    /// third vertices.
+    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision<'_>> {
+        count_intersection_test();
+        let [a, b, c] = self.vertices;
+        let edge_1 = b - a;
+        let edge_2 = c - a;
+
+        let p = ray.direction.cross(&edge_2);
+        let determinant = edge_1.dot(&p);
+        // the ray is parallel to the triangle
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+
+        let s = ray.origin - a;
+        let u = s.dot(&p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(&edge_1);
+        let v = ray.direction.dot(&q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let root = edge_2.dot(&q) * inverse;
+        if !t.contains(&root) {
+            return None;
+        }
+
+        let normal = match self.normals {
+            Some([n_a, n_b, n_c]) => ((1.0 - u - v) * n_a + u * n_b + v * n_c).normalize(),
+            None => edge_1.cross(&edge_2).normalize(),
+        };
+        let (normal, facing) = set_facing(ray, normal);
+
+        Some(Collision {
+            point: ray.at(root),
+            normal,
+            tangent: edge_1,
+            t: root,
+            u,
+            v,
+            facing,
+            material: &self.material,
+        })
+    }
+}
+
+impl Bounded for Triangle {
+    fn bounding_box(&self) -> Aabb {
+        let [a, b, c] = self.vertices;
+        Aabb::new(a, b).union(&Aabb::new(c, c))
+    }
+}
+
+impl MemoryEstimate for Triangle {
+    fn memory_estimate(&self) -> usize {
+        std::mem::size_of::<Self>() + self.material.heap_size()
+    }
+}
+
+/// The six faces of the box between corners `a` and `b`, turned by `quat` about its middle.
+/// Every face's `u × v` points out of the box.
 pub fn build_cuboid(a: Point, b: Point, quat: Quaternion, material: Material) -> [Quad; 6] {
     let min = Point::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
     let max = Point::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
@@ -181,40 +725,79 @@ pub fn build_cuboid(a: Point, b: Point, quat: Quaternion, material: Material) ->
     let dy = Vector::new(0.0, max.y - min.y, 0.0);
     let dz = Vector::new(0.0, 0.0, max.z - min.z);
 
-    let dx = quat.rotate_point(dx);
-    let dy = quat.rotate_point(dy);
-    let dz = quat.rotate_point(dz);
+    // each face starts from its own corner, its edges counterclockwise when seen from outside
+    let faces = [
+        // front, +z
+        (Point::new(min.x, min.y, max.z), dx, dy),
+        // right, +x
+        (Point::new(max.x, min.y, max.z), -dz, dy),
+        // back, -z
+        (Point::new(max.x, min.y, min.z), -dx, dy),
+        // left, -x
+        (Point::new(min.x, min.y, min.z), dz, dy),
+        // top, +y
+        (Point::new(min.x, max.y, max.z), dx, -dz),
+        // bottom, -y
+        (Point::new(min.x, min.y, max.z), -dz, dx),
+    ];
 
-    // TODO: figure out quaternion rotation
+    // every corner turns the same way, so work out the rotation once
+    let matrix = quat.to_matrix();
+    faces.map(|(q, u, v)| {
+        Quad::new(
+            rotate_about_midpoint(q, midpoint, &matrix),
+            apply_matrix(&matrix, u),
+            apply_matrix(&matrix, v),
+            material.clone(),
+        )
+    })
+}
 
-    [
-        Quad::new(rotate_about_midpoint(Point::new(min.x, min.y, max.z), midpoint, quat), dx, dy, material.clone()),
-        Quad::new(rotate_about_midpoint(Point::new(max.x, min.y, max.z), midpoint, quat), -dz, dy, material.clone()),
-        Quad::new(rotate_about_midpoint(Point::new(max.x, min.y, min.z), midpoint, quat), -dx, dy, material.clone()),
-        Quad::new(rotate_about_midpoint(Point::new(min.x, min.y, min.z), midpoint, quat), dz, dy, material.clone()),
-        Quad::new(rotate_about_midpoint(Point::new(min.x, max.y, max.z), midpoint, quat), dx, -dz, material.clone()),
-        Quad::new(rotate_about_midpoint(Point::new(min.x, min.y, min.z), midpoint, quat), dx, dz, material.clone()),
-    ]
+fn rotate_about_midpoint(point: Point, midpoint: Point, matrix: &[[f64; 3]; 3]) -> Point {
+    midpoint + apply_matrix(matrix, point - midpoint)
 }
 
-fn rotate_about_midpoint(point: Point, midpoint: Point, quat: Quaternion) -> Point {
-    let v = point - midpoint;
-    let v = quat.rotate_point(v);
-    midpoint + v
+fn apply_matrix(matrix: &[[f64; 3]; 3], v: Vector) -> Vector {
+    let [x, y, z] = matrix.map(|row| row[0] * v.x + row[1] * v.y + row[2] * v.z);
+    Vector::new(x, y, z)
 }
 
 pub struct Collection {
     pub objects: Vec<Object>,
 }
 
+/// Hits closer together than this fraction of their distance are on the same surface, like
+/// coplanar quads or concentric spheres of the same radius. Rounding would otherwise pick between
+/// them arbitrarily and the surface would flicker between their materials.
+const COINCIDENT_TOLERANCE: f64 = 1e-9;
+
+/// Whether `hit` replaces the closest hit so far, at `closest`. The earlier object keeps a
+/// coincident surface.
+fn is_closer(hit: &Collision, closest: f64) -> bool {
+    hit.t < closest * (1.0 - COINCIDENT_TOLERANCE)
+}
+
+impl Collection {
+    /// Builds a BVH over the objects up front, so each ray only tests the objects whose bounds it
+    /// passes through instead of scanning all of them
+    pub fn with_bvh(objects: Vec<Object>) -> Self {
+        if objects.len() < 2 {
+            return Collection { objects };
+        }
+        Collection {
+            objects: vec![BvhNode::build(objects)],
+        }
+    }
+}
+
 impl Hit for Collection {
-    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision> {
+    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision<'_>> {
         let mut closest = t.end;
         let mut record = None;
 
         for object in &self.objects {
             if let Some(hit) = object.hit(ray, t.clone()) {
-                if hit.t < closest {
+                if is_closer(&hit, closest) {
                     closest = hit.t;
                     record = Some(hit);
                 }
@@ -225,28 +808,1264 @@ impl Hit for Collection {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Bounded for Collection {
+    fn bounding_box(&self) -> Aabb {
+        bounds_of(&self.objects)
+    }
+}
 
-    #[test]
-    fn test_set_facing() {
-        let ray = Ray {
-            origin: Point::new(0.0, 0.0, 0.0),
-            direction: Vector::new(1.0, 0.0, 0.0),
-        };
-        let normal = Vector::new(1.0, 0.0, 0.0);
-        let (normal, facing) = set_facing(&ray, normal);
-        assert_eq!(normal, Vector::new(-1.0, -0.0, -0.0));
-        assert_eq!(facing, Facing::Outward);
+impl MemoryEstimate for Collection {
+    fn memory_estimate(&self) -> usize {
+        let unused = self.objects.capacity() - self.objects.len();
+        std::mem::size_of::<Self>()
+            + unused * std::mem::size_of::<Object>()
+            + self
+                .objects
+                .iter()
+                .map(|object| object.memory_estimate())
+                .sum::<usize>()
+    }
+}
 
-        let ray = Ray {
-            origin: Point::new(0.0, 0.0, 0.0),
-            direction: Vector::new(-1.0, 0.0, 0.0),
+/// Objects bucketed into a uniform 3D grid over their bounds. A ray steps through the cells it
+/// crosses in order (a 3D DDA), only testing the objects overlapping each cell, and stops as soon
+/// as the closest hit lies within the cell it has reached.
+///
+/// Works best for many similarly sized objects spread evenly through space, like the Many Spheres
+/// scene, where a BVH spends most of its time descending the tree.
+pub struct GridCollection {
+    bounds: Aabb,
+    resolution: [usize; 3],
+    cell_size: Vector,
+    /// Indices into `objects` of the objects overlapping each cell, x varying fastest
+    cells: Vec<Vec<usize>>,
+    objects: Vec<Object>,
+    /// Objects without finite bounds (e.g. planes), tested for every ray
+    unbounded: Vec<Object>,
+}
+
+impl GridCollection {
+    /// Average number of objects per cell the resolution aims for
+    const DENSITY: f64 = 3.0;
+    const MAX_RESOLUTION: usize = 64;
+
+    pub fn new(objects: Vec<Object>) -> Self {
+        let (objects, unbounded): (Vec<_>, Vec<_>) = objects.into_iter().partition(|object| {
+            let bounds = object.bounding_box();
+            !bounds.is_empty()
+                && (0..3).all(|axis| {
+                    component(&bounds.min, axis).is_finite()
+                        && component(&bounds.max, axis).is_finite()
+                })
+        });
+
+        let bounds = bounds_of(&objects);
+        if objects.is_empty() {
+            return GridCollection {
+                bounds,
+                resolution: [1; 3],
+                cell_size: Vector::new(1.0, 1.0, 1.0),
+                cells: vec![vec![]],
+                objects,
+                unbounded,
+            };
+        }
+
+        // pick about `DENSITY` objects per cell, with roughly cubic cells
+        let size = bounds.max - bounds.min;
+        let longest = component(&size, bounds.longest_axis());
+        let thickness = |axis| component(&size, axis).max(longest * 1e-3);
+        let volume = thickness(0) * thickness(1) * thickness(2);
+        let cells_per_unit = (Self::DENSITY * objects.len() as f64 / volume).cbrt();
+        let resolution = [0, 1, 2].map(|axis| {
+            ((thickness(axis) * cells_per_unit).round() as usize).clamp(1, Self::MAX_RESOLUTION)
+        });
+        let cell_size = Vector::new(
+            size.x / resolution[0] as f64,
+            size.y / resolution[1] as f64,
+            size.z / resolution[2] as f64,
+        );
+
+        let mut grid = GridCollection {
+            bounds,
+            resolution,
+            cell_size,
+            cells: vec![vec![]; resolution.iter().product()],
+            objects: vec![],
+            unbounded,
+        };
+        for (index, object) in objects.iter().enumerate() {
+            let object_bounds = object.bounding_box();
+            let min = grid.cell_of(&object_bounds.min);
+            let max = grid.cell_of(&object_bounds.max);
+            for z in min[2]..=max[2] {
+                for y in min[1]..=max[1] {
+                    for x in min[0]..=max[0] {
+                        let cell = grid.cell_index([x, y, z]);
+                        grid.cells[cell].push(index);
+                    }
+                }
+            }
+        }
+        grid.objects = objects;
+        grid
+    }
+
+    /// Coordinates of the cell containing `point`, clamped to the grid
+    fn cell_of(&self, point: &Point) -> [usize; 3] {
+        [0, 1, 2].map(|axis| {
+            let offset = component(point, axis) - component(&self.bounds.min, axis);
+            let cell = (offset / component(&self.cell_size, axis)).floor();
+            // the cast saturates, so NaN and negative offsets land in the first cell
+            (cell as usize).min(self.resolution[axis] - 1)
+        })
+    }
+
+    fn cell_index(&self, [x, y, z]: [usize; 3]) -> usize {
+        (z * self.resolution[1] + y) * self.resolution[0] + x
+    }
+}
+
+impl Hit for GridCollection {
+    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision<'_>> {
+        let mut closest = t.end;
+        let mut record = None;
+
+        for object in &self.unbounded {
+            if let Some(hit) = object.hit(ray, t.start..closest) {
+                if is_closer(&hit, closest) {
+                    closest = hit.t;
+                    record = Some(hit);
+                }
+            }
+        }
+
+        let Some(inside) = self.bounds.hit_range(ray, t.start..closest) else {
+            return record;
+        };
+
+        let mut cell = self.cell_of(&ray.at(inside.start));
+        let mut step = [0isize; 3];
+        let mut t_next = [f64::INFINITY; 3];
+        let mut t_delta = [f64::INFINITY; 3];
+        for axis in 0..3 {
+            let direction = component(&ray.direction, axis);
+            let origin = component(&ray.origin, axis);
+            let size = component(&self.cell_size, axis);
+            let min = component(&self.bounds.min, axis);
+            if direction > 0.0 {
+                step[axis] = 1;
+                t_next[axis] = (min + (cell[axis] + 1) as f64 * size - origin) / direction;
+                t_delta[axis] = size / direction;
+            } else if direction < 0.0 {
+                step[axis] = -1;
+                t_next[axis] = (min + cell[axis] as f64 * size - origin) / direction;
+                t_delta[axis] = -size / direction;
+            }
+        }
+
+        loop {
+            for &index in &self.cells[self.cell_index(cell)] {
+                if let Some(hit) = self.objects[index].hit(ray, t.start..closest) {
+                    if is_closer(&hit, closest) {
+                        closest = hit.t;
+                        record = Some(hit);
+                    }
+                }
+            }
+
+            let axis = (0..3)
+                .min_by(|&a, &b| t_next[a].total_cmp(&t_next[b]))
+                .unwrap();
+            let exit = t_next[axis];
+            // nothing in a later cell can be closer than a hit inside this one
+            if closest <= exit || exit >= inside.end {
+                break;
+            }
+
+            let next = cell[axis] as isize + step[axis];
+            if next < 0 || next as usize >= self.resolution[axis] {
+                break;
+            }
+            cell[axis] = next as usize;
+            t_next[axis] += t_delta[axis];
+        }
+
+        record
+    }
+}
+
+impl Bounded for GridCollection {
+    fn bounding_box(&self) -> Aabb {
+        self.bounds.union(&bounds_of(&self.unbounded))
+    }
+}
+
+impl MemoryEstimate for GridCollection {
+    fn memory_estimate(&self) -> usize {
+        let cells = self
+            .cells
+            .iter()
+            .map(|cell| cell.capacity() * std::mem::size_of::<usize>())
+            .sum::<usize>();
+        let unused = self.objects.capacity() - self.objects.len() + self.unbounded.capacity()
+            - self.unbounded.len();
+        std::mem::size_of::<Self>()
+            + self.cells.capacity() * std::mem::size_of::<Vec<usize>>()
+            + cells
+            + unused * std::mem::size_of::<Object>()
+            + self
+                .objects
+                .iter()
+                .chain(&self.unbounded)
+                .map(|object| object.memory_estimate())
+                .sum::<usize>()
+    }
+}
+
+/// Objects sharing one transform. The ray is moved into the group's local space once, rather than
+/// transforming every child separately.
+pub struct Group {
+    rotation: Quaternion,
+    translation: Vector,
+    children: Vec<Object>,
+}
+
+impl Group {
+    /// Children are rotated about the local origin, then moved by `translation`
+    pub fn new(rotation: Quaternion, translation: Vector, children: Vec<Object>) -> Self {
+        Group {
+            rotation,
+            translation,
+            children,
+        }
+    }
+
+    pub fn children(&self) -> &[Object] {
+        &self.children
+    }
+}
+
+impl Hit for Group {
+    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision<'_>> {
+        let inverse = self.rotation.inverse();
+        // the direction isn't normalized, so t is the same in both spaces
+        let local_ray = Ray {
+            origin: inverse.rotate_point(ray.origin - self.translation),
+            direction: inverse.rotate_point(ray.direction),
+            media: ray.media,
+            time: ray.time,
+        };
+
+        let mut closest = t.end;
+        let mut record = None;
+        for object in &self.children {
+            if let Some(hit) = object.hit(&local_ray, t.clone()) {
+                if is_closer(&hit, closest) {
+                    closest = hit.t;
+                    record = Some(hit);
+                }
+            }
+        }
+
+        record.map(|hit| Collision {
+            point: self.rotation.rotate_point(hit.point) + self.translation,
+            normal: self.rotation.rotate_point(hit.normal),
+            tangent: self.rotation.rotate_point(hit.tangent),
+            ..hit
+        })
+    }
+}
+
+impl Bounded for Group {
+    fn bounding_box(&self) -> Aabb {
+        bounds_of(&self.children).transformed(self.rotation, self.translation)
+    }
+}
+
+impl MemoryEstimate for Group {
+    fn memory_estimate(&self) -> usize {
+        let unused = self.children.capacity() - self.children.len();
+        std::mem::size_of::<Self>()
+            + unused * std::mem::size_of::<Object>()
+            + self
+                .children
+                .iter()
+                .map(|object| object.memory_estimate())
+                .sum::<usize>()
+    }
+}
+
+/// Moves an object by `offset` without touching its geometry. The ray is moved the other way
+/// instead, and the hit point moved back.
+pub struct Translate {
+    object: Box<Object>,
+    offset: Vector,
+}
+
+impl Translate {
+    pub fn new(object: Object, offset: Vector) -> Self {
+        Translate {
+            object: Box::new(object),
+            offset,
+        }
+    }
+
+    pub fn object(&self) -> &Object {
+        &self.object
+    }
+
+    pub fn offset(&self) -> Vector {
+        self.offset
+    }
+}
+
+impl Hit for Translate {
+    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision<'_>> {
+        let local_ray = Ray {
+            origin: ray.origin - self.offset,
+            ..ray.clone()
+        };
+
+        self.object.hit(&local_ray, t).map(|hit| Collision {
+            point: hit.point + self.offset,
+            ..hit
+        })
+    }
+}
+
+impl Bounded for Translate {
+    fn bounding_box(&self) -> Aabb {
+        let bounds = self.object.bounding_box();
+        Aabb {
+            min: bounds.min + self.offset,
+            max: bounds.max + self.offset,
+        }
+    }
+}
+
+impl MemoryEstimate for Translate {
+    fn memory_estimate(&self) -> usize {
+        std::mem::size_of::<Self>() + self.object.memory_estimate()
+    }
+}
+
+/// Rotates an object about the origin without touching its geometry. The ray is rotated the other
+/// way instead, and the hit point and normal rotated back.
+pub struct Rotate {
+    object: Box<Object>,
+    rotation: Quaternion,
+}
+
+impl Rotate {
+    pub fn new(object: Object, rotation: Quaternion) -> Self {
+        Rotate {
+            object: Box::new(object),
+            rotation,
+        }
+    }
+
+    pub fn object(&self) -> &Object {
+        &self.object
+    }
+
+    pub fn rotation(&self) -> Quaternion {
+        self.rotation
+    }
+}
+
+impl Hit for Rotate {
+    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision<'_>> {
+        let inverse = self.rotation.inverse();
+        // directions keep their length, so `t` means the same distance in both spaces
+        let local_ray = Ray {
+            origin: inverse.rotate_point(ray.origin),
+            direction: inverse.rotate_point(ray.direction),
+            ..ray.clone()
         };
+
+        self.object.hit(&local_ray, t).map(|hit| Collision {
+            point: self.rotation.rotate_point(hit.point),
+            normal: self.rotation.rotate_point(hit.normal),
+            tangent: self.rotation.rotate_point(hit.tangent),
+            ..hit
+        })
+    }
+}
+
+impl Bounded for Rotate {
+    fn bounding_box(&self) -> Aabb {
+        let bounds = self.object.bounding_box();
+        if bounds.is_empty() {
+            return bounds;
+        }
+        bounds.transformed(self.rotation, Vector::ZERO)
+    }
+}
+
+impl MemoryEstimate for Rotate {
+    fn memory_estimate(&self) -> usize {
+        std::mem::size_of::<Self>() + self.object.memory_estimate()
+    }
+}
+
+/// Smoke or fog of uniform density filling a closed boundary. Rays travelling through it scatter
+/// at a random distance, more often the denser it is.
+pub struct ConstantMedium {
+    boundary: Box<Object>,
+    /// Negative inverse of the density, so the scatter distance is this times ln(random)
+    neg_inv_density: f64,
+    phase_function: Material,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Object, density: f64, texture: Texture) -> Self {
+        ConstantMedium {
+            boundary: Box::new(boundary),
+            neg_inv_density: -1.0 / density,
+            phase_function: Material::Isotropic(Isotropic { texture }),
+        }
+    }
+
+    pub fn boundary(&self) -> &Object {
+        &self.boundary
+    }
+
+    pub fn density(&self) -> f64 {
+        -1.0 / self.neg_inv_density
+    }
+}
+
+impl Hit for ConstantMedium {
+    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision<'_>> {
+        // where the ray enters and leaves the boundary, even if that's behind its origin
+        let entry = self.boundary.hit(ray, f64::NEG_INFINITY..f64::INFINITY)?;
+        let exit = self.boundary.hit(ray, entry.t + 0.0001..f64::INFINITY)?;
+
+        let start = entry.t.max(t.start).max(0.0);
+        let end = exit.t.min(t.end);
+        if start >= end {
+            return None;
+        }
+
+        let ray_length = ray.direction.length();
+        let distance_inside = (end - start) * ray_length;
+        let hit_distance = self.neg_inv_density * rng::random::<f64>().ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = start + hit_distance / ray_length;
+        Some(Collision {
+            point: ray.at(t),
+            // arbitrary, the phase function doesn't use it
+            normal: Vector::new(1.0, 0.0, 0.0),
+            tangent: Vector::ZERO,
+            t,
+            u: 0.0,
+            v: 0.0,
+            facing: Facing::Inward,
+            material: &self.phase_function,
+        })
+    }
+}
+
+impl Bounded for ConstantMedium {
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+impl MemoryEstimate for ConstantMedium {
+    fn memory_estimate(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.boundary.memory_estimate()
+            + self.phase_function.heap_size()
+    }
+}
+
+fn bounds_of(objects: &[Object]) -> Aabb {
+    objects.iter().fold(Aabb::EMPTY, |bounds, object| {
+        bounds.union(&object.bounding_box())
+    })
+}
+
+/// Node of a bounding volume hierarchy. Rays that miss the node's box skip everything below it.
+pub struct BvhNode {
+    bounds: Aabb,
+    left: Box<Object>,
+    right: Box<Object>,
+}
+
+impl BvhNode {
+    /// Recursively splits the objects in half along the longest axis of their centers' spread.
+    /// A single object is returned as is.
+    pub fn build(mut objects: Vec<Object>) -> Object {
+        assert!(!objects.is_empty(), "can't build a BVH without any objects");
+        if objects.len() == 1 {
+            return objects.pop().unwrap();
+        }
+
+        let centroids = objects.iter().fold(Aabb::EMPTY, |bounds, object| {
+            let center = object.bounding_box().centroid();
+            bounds.union(&Aabb {
+                min: center,
+                max: center,
+            })
+        });
+        let axis = centroids.longest_axis();
+        objects.sort_by(|a, b| {
+            let a = component(&a.bounding_box().centroid(), axis);
+            let b = component(&b.bounding_box().centroid(), axis);
+            a.total_cmp(&b)
+        });
+
+        let right = objects.split_off(objects.len() / 2);
+        let left = BvhNode::build(objects);
+        let right = BvhNode::build(right);
+        Object::BvhNode(BvhNode {
+            bounds: left.bounding_box().union(&right.bounding_box()),
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+    }
+
+    pub fn children(&self) -> [&Object; 2] {
+        [&self.left, &self.right]
+    }
+}
+
+impl Hit for BvhNode {
+    fn hit(&self, ray: &Ray, t: Range<f64>) -> Option<Collision<'_>> {
+        if !self.bounds.hit(ray, t.clone()) {
+            return None;
+        }
+
+        let left = self.left.hit(ray, t.clone());
+        let end = left.as_ref().map_or(t.end, |hit| hit.t);
+        match self.right.hit(ray, t.start..end) {
+            Some(right) if is_closer(&right, end) => Some(right),
+            _ => left,
+        }
+    }
+}
+
+impl Bounded for BvhNode {
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+}
+
+impl MemoryEstimate for BvhNode {
+    fn memory_estimate(&self) -> usize {
+        std::mem::size_of::<Self>() + self.left.memory_estimate() + self.right.memory_estimate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::{Dielectric, Lambertian};
+
+    #[test]
+    fn test_cuboid_faces_point_outward() {
+        let material = Material::Lambertian(Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5),
+        });
+        let (a, b) = (Point::new(1.0, 1.0, 1.0), Point::new(0.0, 0.0, 0.0));
+        let cuboid = build_cuboid(a, b, Quaternion::from_euler(0.0, 0.0, 0.0), material);
+
+        let midpoint = Point::new(0.5, 0.5, 0.5);
+        let mut normals = Vec::new();
+        let mut bounds = Aabb::EMPTY;
+        for quad in &cuboid {
+            let center = quad.q + quad.u / 2.0 + quad.v / 2.0;
+            let outward = center - midpoint;
+            // each face sits half the cube away from the middle, along its normal
+            assert!(
+                (quad.normal - outward * 2.0).is_near_zero(),
+                "{:?}",
+                quad.normal
+            );
+            normals.push(quad.normal);
+            bounds = bounds.union(&quad.bounding_box());
+        }
+        for (i, normal) in normals.iter().enumerate() {
+            let distinct = |other: &Vector| !(*other - *normal).is_near_zero();
+            assert!(normals[i + 1..].iter().all(distinct));
+        }
+
+        // flat faces are padded a little to keep their boxes hittable
+        assert!((bounds.min - b).length() < 1e-3, "{:?}", bounds);
+        assert!((bounds.max - a).length() < 1e-3, "{:?}", bounds);
+    }
+
+    #[test]
+    fn test_rotated_cuboid() {
+        let material = Material::Lambertian(Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5),
+        });
+        // long along z, then turned a quarter around y to be long along x
+        let cuboid = build_cuboid(
+            Point::new(-1.0, 0.0, -2.0),
+            Point::new(1.0, 1.0, 2.0),
+            Quaternion::from_euler(0.0, 0.0, 90.0_f64.to_radians()),
+            material,
+        );
+        let collection = Collection {
+            objects: cuboid.into_iter().map(Object::Quad).collect(),
+        };
+
+        let along_x = Ray::new(Point::new(-5.0, 0.5, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let hit = collection.hit(&along_x, 0.001..f64::INFINITY).unwrap();
+        assert!((hit.t - 3.0).abs() < 1e-9, "{}", hit.t);
+
+        let along_z = Ray::new(Point::new(0.0, 0.5, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = collection.hit(&along_z, 0.001..f64::INFINITY).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-9, "{}", hit.t);
+    }
+
+    #[test]
+    fn test_set_facing() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let normal = Vector::new(1.0, 0.0, 0.0);
+        let (normal, facing) = set_facing(&ray, normal);
+        assert_eq!(normal, Vector::new(-1.0, -0.0, -0.0));
+        assert_eq!(facing, Facing::Outward);
+
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(-1.0, 0.0, 0.0));
         let normal = Vector::new(1.0, 0.0, 0.0);
         let (normal, facing) = set_facing(&ray, normal);
         assert_eq!(normal, Vector::new(1.0, 0.0, 0.0));
         assert_eq!(facing, Facing::Inward);
     }
+
+    #[test]
+    fn test_coincident_quads_prefer_earlier_object() {
+        // the same tilted square twice, built from different corners, so the hit distances differ
+        // in the last few bits
+        let q = Point::new(0.1, 0.2, -2.3);
+        let u = Vector::new(1.7, 0.3, 0.9);
+        let v = Vector::new(-0.4, 1.3, 0.2);
+        let color = |r, g, b| {
+            Material::Lambertian(Lambertian {
+                albedo: Color::new(r, g, b),
+            })
+        };
+        let quads = || {
+            vec![
+                Object::Quad(Quad::new(q, u, v, color(1.0, 0.0, 0.0))),
+                Object::Quad(Quad::new(q + u, v, -u, color(0.0, 0.0, 1.0))),
+            ]
+        };
+        // every container that picks the closest of its children breaks the tie the same way
+        let containers = [
+            (
+                "collection",
+                Object::Collection(Collection { objects: quads() }),
+            ),
+            ("BVH", Object::Collection(Collection::with_bvh(quads()))),
+            ("grid", Object::GridCollection(GridCollection::new(quads()))),
+            (
+                "group",
+                Object::Group(Group::new(
+                    Quaternion::new(0.0, 0.0, 0.0, 1.0),
+                    Vector::ZERO,
+                    quads(),
+                )),
+            ),
+        ];
+
+        for (name, container) in &containers {
+            let mut first = 0;
+            for i in 0..1000 {
+                let (a, b) = ((i % 37) as f64 / 37.0, (i % 41) as f64 / 41.0);
+                let target = q + u * (0.01 + 0.98 * a) + v * (0.01 + 0.98 * b);
+                let ray = Ray::new(Point::new(0.0, 0.0, 0.0), target);
+                let hit = container.hit(&ray, 0.001..f64::INFINITY).unwrap();
+                let Material::Lambertian(lambertian) = hit.material else {
+                    panic!("Expected a Lambertian material");
+                };
+                if lambertian.albedo == Color::new(1.0, 0.0, 0.0) {
+                    first += 1;
+                }
+            }
+            assert_eq!(first, 1000, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_quad_accessors() {
+        let q = Point::new(1.0, 2.0, 3.0);
+        let u = Vector::new(2.0, 0.0, 0.0);
+        let v = Vector::new(0.0, 0.0, 4.0);
+        let material = Material::Lambertian(Lambertian {
+            albedo: Color::new(0.1, 0.2, 0.3),
+        });
+        let quad = Quad::new(q, u, v, material);
+
+        assert_eq!(quad.origin(), q);
+        assert_eq!(quad.edge_u(), u);
+        assert_eq!(quad.edge_v(), v);
+        assert_eq!(quad.normal(), Vector::new(0.0, -1.0, 0.0));
+        let Material::Lambertian(lambertian) = quad.material() else {
+            panic!("Expected a Lambertian material");
+        };
+        assert_eq!(lambertian.albedo, Color::new(0.1, 0.2, 0.3));
+    }
+
+    fn ground_plane() -> Plane {
+        Plane::new(
+            Point::new(0.0, -1.0, 0.0),
+            Vector::new(0.0, 2.0, 0.0),
+            Material::Lambertian(Lambertian {
+                albedo: Color::new(0.5, 0.5, 0.5),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_plane_hit() {
+        let plane = ground_plane();
+        assert_eq!(plane.normal(), Vector::new(0.0, 1.0, 0.0));
+
+        let ray = Ray::new(Point::new(100.0, 1.0, -50.0), Vector::new(0.0, -1.0, 1.0));
+        let hit = plane.hit(&ray, 0.001..f64::INFINITY).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-12);
+        assert_eq!(hit.point, Point::new(100.0, -1.0, -48.0));
+        assert_eq!(hit.normal, Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(hit.facing, Facing::Inward);
+        // u and v are the distances along the in-plane axes
+        assert!((hit.u.hypot(hit.v) - 100.0_f64.hypot(48.0)).abs() < 1e-9);
+
+        let below = Ray::new(Point::new(0.0, -3.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        let hit = plane.hit(&below, 0.001..f64::INFINITY).unwrap();
+        assert_eq!(hit.normal, Vector::new(0.0, -1.0, 0.0));
+        assert_eq!(hit.facing, Facing::Outward);
+
+        let away = Ray::new(Point::new(0.0, 1.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        assert!(plane.hit(&away, 0.001..f64::INFINITY).is_none());
+        let parallel = Ray::new(Point::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(plane.hit(&parallel, 0.001..f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_plane_bounding_box() {
+        let bounds = ground_plane().bounding_box();
+        assert!(bounds.min.y < -1.0 && bounds.max.y > -1.0);
+        assert!(bounds.max.y - bounds.min.y < 1e-3);
+        assert_eq!(bounds.min.x, f64::NEG_INFINITY);
+        assert_eq!(bounds.max.z, f64::INFINITY);
+
+        let tilted = Plane::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 0.0),
+            Material::Lambertian(Lambertian {
+                albedo: Color::WHITE,
+            }),
+        );
+        assert_eq!(tilted.bounding_box().max.y, f64::INFINITY);
+    }
+
+    fn tabletop() -> Disk {
+        Disk::new(
+            Point::new(1.0, 2.0, 3.0),
+            Vector::new(0.0, 0.0, 1.0),
+            2.0,
+            Material::Lambertian(Lambertian {
+                albedo: Color::new(0.5, 0.5, 0.5),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_disk_hit() {
+        let disk = tabletop();
+
+        let ray = Ray::new(Point::new(1.0, 3.5, 10.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = disk.hit(&ray, 0.001..f64::INFINITY).unwrap();
+        assert!((hit.t - 7.0).abs() < 1e-12);
+        assert_eq!(hit.normal, Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(hit.facing, Facing::Inward);
+        assert!((hit.v - 0.75).abs() < 1e-12);
+        assert!((0.0..=1.0).contains(&hit.u));
+
+        // the u of a point a quarter turn around is a quarter apart
+        let turned = Ray::new(Point::new(2.5, 2.0, 10.0), Vector::new(0.0, 0.0, -1.0));
+        let turned_hit = disk.hit(&turned, 0.001..f64::INFINITY).unwrap();
+        let du = (turned_hit.u - hit.u).rem_euclid(1.0);
+        assert!(
+            (du - 0.25).abs() < 1e-12 || (du - 0.75).abs() < 1e-12,
+            "{}",
+            du
+        );
+
+        let from_behind = Ray::new(Point::new(1.0, 2.0, -10.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = disk.hit(&from_behind, 0.001..f64::INFINITY).unwrap();
+        assert_eq!(hit.normal, Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(hit.facing, Facing::Outward);
+
+        // inside the disk's square but outside its circle
+        let corner = Ray::new(Point::new(2.5, 3.5, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(disk.hit(&corner, 0.001..f64::INFINITY).is_none());
+        let parallel = Ray::new(Point::new(1.0, 2.0, 10.0), Vector::new(0.0, 1.0, 0.0));
+        assert!(disk.hit(&parallel, 0.001..f64::INFINITY).is_none());
+
+        let bounds = disk.bounding_box();
+        assert_eq!(bounds.min.x, -1.0);
+        assert_eq!(bounds.max.y, 4.0);
+        assert!(bounds.max.z - bounds.min.z < 1e-3);
+    }
+
+    #[test]
+    fn test_sphere_accessors() {
+        let sphere = Sphere::new(
+            Point::new(0.0, 1.0, 0.0),
+            0.5,
+            Material::Dielectric(Dielectric {
+                refraction_index: 1.5,
+                absorption: None,
+                fuzz: 0.0,
+            }),
+        );
+
+        assert_eq!(sphere.center(), Point::new(0.0, 1.0, 0.0));
+        assert_eq!(sphere.radius(), 0.5);
+        assert!(matches!(sphere.material(), Material::Dielectric(_)));
+        assert!(sphere.orientation().is_none());
+    }
+
+    fn test_sphere() -> Sphere {
+        Sphere::new(
+            Point::new(1.0, 2.0, 3.0),
+            2.0,
+            Material::Lambertian(Lambertian {
+                albedo: Color::new(0.5, 0.5, 0.5),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_sphere_identity_orientation_keeps_uv() {
+        let oriented = test_sphere().with_orientation(Quaternion::new(0.0, 0.0, 0.0, 1.0));
+        let sphere = test_sphere();
+
+        for direction in [
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, -1.0),
+            Vector::new(0.6, 0.48, 0.64),
+            Vector::new(-0.36, -0.8, 0.48),
+        ] {
+            let point = sphere.center() + direction * sphere.radius();
+            let (u, v) = sphere.uv(&point, sphere.center());
+            let (oriented_u, oriented_v) = oriented.uv(&point, sphere.center());
+            assert!((u - oriented_u).abs() < 1e-8, "u: {} != {}", u, oriented_u);
+            assert!((v - oriented_v).abs() < 1e-8, "v: {} != {}", v, oriented_v);
+        }
+    }
+
+    #[test]
+    fn test_sphere_orientation_shifts_longitude() {
+        let quat = Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), 90.0_f64.to_radians());
+        let oriented = test_sphere().with_orientation(quat);
+        let sphere = test_sphere();
+
+        for direction in [
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(0.6, 0.48, 0.64),
+        ] {
+            let point = sphere.center() + direction * sphere.radius();
+            let (u, v) = sphere.uv(&point, sphere.center());
+            let (oriented_u, oriented_v) = oriented.uv(&point, sphere.center());
+            let shift = (oriented_u - u).rem_euclid(1.0);
+            assert!((shift - 0.25).abs() < 1e-8, "shift: {}", shift);
+            assert!((v - oriented_v).abs() < 1e-8, "v: {} != {}", v, oriented_v);
+        }
+    }
+
+    #[test]
+    fn test_tangents_follow_u() {
+        let quad = || {
+            Quad::new(
+                Point::new(-1.0, -1.0, 0.0),
+                Vector::new(2.0, 0.0, 0.0),
+                Vector::new(0.0, 2.0, 0.0),
+                Material::Lambertian(Lambertian {
+                    albedo: Color::new(0.5, 0.5, 0.5),
+                }),
+            )
+        };
+        let quarter_turn =
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), 90.0_f64.to_radians());
+        let axis = Vector::new(1.0, 0.0, 1.0).normalize();
+        let tilt = Quaternion::from_axis_angle(axis, 40.0_f64.to_radians());
+        // each shape with a point to look from and a point on it to look at
+        let cases = [
+            (
+                Object::Sphere(test_sphere()),
+                Point::new(5.0, 3.0, 4.0),
+                Point::new(1.0, 2.0, 3.0),
+            ),
+            (
+                Object::Sphere(test_sphere().with_orientation(tilt)),
+                Point::new(5.0, 3.0, 4.0),
+                Point::new(1.0, 2.0, 3.0),
+            ),
+            (
+                Object::Quad(quad()),
+                Point::new(0.3, 0.2, 5.0),
+                Point::new(0.1, -0.4, 0.0),
+            ),
+            (
+                Object::Rotate(Rotate::new(Object::Quad(quad()), quarter_turn)),
+                Point::new(5.0, 0.2, 0.3),
+                Point::new(0.0, 0.1, 0.4),
+            ),
+            (
+                Object::Disk(tabletop()),
+                Point::new(0.5, 3.0, 10.0),
+                Point::new(1.5, 2.5, 3.0),
+            ),
+            (
+                Object::Triangle(test_triangle()),
+                Point::new(0.2, 0.3, 5.0),
+                Point::new(1.0, 1.0, -2.0),
+            ),
+        ];
+
+        for (object, origin, target) in cases {
+            let towards = |target: Point| Ray::new(origin, target - origin);
+            let hit = object.hit(&towards(target), 0.001..f64::INFINITY).unwrap();
+            assert!(!hit.tangent.is_near_zero());
+            let tangent = hit.tangent.normalize();
+            assert!(tangent.dot(&hit.normal).abs() < 1e-9, "{:?}", tangent);
+
+            let nudged = hit.point + 1e-4 * tangent;
+            let next = object.hit(&towards(nudged), 0.001..f64::INFINITY).unwrap();
+            let du = (next.u - hit.u + 0.5).rem_euclid(1.0) - 0.5;
+            assert!(du > 0.0, "{:?} from {:?}: {}", tangent, origin, du);
+        }
+    }
+
+    #[test]
+    fn test_group_matches_transformed_children() {
+        let material = Material::Lambertian(Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5),
+        });
+        let rotation =
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), 90.0_f64.to_radians());
+        let translation = Vector::new(0.0, 0.5, -3.0);
+        let centers = [Point::new(1.0, 0.0, 0.0), Point::new(-1.0, 0.0, 0.5)];
+
+        let group = Group::new(
+            rotation,
+            translation,
+            centers
+                .iter()
+                .map(|&center| Object::Sphere(Sphere::new(center, 0.5, material.clone())))
+                .collect(),
+        );
+        let individual = Collection {
+            objects: centers
+                .iter()
+                .map(|&center| {
+                    Object::Sphere(Sphere::new(
+                        rotation.rotate_point(center) + translation,
+                        0.5,
+                        material.clone(),
+                    ))
+                })
+                .collect(),
+        };
+
+        let origin = Point::new(0.0, 0.5, 0.0);
+        let mut hits = 0;
+        for x in -20..=20 {
+            for y in -20..=20 {
+                let direction = Vector::new(x as f64 * 0.025, y as f64 * 0.025, -1.0);
+                let ray = Ray::new(origin, direction);
+                let grouped = group.hit(&ray, 0.001..f64::INFINITY);
+                let expected = individual.hit(&ray, 0.001..f64::INFINITY);
+
+                match (grouped, expected) {
+                    (Some(grouped), Some(expected)) => {
+                        hits += 1;
+                        assert!((grouped.t - expected.t).abs() < 1e-8);
+                        assert!((grouped.point - expected.point).length() < 1e-8);
+                        assert!((grouped.normal - expected.normal).length() < 1e-8);
+                        assert_eq!(grouped.facing, expected.facing);
+                    }
+                    (None, None) => {}
+                    _ => panic!("Group and transformed spheres disagree for {:?}", ray),
+                }
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn test_moving_sphere_follows_ray_time() {
+        let sphere = test_sphere().with_motion(Point::new(1.0, 6.0, 3.0));
+        assert_eq!(sphere.center_at(0.5), Point::new(1.0, 4.0, 3.0));
+
+        let ray_at = |time: f64| Ray {
+            time,
+            ..Ray::new(Point::new(1.0, 2.0, 10.0), Vector::new(0.0, 0.0, -1.0))
+        };
+        let start = sphere.hit(&ray_at(0.0), 0.001..f64::INFINITY).unwrap();
+        assert_eq!(start.point, Point::new(1.0, 2.0, 5.0));
+        assert_eq!(start.normal, Vector::new(0.0, 0.0, 1.0));
+        assert!(sphere.hit(&ray_at(1.0), 0.001..f64::INFINITY).is_none());
+
+        let bounds = sphere.bounding_box();
+        assert_eq!(bounds.min.y, 0.0);
+        assert_eq!(bounds.max.y, 8.0);
+    }
+
+    fn test_triangle() -> Triangle {
+        Triangle::new(
+            Point::new(0.0, 0.0, -2.0),
+            Point::new(3.0, 0.0, -2.0),
+            Point::new(0.0, 3.0, -2.0),
+            Material::Lambertian(Lambertian {
+                albedo: Color::new(0.5, 0.5, 0.5),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_triangle_hit_at_centroid() {
+        let triangle = test_triangle();
+        let ray = Ray::new(Point::new(1.0, 1.0, 2.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = triangle.hit(&ray, 0.001..f64::INFINITY).unwrap();
+
+        assert!((hit.t - 4.0).abs() < 1e-12, "{}", hit.t);
+        assert!((hit.point - Point::new(1.0, 1.0, -2.0)).is_near_zero());
+        assert!((hit.u - 1.0 / 3.0).abs() < 1e-12);
+        assert!((hit.v - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(hit.normal, Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(hit.facing, Facing::Inward);
+
+        // from behind, the normal is flipped to face the ray
+        let ray = Ray::new(Point::new(1.0, 1.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let hit = triangle.hit(&ray, 0.001..f64::INFINITY).unwrap();
+        assert_eq!(hit.normal, Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(hit.facing, Facing::Outward);
+    }
+
+    #[test]
+    fn test_triangle_misses() {
+        let triangle = test_triangle();
+        // parallel to the plane
+        let ray = Ray::new(Point::new(1.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        assert!(triangle.hit(&ray, 0.001..f64::INFINITY).is_none());
+        // through the plane, but outside the triangle
+        let ray = Ray::new(Point::new(2.0, 2.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(triangle.hit(&ray, 0.001..f64::INFINITY).is_none());
+        // pointing away from the plane
+        let ray = Ray::new(Point::new(1.0, 1.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(triangle.hit(&ray, 0.001..f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_triangle_interpolates_vertex_normals() {
+        let up = Vector::new(0.0, 0.0, 1.0);
+        let tilted = Vector::new(1.0, 0.0, 1.0).normalize();
+        let triangle = test_triangle().with_normals([up, tilted, up]);
+
+        let ray = Ray::new(Point::new(3.0, 0.0, 2.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = triangle.hit(&ray, 0.001..f64::INFINITY).unwrap();
+        assert!((hit.normal - tilted).is_near_zero(), "{:?}", hit.normal);
+    }
+
+    #[test]
+    fn test_translate_moves_hit_point() {
+        let ray = Ray::new(Point::new(1.0, 2.5, 10.0), Vector::new(0.0, 0.0, -1.0));
+        let sphere = test_sphere();
+        let moved = Translate::new(Object::Sphere(test_sphere()), Vector::new(0.0, 1.0, 0.0));
+
+        let hit = sphere.hit(&ray, 0.001..f64::INFINITY).unwrap();
+        let moved_ray = Ray::new(Point::new(1.0, 3.5, 10.0), Vector::new(0.0, 0.0, -1.0));
+        let moved_hit = moved.hit(&moved_ray, 0.001..f64::INFINITY).unwrap();
+        assert_eq!(moved_hit.point, hit.point + Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(moved_hit.normal, hit.normal);
+        assert_eq!(moved_hit.t, hit.t);
+
+        let bounds = moved.bounding_box();
+        assert_eq!(bounds.min.y, sphere.bounding_box().min.y + 1.0);
+
+        // the bottom of the original sphere is now empty space
+        let below = Ray::new(Point::new(1.0, 0.5, 10.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(sphere.hit(&below, 0.001..f64::INFINITY).is_some());
+        assert!(moved.hit(&below, 0.001..f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_rotate_turns_quad_normal() {
+        // a 2x2 quad centered on the origin, facing +z
+        let quad = Quad::new(
+            Point::new(-1.0, -1.0, 0.0),
+            Vector::new(2.0, 0.0, 0.0),
+            Vector::new(0.0, 2.0, 0.0),
+            Material::Lambertian(Lambertian {
+                albedo: Color::new(0.5, 0.5, 0.5),
+            }),
+        );
+        let quarter_turn =
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), 90.0_f64.to_radians());
+        let rotated = Rotate::new(Object::Quad(quad), quarter_turn);
+
+        // after the turn the quad lies in the x = 0 plane with its front facing -x
+        let ray = Ray::new(Point::new(5.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0));
+        let hit = rotated.hit(&ray, 0.001..f64::INFINITY).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-9);
+        assert!(
+            (hit.point - Point::new(0.0, 0.5, 0.5)).is_near_zero(),
+            "{:?}",
+            hit.point
+        );
+        assert!(
+            (hit.normal - Vector::new(1.0, 0.0, 0.0)).is_near_zero(),
+            "{:?}",
+            hit.normal
+        );
+        assert_eq!(hit.facing, Facing::Outward);
+
+        let ray = Ray::new(Point::new(-5.0, 0.5, 0.5), Vector::new(1.0, 0.0, 0.0));
+        let hit = rotated.hit(&ray, 0.001..f64::INFINITY).unwrap();
+        assert!(
+            (hit.normal - Vector::new(-1.0, 0.0, 0.0)).is_near_zero(),
+            "{:?}",
+            hit.normal
+        );
+        assert_eq!(hit.facing, Facing::Inward);
+
+        // edge on, the quad can no longer be hit head-on from +z
+        let ray = Ray::new(Point::new(0.5, 0.5, 5.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(rotated.hit(&ray, 0.001..f64::INFINITY).is_none());
+
+        let bounds = rotated.bounding_box();
+        assert!(bounds.max.x - bounds.min.x < 1e-3);
+        assert!((bounds.max.z - 1.0).abs() < 1e-9);
+    }
+
+    fn fog_ball(density: f64) -> ConstantMedium {
+        let boundary = Object::Sphere(Sphere::new(
+            Point::new(0.0, 0.0, -5.0),
+            1.0,
+            Material::Lambertian(Lambertian {
+                albedo: Color::WHITE,
+            }),
+        ));
+        let texture = Texture::Solid(crate::texture::Solid {
+            color: Color::new(0.2, 0.4, 0.6),
+        });
+        ConstantMedium::new(boundary, density, texture)
+    }
+
+    #[test]
+    fn test_constant_medium() {
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+
+        let dense = fog_ball(1e9);
+        let hit = dense.hit(&ray, 0.001..f64::INFINITY).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-6, "{}", hit.t);
+        assert!(matches!(hit.material, Material::Isotropic(_)));
+
+        // starting inside, the ray scatters right away rather than at the far side
+        let inside = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, -1.0));
+        let hit = dense.hit(&inside, 0.001..f64::INFINITY).unwrap();
+        assert!(hit.t < 0.01, "{}", hit.t);
+
+        let thin = fog_ball(1e-9);
+        assert!(thin.hit(&ray, 0.001..f64::INFINITY).is_none());
+
+        for _ in 0..100 {
+            if let Some(hit) = fog_ball(1.0).hit(&ray, 0.001..f64::INFINITY) {
+                assert!((4.0..=6.0).contains(&hit.t), "{}", hit.t);
+            }
+        }
+        let miss = Ray::new(Point::new(0.0, 2.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(dense.hit(&miss, 0.001..f64::INFINITY).is_none());
+    }
+
+    fn grid_of_spheres() -> Vec<Object> {
+        let material = Material::Lambertian(Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5),
+        });
+        let mut objects = vec![];
+        for a in -5..5 {
+            for b in -5..5 {
+                objects.push(Object::Sphere(Sphere::new(
+                    Point::new(a as f64, 0.2 * (a + b) as f64, b as f64 - 10.0),
+                    0.3,
+                    material.clone(),
+                )));
+            }
+        }
+        objects.push(Object::Quad(Quad::new(
+            Point::new(-10.0, -2.0, -20.0),
+            Vector::new(20.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 20.0),
+            material,
+        )));
+        objects
+    }
+
+    #[test]
+    fn test_bounding_boxes() {
+        let sphere = test_sphere();
+        let bounds = sphere.bounding_box();
+        assert_eq!(
+            bounds.min,
+            sphere.center() - Vector::new(1.0, 1.0, 1.0) * sphere.radius()
+        );
+        assert_eq!(
+            bounds.max,
+            sphere.center() + Vector::new(1.0, 1.0, 1.0) * sphere.radius()
+        );
+
+        let quad = Quad::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 0.0, 0.0),
+            Vector::new(0.0, 3.0, 0.0),
+            sphere.material().clone(),
+        );
+        let bounds = quad.bounding_box();
+        assert_eq!((bounds.max.x, bounds.max.y), (2.0, 3.0));
+        assert!(bounds.min.z < 0.0 && bounds.max.z > 0.0);
+    }
+
+    #[test]
+    fn test_bvh_matches_linear_scan() {
+        let linear = Collection {
+            objects: grid_of_spheres(),
+        };
+        let bvh = Collection::with_bvh(grid_of_spheres());
+        assert!(matches!(bvh.objects[..], [Object::BvhNode(_)]));
+
+        let origin = Point::new(0.0, 3.0, 2.0);
+        let mut hits = 0;
+        for x in -30..=30 {
+            for y in -30..=30 {
+                let direction = Vector::new(x as f64 * 0.02, y as f64 * 0.02 - 0.3, -1.0);
+                let ray = Ray::new(origin, direction);
+                let expected = linear.hit(&ray, 0.001..f64::INFINITY);
+                let actual = bvh.hit(&ray, 0.001..f64::INFINITY);
+
+                match (actual, expected) {
+                    (Some(actual), Some(expected)) => {
+                        hits += 1;
+                        assert_eq!(actual.t, expected.t);
+                        assert_eq!(actual.point, expected.point);
+                    }
+                    (None, None) => {}
+                    _ => panic!("BVH and linear scan disagree for {:?}", ray),
+                }
+            }
+        }
+        assert!(hits > 0);
+    }
 }
@@ -0,0 +1,272 @@
+use crate::color::Color;
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, ImageAttributes, Layer, LayerAttributes,
+    SmallVec, WritableImage,
+};
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Write};
+use std::path::Path;
+
+/// Output target that means "write to stdout" rather than to a file
+pub const STDOUT_TARGET: &str = "-";
+
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Deserialize,
+    Serialize,
+    strum_macros::Display,
+    clap::ValueEnum,
+)]
+pub enum OutputFormat {
+    #[default]
+    #[strum(to_string = "PNG")]
+    Png,
+    /// Linear, unclamped radiance as a float32 NumPy array of shape (height, width, 3)
+    #[strum(to_string = "NumPy")]
+    Npy,
+    /// Linear, unclamped radiance as a float32 OpenEXR image
+    #[strum(to_string = "OpenEXR")]
+    Exr,
+    /// Lossy 8-bit image at `RenderSettings::jpeg_quality`
+    #[strum(to_string = "JPEG")]
+    Jpeg,
+    #[strum(to_string = "BMP")]
+    Bmp,
+    /// Binary portable pixmap (P6)
+    #[strum(to_string = "PPM")]
+    Ppm,
+    /// 8-bit image reduced to a palette of 256 colors. A turntable rendered as GIF becomes one
+    /// animated file instead of numbered images.
+    #[strum(to_string = "GIF")]
+    Gif,
+}
+
+impl OutputFormat {
+    /// The format a file name's extension asks for, if it's one we can write
+    pub fn from_extension(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(OutputFormat::Png),
+            "npy" => Some(OutputFormat::Npy),
+            "exr" => Some(OutputFormat::Exr),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "bmp" => Some(OutputFormat::Bmp),
+            "ppm" => Some(OutputFormat::Ppm),
+            "gif" => Some(OutputFormat::Gif),
+            _ => None,
+        }
+    }
+
+    /// The usual extension for files in the format, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Npy => "npy",
+            OutputFormat::Exr => "exr",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Bmp => "bmp",
+            OutputFormat::Ppm => "ppm",
+            OutputFormat::Gif => "gif",
+        }
+    }
+}
+
+pub struct ExrLayer<'a> {
+    /// Layer name, or `None` for the unnamed main layer
+    pub name: Option<&'a str>,
+    /// Channel names, filled from the red, green, and blue components of the pixels in order
+    pub channels: &'a [&'a str],
+    pub pixels: &'a [Color],
+}
+
+/// Encodes the linear color buffer as a version 1.0 `.npy` file.
+/// See https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html
+pub fn encode_npy(pixels: &[Color], width: u32, height: u32) -> Vec<u8> {
+    const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+    const ALIGNMENT: usize = 64;
+
+    debug_assert_eq!(pixels.len(), (width * height) as usize);
+
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}, 3), }}",
+        height, width
+    );
+    // magic + header length + header + newline must be a multiple of the alignment
+    let unpadded = MAGIC.len() + 2 + header.len() + 1;
+    let padding = (ALIGNMENT - unpadded % ALIGNMENT) % ALIGNMENT;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 2 + header.len() + pixels.len() * 12);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for color in pixels {
+        for channel in [color.r, color.g, color.b] {
+            bytes.extend_from_slice(&(channel as f32).to_le_bytes());
+        }
+    }
+    bytes
+}
+
+/// Encodes one or more layers of the same size as an OpenEXR file
+pub fn encode_exr(layers: &[ExrLayer], width: u32, height: u32) -> exr::error::Result<Vec<u8>> {
+    let size = (width as usize, height as usize);
+    let layers = layers
+        .iter()
+        .map(|layer| {
+            debug_assert_eq!(layer.pixels.len(), size.0 * size.1);
+            let channels = layer
+                .channels
+                .iter()
+                .enumerate()
+                .map(|(index, &name)| {
+                    let samples = layer
+                        .pixels
+                        .iter()
+                        .map(|color| [color.r, color.g, color.b][index] as f32)
+                        .collect();
+                    AnyChannel::new(name, FlatSamples::F32(samples))
+                })
+                .collect();
+            let attributes = match layer.name {
+                Some(name) => LayerAttributes::named(name),
+                None => LayerAttributes::default(),
+            };
+            Layer::new(
+                size,
+                attributes,
+                Encoding::FAST_LOSSLESS,
+                AnyChannels::sort(SmallVec::from_vec(channels)),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let image = Image::from_layers(ImageAttributes::with_size(size), layers);
+    let mut buffer = Cursor::new(vec![]);
+    image.write().to_buffered(&mut buffer)?;
+    Ok(buffer.into_inner())
+}
+
+/// Writes the encoded render to the file at `target`, or to `stdout` if the target is `-`
+pub fn write_output(target: &str, bytes: &[u8], mut stdout: impl Write) -> std::io::Result<()> {
+    if target == STDOUT_TARGET {
+        stdout.write_all(bytes)?;
+        stdout.flush()
+    } else {
+        std::fs::write(Path::new(target), bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exr::prelude::{ReadChannels, ReadLayers};
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(
+            OutputFormat::from_extension("render.png"),
+            Some(OutputFormat::Png)
+        );
+        assert_eq!(
+            OutputFormat::from_extension("out/a.JPG"),
+            Some(OutputFormat::Jpeg)
+        );
+        assert_eq!(
+            OutputFormat::from_extension("a.jpeg"),
+            Some(OutputFormat::Jpeg)
+        );
+        assert_eq!(
+            OutputFormat::from_extension("a.ppm"),
+            Some(OutputFormat::Ppm)
+        );
+        assert_eq!(OutputFormat::from_extension("a.tiff"), None);
+
+        for format in [
+            OutputFormat::Png,
+            OutputFormat::Jpeg,
+            OutputFormat::Exr,
+            OutputFormat::Gif,
+        ] {
+            let name = format!("a.{}", format.extension());
+            assert_eq!(OutputFormat::from_extension(&name), Some(format));
+        }
+        assert_eq!(OutputFormat::from_extension("-"), None);
+    }
+
+    #[test]
+    fn test_encode_npy() {
+        let pixels = vec![Color::new(0.5, 2.0, -1.0); 6];
+        let bytes = encode_npy(&pixels, 3, 2);
+
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.contains("'descr': '<f4'"), "{}", header);
+        assert!(header.contains("'shape': (2, 3, 3)"), "{}", header);
+        assert!(header.ends_with('\n'));
+        assert_eq!((10 + header_len) % 64, 0);
+
+        assert_eq!(bytes.len(), 10 + header_len + 2 * 3 * 3 * 4);
+        let data = &bytes[10 + header_len..];
+        assert_eq!(f32::from_le_bytes(data[4..8].try_into().unwrap()), 2.0);
+    }
+
+    #[test]
+    fn test_encode_exr() {
+        let pixels = vec![Color::new(0.5, 2.0, -1.0); 6];
+        let bytes = encode_exr(
+            &[ExrLayer {
+                name: None,
+                channels: &["R", "G", "B"],
+                pixels: &pixels,
+            }],
+            3,
+            2,
+        )
+        .unwrap();
+
+        let image = exr::prelude::read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .all_layers()
+            .all_attributes()
+            .from_buffered(Cursor::new(bytes))
+            .unwrap();
+        let layer = &image.layer_data[0];
+        assert_eq!(layer.size, (3, 2).into());
+        let green = layer
+            .channel_data
+            .list
+            .iter()
+            .find(|channel| channel.name.to_string() == "G")
+            .unwrap();
+        assert_eq!(green.sample_data.value_by_flat_index(4).to_f32(), 2.0);
+    }
+
+    #[test]
+    fn test_write_output_to_stdout() {
+        let mut stdout = Vec::new();
+        write_output("-", b"image bytes", &mut stdout).unwrap();
+
+        assert_eq!(stdout, b"image bytes");
+        assert!(!Path::new("-").exists());
+    }
+
+    #[test]
+    fn test_write_output_to_file() {
+        let path = std::env::temp_dir().join(format!("raytracing-{}.png", std::process::id()));
+        let mut stdout = Vec::new();
+        write_output(path.to_str().unwrap(), b"image bytes", &mut stdout).unwrap();
+
+        assert!(stdout.is_empty());
+        assert_eq!(std::fs::read(&path).unwrap(), b"image bytes");
+        std::fs::remove_file(path).unwrap();
+    }
+}
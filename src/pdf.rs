@@ -0,0 +1,178 @@
+use crate::material::tangent_frame;
+use crate::object::Object;
+use crate::rng;
+use crate::vector::{Point, Vector};
+use std::f64::consts::PI;
+
+/// A distribution of directions to send a scattered ray in, along with the density of picking
+/// any given direction, so the contribution of each path can be weighted back to what the
+/// material itself would have scattered
+pub trait Pdf {
+    /// Probability density, per unit solid angle, of `generate` returning `direction`
+    fn value(&self, direction: &Vector) -> f64;
+
+    /// Picks a direction from the distribution. It doesn't have to be normalized.
+    fn generate(&self) -> Vector;
+}
+
+/// Directions weighted by the cosine of their angle to a surface normal, the way diffuse
+/// materials scatter light
+pub struct CosinePdf {
+    normal: Vector,
+    tangent: Vector,
+    bitangent: Vector,
+}
+
+impl CosinePdf {
+    pub fn new(normal: &Vector) -> Self {
+        let (tangent, bitangent) = tangent_frame(normal);
+        CosinePdf {
+            normal: normal.normalize(),
+            tangent,
+            bitangent,
+        }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vector) -> f64 {
+        let cos_theta = self.normal.dot(&direction.normalize());
+        cos_theta.max(0.0) / PI
+    }
+
+    fn generate(&self) -> Vector {
+        // a point picked uniformly on the unit disk, projected up onto the hemisphere
+        let phi = 2.0 * PI * rng::random::<f64>();
+        let r2: f64 = rng::random();
+        let r = r2.sqrt();
+        let z = (1.0 - r2).sqrt();
+        r * phi.cos() * self.tangent + r * phi.sin() * self.bitangent + z * self.normal
+    }
+}
+
+/// Directions from `origin` towards a randomly chosen one of `objects`, so paths find small
+/// bright things like lights far more often than by scattering at random. Quads are sampled by
+/// their area; any other object falls back to every direction being equally likely.
+pub struct HittablePdf<'a> {
+    objects: &'a [Object],
+    origin: Point,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(objects: &'a [Object], origin: Point) -> Self {
+        HittablePdf { objects, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: &Vector) -> f64 {
+        let total: f64 = self
+            .objects
+            .iter()
+            .map(|object| match object {
+                Object::Quad(quad) => quad.pdf_value(&self.origin, direction),
+                _ => 1.0 / (4.0 * PI),
+            })
+            .sum();
+        total / self.objects.len() as f64
+    }
+
+    fn generate(&self) -> Vector {
+        let index = rng::random::<usize>() % self.objects.len();
+        match &self.objects[index] {
+            Object::Quad(quad) => quad.random_towards(&self.origin),
+            _ => Vector::random_unit_vector(),
+        }
+    }
+}
+
+/// An even mix of two distributions, picking from either with equal probability
+pub struct MixturePdf<'a> {
+    pdfs: [&'a dyn Pdf; 2],
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(first: &'a dyn Pdf, second: &'a dyn Pdf) -> Self {
+        MixturePdf {
+            pdfs: [first, second],
+        }
+    }
+}
+
+impl Pdf for MixturePdf<'_> {
+    fn value(&self, direction: &Vector) -> f64 {
+        0.5 * self.pdfs[0].value(direction) + 0.5 * self.pdfs[1].value(direction)
+    }
+
+    fn generate(&self) -> Vector {
+        if rng::random::<f64>() < 0.5 {
+            self.pdfs[0].generate()
+        } else {
+            self.pdfs[1].generate()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::{Light, Material};
+    use crate::object::Quad;
+
+    #[test]
+    fn test_cosine_pdf_stays_above_surface() {
+        let normal = Vector::new(0.3, -1.0, 0.2);
+        let pdf = CosinePdf::new(&normal);
+        for _ in 0..1000 {
+            let direction = pdf.generate();
+            assert!(direction.dot(&normal) > 0.0);
+            assert!((direction.length() - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_quad_pdf_matches_solid_angle() {
+        // the pdf is uniform over the quad's solid angle, so 1 / value averages out to it
+        let light = Material::Light(Light::new(Color::WHITE));
+        let quad = Quad::new(
+            Point::new(-1.0, 2.0, -1.0),
+            Vector::new(2.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 2.0),
+            light,
+        );
+        let objects = [Object::Quad(quad)];
+        let pdf = HittablePdf::new(&objects, Point::new(0.0, 0.0, 0.0));
+
+        let samples = 20_000;
+        let solid_angle: f64 = (0..samples)
+            .map(|_| 1.0 / pdf.value(&pdf.generate()))
+            .sum::<f64>()
+            / samples as f64;
+        // a square of side 2 centered 2 away covers 4 asin(1/5) steradians
+        let expected = 4.0 * (1.0_f64 / 5.0).asin();
+        assert!((solid_angle - expected).abs() < 0.01 * expected);
+        assert_eq!(pdf.value(&Vector::new(0.0, -1.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn test_mixture_pdf_averages_values() {
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        let cosine = CosinePdf::new(&normal);
+        let light = Material::Light(Light::new(Color::WHITE));
+        let objects = [Object::Quad(Quad::new(
+            Point::new(-1.0, 2.0, -1.0),
+            Vector::new(2.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 2.0),
+            light,
+        ))];
+        let towards = HittablePdf::new(&objects, Point::new(0.0, 0.0, 0.0));
+        let mixture = MixturePdf::new(&cosine, &towards);
+
+        let up = Vector::new(0.0, 1.0, 0.0);
+        let expected = 0.5 * cosine.value(&up) + 0.5 * towards.value(&up);
+        assert!((mixture.value(&up) - expected).abs() < 1e-12);
+        let sideways = Vector::new(1.0, 0.1, 0.0);
+        assert_eq!(mixture.value(&sideways), 0.5 * cosine.value(&sideways));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::rng;
 use crate::vector::{Point, Vector};
 use rand::Rng;
 
@@ -9,6 +10,12 @@ pub struct Perlin {
     perm_z: Vec<i32>,
 }
 
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Perlin {
     const POINT_COUNT: usize = 256;
     pub fn new() -> Self {
@@ -27,6 +34,13 @@ impl Perlin {
         }
     }
 
+    /// Bytes allocated for the gradient and permutation tables
+    pub fn heap_size(&self) -> usize {
+        self.ranvec.capacity() * std::mem::size_of::<Vector>()
+            + (self.perm_x.capacity() + self.perm_y.capacity() + self.perm_z.capacity())
+                * std::mem::size_of::<i32>()
+    }
+
     pub fn noise(&self, point: &Point) -> f64 {
         let u = point.x - point.x.floor();
         let v = point.y - point.y.floor();
@@ -40,10 +54,10 @@ impl Perlin {
         let k = point.z.floor() as i32;
 
         let mut c = [[[Vector::ZERO; 2]; 2]; 2];
-        for di in 0..2 {
-            for dj in 0..2 {
-                for dk in 0..2 {
-                    c[di][dj][dk] = self.ranvec[(self.perm_x[((i + di as i32) & 255) as usize]
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    *corner = self.ranvec[(self.perm_x[((i + di as i32) & 255) as usize]
                         ^ self.perm_y[((j + dj as i32) & 255) as usize]
                         ^ self.perm_z[((k + dk as i32) & 255) as usize])
                         as usize];
@@ -60,14 +74,14 @@ impl Perlin {
         let ww = w * w * (3.0 - 2.0 * w);
 
         let mut accum = 0.0;
-        for i in 0..2 {
-            for j in 0..2 {
-                for k in 0..2 {
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, corner) in row.iter().enumerate() {
                     let weight_v = Vector::new(u - i as f64, v - j as f64, w - k as f64);
                     accum += (i as f64 * uu + (1.0 - i as f64) * (1.0 - uu))
                         * (j as f64 * vv + (1.0 - j as f64) * (1.0 - vv))
                         * (k as f64 * ww + (1.0 - k as f64) * (1.0 - ww))
-                        * c[i][j][k].dot(&weight_v);
+                        * corner.dot(&weight_v);
                 }
             }
         }
@@ -101,8 +115,8 @@ impl Perlin {
         p
     }
 
-    fn permute(p: &mut Vec<i32>) {
-        let mut rng = rand::thread_rng();
+    fn permute(p: &mut [i32]) {
+        let mut rng = rng::thread_rng();
         for i in (0..Self::POINT_COUNT).rev() {
             let target = rng.gen_range(0..=i);
             p.swap(i, target);
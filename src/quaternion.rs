@@ -25,6 +25,40 @@ impl Quaternion {
         }
     }
 
+    /// Rotation by `roll` around the z axis, then `pitch` around the x axis, then `yaw` around
+    /// the y axis, in radians. Each turns the same way as `from_axis_angle` around that axis, so
+    /// with +y up, `yaw` alone turns an object in place on the ground.
+    pub fn from_euler(roll: f64, pitch: f64, yaw: f64) -> Self {
+        let roll = Self::from_axis_angle(Vector::new(0.0, 0.0, 1.0), roll);
+        let pitch = Self::from_axis_angle(Vector::new(1.0, 0.0, 0.0), pitch);
+        let yaw = Self::from_axis_angle(Vector::new(0.0, 1.0, 0.0), yaw);
+        // the left side of a product rotates first
+        roll * pitch * yaw
+    }
+
+    /// The rotation as a matrix, where `m * v` matches `rotate_point(v)`. Cheaper than
+    /// `rotate_point` when the same rotation is applied to many points.
+    pub fn to_matrix(&self) -> [[f64; 3]; 3] {
+        let Self { x, y, z, w } = *self;
+        [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y + z * w),
+                2.0 * (x * z - y * w),
+            ],
+            [
+                2.0 * (x * y - z * w),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z + x * w),
+            ],
+            [
+                2.0 * (x * z + y * w),
+                2.0 * (y * z - x * w),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ]
+    }
+
     pub fn inverse(&self) -> Self {
         Self {
             x: -self.x,
@@ -34,6 +68,58 @@ impl Quaternion {
         }
     }
 
+    pub fn dot(&self, other: &Quaternion) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    /// Scales back to unit length, undoing the drift that builds up over many operations
+    pub fn normalize(self) -> Self {
+        let length = self.length();
+        Self {
+            x: self.x / length,
+            y: self.y / length,
+            z: self.z / length,
+            w: self.w / length,
+        }
+    }
+
+    /// Spherical interpolation from `self` at `t = 0` to `other` at `t = 1`, turning at a
+    /// constant rate along the shortest way between the two rotations
+    pub fn slerp(self, other: Quaternion, t: f64) -> Self {
+        // q and -q are the same rotation, pick the one less than half a turn away
+        let mut cos_theta = self.dot(&other);
+        let other = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            Quaternion::new(-other.x, -other.y, -other.z, -other.w)
+        } else {
+            other
+        };
+
+        // nearly the same rotation, where sin θ is too small to divide by
+        let (a, b) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+
+        Self {
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z,
+            w: a * self.w + b * other.w,
+        }
+        .normalize()
+    }
+
     pub fn rotate_point(self, point: Point) -> Point {
         let prime = self.inverse() * Quaternion::new(point.x, point.y, point.z, 0.0) * self;
         Point::new(prime.x, prime.y, prime.z)
@@ -78,4 +164,129 @@ mod tests {
         assert!(rotated.y.sub(expected.y).abs() < 1e-8, "y: {} != {}", rotated.y, expected.y);
         assert!(rotated.z.sub(expected.z).abs() < 1e-8, "z: {} != {}", rotated.z, expected.z);
     }
-}
\ No newline at end of file
+
+    fn assert_near(a: Quaternion, b: Quaternion) {
+        assert!(
+            (a.x - b.x).abs() < 1e-9
+                && (a.y - b.y).abs() < 1e-9
+                && (a.z - b.z).abs() < 1e-9
+                && (a.w - b.w).abs() < 1e-9,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn test_quaternion_slerp() {
+        let y_axis = Vector::new(0.0, 1.0, 0.0);
+        let start = Quaternion::from_axis_angle(y_axis, 0.0);
+        let end = Quaternion::from_axis_angle(y_axis, 90.0_f64.to_radians());
+
+        assert_near(start.slerp(end, 0.0), start);
+        assert_near(start.slerp(end, 1.0), end);
+
+        let middle = start.slerp(end, 0.5);
+        assert!((middle.length() - 1.0).abs() < 1e-9);
+        let expected = Quaternion::from_axis_angle(y_axis, 45.0_f64.to_radians());
+        assert_near(middle, expected);
+    }
+
+    #[test]
+    fn test_quaternion_slerp_takes_shortest_path() {
+        let y_axis = Vector::new(0.0, 1.0, 0.0);
+        let start = Quaternion::from_axis_angle(y_axis, 0.0);
+        // the same rotation as 90 degrees, but with every component negated
+        let end = Quaternion::from_axis_angle(y_axis, 450.0_f64.to_radians());
+
+        let middle = start.slerp(end, 0.5);
+        let expected = Quaternion::from_axis_angle(y_axis, 45.0_f64.to_radians());
+        let point = Point::new(1.0, 0.0, 0.0);
+        let rotated = middle.rotate_point(point);
+        let expected = expected.rotate_point(point);
+        assert!(
+            (rotated - expected).length() < 1e-9,
+            "{:?} != {:?}",
+            rotated,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_quaternion_slerp_nearly_parallel() {
+        let y_axis = Vector::new(0.0, 1.0, 0.0);
+        let start = Quaternion::from_axis_angle(y_axis, 0.0);
+        let end = Quaternion::from_axis_angle(y_axis, 1e-6);
+
+        let middle = start.slerp(end, 0.5);
+        assert!(middle.w.is_finite());
+        assert!((middle.length() - 1.0).abs() < 1e-12);
+        assert_near(middle, Quaternion::from_axis_angle(y_axis, 0.5e-6));
+    }
+
+    #[test]
+    fn test_quaternion_normalize() {
+        let drifted = Quaternion::new(0.0, 1.1, 0.0, 0.0);
+        assert_near(drifted.normalize(), Quaternion::new(0.0, 1.0, 0.0, 0.0));
+    }
+
+    fn assert_points_near(a: Point, b: Point) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_quaternion_from_euler_single_axis() {
+        let angle = 35.0_f64.to_radians();
+        let point = Point::new(0.3, -1.2, 2.5);
+        for (euler, axis) in [
+            (
+                Quaternion::from_euler(angle, 0.0, 0.0),
+                Vector::new(0.0, 0.0, 1.0),
+            ),
+            (
+                Quaternion::from_euler(0.0, angle, 0.0),
+                Vector::new(1.0, 0.0, 0.0),
+            ),
+            (
+                Quaternion::from_euler(0.0, 0.0, angle),
+                Vector::new(0.0, 1.0, 0.0),
+            ),
+        ] {
+            let expected = Quaternion::from_axis_angle(axis, angle);
+            assert_near(euler, expected);
+            assert_points_near(euler.rotate_point(point), expected.rotate_point(point));
+        }
+    }
+
+    #[test]
+    fn test_quaternion_from_euler_order() {
+        let (roll, pitch, yaw) = (0.3, -0.7, 1.1);
+        let point = Point::new(1.0, 2.0, 3.0);
+
+        let rolled =
+            Quaternion::from_axis_angle(Vector::new(0.0, 0.0, 1.0), roll).rotate_point(point);
+        let pitched =
+            Quaternion::from_axis_angle(Vector::new(1.0, 0.0, 0.0), pitch).rotate_point(rolled);
+        let expected =
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), yaw).rotate_point(pitched);
+
+        let rotated = Quaternion::from_euler(roll, pitch, yaw).rotate_point(point);
+        assert_points_near(rotated, expected);
+    }
+
+    #[test]
+    fn test_quaternion_to_matrix_matches_rotate_point() {
+        let quat = Quaternion::from_euler(0.3, -0.7, 1.1);
+        let matrix = quat.to_matrix();
+        for point in [
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+            Point::new(0.0, 0.0, 1.0),
+            Point::new(-2.0, 0.5, 3.0),
+        ] {
+            let [x, y, z] =
+                matrix.map(|row| row[0] * point.x + row[1] * point.y + row[2] * point.z);
+            assert_points_near(Point::new(x, y, z), quat.rotate_point(point));
+        }
+    }
+}
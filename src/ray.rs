@@ -1,17 +1,92 @@
 use crate::vector::{Point, Vector};
 
+const MAX_NESTED_MEDIA: usize = 4;
+const VACUUM_REFRACTION_INDEX: f64 = 1.0;
+
 #[derive(Debug, Clone)]
 pub struct Ray {
     pub origin: Point,
     pub direction: Vector,
+    pub media: Media,
+    /// When during the shutter interval the ray was cast, from 0 (open) to 1 (closed)
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Vector) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            media: Media::default(),
+            time: 0.0,
+        }
     }
 
     pub fn at(&self, t: f64) -> Point {
         self.origin + self.direction * t
     }
 }
+
+/// Refraction indices of the dielectrics a ray is currently inside, innermost last.
+/// An empty stack means the ray is travelling through vacuum/air.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Media {
+    indices: [f64; MAX_NESTED_MEDIA],
+    depth: usize,
+}
+
+impl Media {
+    /// Refraction index of the medium the ray is travelling through
+    pub fn current(&self) -> f64 {
+        match self.depth {
+            0 => VACUUM_REFRACTION_INDEX,
+            depth => self.indices[depth - 1],
+        }
+    }
+
+    pub fn enter(mut self, refraction_index: f64) -> Self {
+        if self.depth == MAX_NESTED_MEDIA {
+            // too deeply nested to track, keep the innermost media
+            self.indices.rotate_left(1);
+            self.depth -= 1;
+        }
+        self.indices[self.depth] = refraction_index;
+        self.depth += 1;
+        self
+    }
+
+    /// Leaves the innermost medium with the given index. Overlapping objects may be left in a
+    /// different order than they were entered, so this isn't necessarily the top of the stack.
+    pub fn exit(mut self, refraction_index: f64) -> Self {
+        if let Some(position) = self.indices[..self.depth]
+            .iter()
+            .rposition(|&index| index == refraction_index)
+        {
+            self.indices.copy_within(position + 1..self.depth, position);
+            self.depth -= 1;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_stack() {
+        let media = Media::default();
+        assert_eq!(media.current(), 1.0);
+
+        let media = media.enter(1.5).enter(1.33);
+        assert_eq!(media.current(), 1.33);
+
+        // leaving the outer medium first keeps the ray in the inner one
+        let overlapping = media.exit(1.5);
+        assert_eq!(overlapping.current(), 1.33);
+        assert_eq!(overlapping.exit(1.33).current(), 1.0);
+
+        assert_eq!(media.exit(1.33).current(), 1.5);
+        assert_eq!(media.exit(2.4).current(), 1.33);
+    }
+}
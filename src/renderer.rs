@@ -1,172 +1,2496 @@
+use crate::aabb::Aabb;
+use crate::annotate::annotate_render;
+use crate::camera::Camera;
 use crate::color::{Clamp, Color, GammaCorrect};
-use crate::data::Size;
-use crate::material::{Deflect, Emit};
-use crate::object::{Hit, Object};
+use crate::material::{finite_emission, Deflect, Emit, Material};
+use crate::object::{count_intersection_tests, Bounded, Hit, Object};
+use crate::output::{encode_exr, encode_npy, ExrLayer, OutputFormat};
+use crate::pdf::{CosinePdf, HittablePdf, MixturePdf, Pdf};
 use crate::ray::Ray;
-use crate::vector::{Point, Vector};
-use crate::world::create_world;
-use crate::RenderSettings;
-#[cfg(feature = "gui")]
-use eframe::egui;
-use image::{ImageOutputFormat, RgbImage};
-use log::info;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-#[cfg(feature = "gui")]
-use single_value_channel::Updater;
+use crate::rng;
+use crate::settings::RenderSettings;
+use crate::vector::Vector;
+use crate::world::{Background, Lights, World, WorldCache};
+use image::codecs::pnm::{PnmSubtype, SampleEncoding};
+use image::{DynamicImage, ImageOutputFormat, RgbImage, RgbaImage};
+use log::{error, info, warn};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+#[cfg(debug_assertions)]
+use std::cell::Cell;
+use std::fmt;
 use std::io::Cursor;
-#[cfg(feature = "gui")]
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(debug_assertions)]
+use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicU32;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-const V_UP: Vector = Vector {
-    x: 0.0,
-    y: 1.0,
-    z: 0.0,
-};
+const WORLD_AXES: [Vector; 3] = [
+    Vector {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+    },
+    Vector {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    },
+    Vector {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    },
+];
 
-pub fn render(
-    settings: RenderSettings,
-    #[cfg(feature = "gui")] sender: Updater<f32>,
-    #[cfg(feature = "gui")] context: &mut egui::Context,
-) -> Vec<u8> {
-    let image = Arc::new(Mutex::new(RgbImage::new(
-        settings.size.width,
-        settings.size.height,
-    )));
-
-    let focal_length = (settings.camera_position - settings.focus_point).length();
-    let theta = settings.field_of_view.to_radians();
-    let h = (theta / 2.0).tan();
-    let viewport_size = {
-        let height = 2.0 * h as f64 * focal_length;
-        Size {
-            height,
-            width: height * settings.size.width as f64 / settings.size.height as f64,
+/// Which contribution to the image is rendered
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Deserialize,
+    Serialize,
+    strum_macros::Display,
+    clap::ValueEnum,
+)]
+pub enum RenderPass {
+    /// The full image
+    #[default]
+    Beauty,
+    /// Only light mirrored or refracted by the first surface hit, for compositing
+    Reflection,
+    /// Surface normal at the first hit, mapped from [-1, 1] to [0, 1]
+    Normal,
+    /// Distance from the camera to the first hit. Misses are infinitely far away.
+    Depth,
+    /// Base color of the first surface hit, without any lighting
+    Albedo,
+    /// How many shapes the camera ray was tested against, from blue for none to red for
+    /// `COST_HEATMAP_MAX` or more, on a log scale
+    #[strum(to_string = "Cost Heatmap")]
+    CostHeatmap,
+    /// How much of the pixel the camera sees geometry through, from 0 where it sees only the
+    /// background to 1 where it sees none
+    Alpha,
+}
+
+/// Passes written by `--exr-layers`
+pub const EXR_LAYER_PASSES: [RenderPass; 4] = [
+    RenderPass::Beauty,
+    RenderPass::Normal,
+    RenderPass::Depth,
+    RenderPass::Albedo,
+];
+
+impl RenderPass {
+    /// Whether the pass holds colors that should be gamma corrected for display, rather than data
+    fn is_color(self) -> bool {
+        match self {
+            RenderPass::Beauty | RenderPass::Reflection | RenderPass::Albedo => true,
+            RenderPass::Normal
+            | RenderPass::Depth
+            | RenderPass::CostHeatmap
+            | RenderPass::Alpha => false,
+        }
+    }
+
+    /// What the pass records for a ray that doesn't hit anything
+    fn miss_color(self, ray: &Ray, background: &Background) -> Color {
+        match self {
+            RenderPass::Beauty => background.color(&ray.direction),
+            RenderPass::Depth => Color::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            RenderPass::Reflection
+            | RenderPass::Normal
+            | RenderPass::Albedo
+            | RenderPass::Alpha => Color::BLACK,
+            // rays culled before reaching the scene aren't tested against anything
+            RenderPass::CostHeatmap => cost_color(0),
+        }
+    }
+
+    fn exr_channels(self) -> &'static [&'static str] {
+        match self {
+            RenderPass::Depth => &["Z"],
+            RenderPass::Alpha => &["A"],
+            _ => &["R", "G", "B"],
+        }
+    }
+}
+
+/// Coordinate system the normal pass is written in
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Deserialize,
+    Serialize,
+    strum_macros::Display,
+    clap::ValueEnum,
+)]
+pub enum NormalSpace {
+    #[default]
+    World,
+    /// Relative to the camera: x is right, y is up, and z points back towards the camera
+    View,
+}
+
+/// Operator that maps the unbounded brightness of color passes into the displayable range
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Deserialize,
+    Serialize,
+    strum_macros::Display,
+    clap::ValueEnum,
+)]
+pub enum ToneMap {
+    /// Clip everything brighter than white
+    #[default]
+    None,
+    Reinhard,
+    #[strum(to_string = "ACES Filmic")]
+    AcesFilmic,
+}
+
+impl ToneMap {
+    fn apply(self, color: Color) -> Color {
+        match self {
+            ToneMap::None => color,
+            ToneMap::Reinhard => color.reinhard(),
+            ToneMap::AcesFilmic => color.aces_filmic(),
+        }
+    }
+}
+
+/// Transfer function that encodes linear color passes for display in 8-bit images
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Deserialize,
+    Serialize,
+    strum_macros::Display,
+    clap::ValueEnum,
+)]
+pub enum ColorSpace {
+    /// The exact piecewise sRGB curve, which matches how 8-bit textures are decoded
+    #[default]
+    #[strum(to_string = "sRGB")]
+    Srgb,
+    /// A plain power curve with the settings' `gamma`, to match a particular display. Close to
+    /// sRGB at 2.2, and fastest at 2.0.
+    Gamma,
+}
+
+impl ColorSpace {
+    fn encode(self, color: Color, gamma: f64) -> Color {
+        match self {
+            ColorSpace::Srgb => color.srgb_encode(),
+            ColorSpace::Gamma => color.gamma_correct(gamma),
+        }
+    }
+}
+
+/// Progress updates are sent at most every this many pixels, unless the time interval elapses first
+const PROGRESS_PIXEL_INTERVAL: u32 = 1024;
+/// Roughly one frame at 60 Hz
+const PROGRESS_TIME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// The encoded render, with the auxiliary images asked for by `settings.aov`
+pub struct RenderOutput {
+    pub image: Vec<u8>,
+    /// PNG of the distance to the first hit of each camera ray, from black at the nearest to
+    /// white at the furthest. Misses are white.
+    pub depth: Option<Vec<u8>>,
+    /// PNG of the normal at the first hit of each camera ray, as in the normal pass
+    pub normal: Option<Vec<u8>>,
+}
+
+/// Why a render produced no image
+#[derive(Debug)]
+pub enum RenderError {
+    /// The image has no pixels, so there is nothing to render or encode
+    EmptyImage { width: u32, height: u32 },
+    /// Encoding the pixels as PNG, JPEG, BMP or PPM failed
+    Image(image::ImageError),
+    /// Encoding the pixels as OpenEXR failed
+    Exr(exr::error::Error),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::EmptyImage { width, height } => {
+                write!(f, "Can't render an image of {}x{} pixels", width, height)
+            }
+            RenderError::Image(e) => write!(f, "Error encoding image: {}", e),
+            RenderError::Exr(e) => write!(f, "Error encoding EXR image: {}", e),
         }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::EmptyImage { .. } => None,
+            RenderError::Image(e) => Some(e),
+            RenderError::Exr(e) => Some(e),
+        }
+    }
+}
+
+impl From<image::ImageError> for RenderError {
+    fn from(e: image::ImageError) -> Self {
+        RenderError::Image(e)
+    }
+}
+
+impl From<exr::error::Error> for RenderError {
+    fn from(e: exr::error::Error) -> Self {
+        RenderError::Exr(e)
+    }
+}
+
+/// Renders and encodes the image described by `settings`, building its world through `worlds`.
+/// `progress` is called from the render threads with the fraction of pixels finished, a few
+/// times a second at most, always increasing, and finally with 1.0.
+pub fn render(
+    mut settings: RenderSettings,
+    worlds: &WorldCache,
+    progress: Option<&(dyn Fn(f32) + Sync)>,
+) -> Result<RenderOutput, RenderError> {
+    let alpha = settings.alpha && !settings.exr_layers;
+    if alpha && settings.output_format != OutputFormat::Png {
+        warn!(
+            "{} output can't be transparent, writing it without alpha",
+            settings.output_format
+        );
+    }
+    let mut passes = if settings.exr_layers {
+        EXR_LAYER_PASSES.to_vec()
+    } else {
+        vec![settings.pass]
     };
+    // extra passes go after the main one, and are found again by their index
+    let alpha_index = (alpha && settings.output_format == OutputFormat::Png).then(|| {
+        passes.push(RenderPass::Alpha);
+        passes.len() - 1
+    });
+    let aov_index = (settings.aov && !settings.exr_layers).then(|| {
+        passes.extend([RenderPass::Depth, RenderPass::Normal]);
+        passes.len() - 2
+    });
+    let buffers = render_passes(&mut settings, worlds, &passes, progress)?;
 
-    let w = (settings.camera_position - settings.focus_point).normalize();
-    let u = V_UP.cross(&w).normalize();
-    let v = w.cross(&u);
+    if settings.exr_layers {
+        let (width, height) = (settings.size.width, settings.size.height);
+        return Ok(RenderOutput {
+            image: encode_exr_layers(&passes, &buffers, width, height)?,
+            depth: None,
+            normal: None,
+        });
+    }
 
-    let viewport_u = u * viewport_size.width;
-    let viewport_v = -v * viewport_size.height;
+    let alpha = alpha_index.map(|index| buffers[index].as_slice());
+    let encode_aov = |pass, pixels: &[Color]| {
+        let settings = RenderSettings {
+            pass,
+            annotate: false,
+            ..settings.clone()
+        };
+        encode_image(pixels, &settings, ImageOutputFormat::Png)
+    };
+    Ok(RenderOutput {
+        image: encode_output(&buffers[0], alpha, &settings)?,
+        depth: aov_index
+            .map(|index| encode_aov(RenderPass::Depth, &normalize_depth(&buffers[index])))
+            .transpose()?,
+        normal: aov_index
+            .map(|index| encode_aov(RenderPass::Normal, &buffers[index + 1]))
+            .transpose()?,
+    })
+}
 
-    let pixel_delta_u = viewport_u / settings.size.width as f64;
-    let pixel_delta_v = viewport_v / settings.size.height as f64;
+/// Renders `settings.pass` into an 8-bit image, tone mapped, encoded for display and annotated
+/// just as `render` would write it, for callers that want to process the pixels themselves
+pub fn render_to_image(mut settings: RenderSettings) -> Result<RgbImage, RenderError> {
+    let passes = [settings.pass];
+    let buffers = render_passes(&mut settings, &WorldCache::default(), &passes, None)?;
+    Ok(display_image(&buffers[0], &settings))
+}
 
-    let viewport_origin =
-        settings.camera_position - focal_length * w - viewport_u / 2.0 - viewport_v / 2.0;
-    let origin_pixel = viewport_origin + (pixel_delta_u + pixel_delta_v) / 2.0;
+/// Renders each of the passes into a linear buffer, after checking the settings and correcting
+/// those that can be
+fn render_passes(
+    settings: &mut RenderSettings,
+    worlds: &WorldCache,
+    passes: &[RenderPass],
+    progress: Option<&(dyn Fn(f32) + Sync)>,
+) -> Result<Vec<Vec<Color>>, RenderError> {
+    let (width, height) = (settings.size.width, settings.size.height);
+    if width == 0 || height == 0 {
+        return Err(RenderError::EmptyImage { width, height });
+    }
 
-    let defocus_radius =
-        settings.focus_distance as f64 * (settings.defocus_angle as f64 / 2.0).to_radians().tan();
-    let defocus_u = u * defocus_radius;
-    let defocus_v = v * defocus_radius;
+    if let Some(focus_distance) = corrected_focus_distance(settings) {
+        warn!(
+            "Focus distance {} is behind the camera, focusing on the focus point at {} instead",
+            settings.focus_distance, focus_distance
+        );
+        settings.focus_distance = focus_distance;
+    }
+    warn_inconsistent_focus(settings);
 
-    let world = create_world(&settings.scene);
+    let world = worlds.get(&settings.scene);
+    info!(
+        "Scene uses approximately {:.1} MiB",
+        world.memory_estimate() as f64 / (1024.0 * 1024.0)
+    );
 
-    #[cfg(feature = "gui")]
-    let completed_pixels = AtomicU32::new(0);
+    let throttle = ProgressThrottle::new(
+        {
+            let (x0, y0, x1, y1) = render_region(settings);
+            (x1 - x0) * (y1 - y0)
+        },
+        PROGRESS_PIXEL_INTERVAL,
+        PROGRESS_TIME_INTERVAL,
+    );
+    // threads can finish their pixels in one order and report them in another, so the reports
+    // are serialized and any overtaken by a later one dropped
+    let reported = Mutex::new(0.0);
 
-    (0..settings.size.width).into_par_iter().for_each(|x| {
-        for y in 0..settings.size.height {
-            let pixel_center =
-                origin_pixel + (x as f64 * pixel_delta_u) + (y as f64 * pixel_delta_v);
-
-            let samples = (0..settings.samples)
-                .map(|_| {
-                    let ray = get_ray(
-                        pixel_center,
-                        settings.camera_position,
-                        pixel_delta_u,
-                        pixel_delta_v,
-                        settings.defocus_angle,
-                        defocus_u,
-                        defocus_v,
-                    );
-                    ray_color(&ray, &world.object, &world.background, settings.max_depth)
-                })
-                .collect::<Vec<_>>();
-            let color: Color = Color::from(samples).gamma_correct().clamp(0.0, 1.0);
-
-            image
-                .lock()
-                .unwrap()
-                .put_pixel(x, y, image::Rgb(color.into()));
-
-            #[cfg(feature = "gui")]
-            {
-                let pixels = completed_pixels.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                let _ = sender.update(
-                    (pixels + 1) as f32 / (settings.size.width * settings.size.height) as f32,
+    let stats = RenderStats::default();
+    let buffers = render_buffers(settings, &world, passes, &stats, || {
+        let Some(progress) = progress else {
+            return;
+        };
+        if let Some(fraction) = throttle.complete_pixel(Instant::now()) {
+            let mut reported = reported.lock().unwrap();
+            if fraction > *reported {
+                *reported = fraction;
+                progress(fraction);
+            }
+        }
+    });
+    info!(
+        "Traced {} camera rays, {} culled",
+        stats.camera_rays(),
+        stats.culled_rays()
+    );
+    Ok(buffers)
+}
+
+/// Scales distances so the nearest hit in the image is 0 and the furthest is 1, for viewing as an
+/// image. Misses, and pixels with any samples that missed, are 1.
+fn normalize_depth(depths: &[Color]) -> Vec<Color> {
+    let finite = || depths.iter().map(|depth| depth.r).filter(|d| d.is_finite());
+    let near = finite().fold(f64::INFINITY, f64::min);
+    let far = finite().fold(f64::NEG_INFINITY, f64::max);
+    let range = far - near;
+
+    depths
+        .iter()
+        .map(|depth| {
+            let normalized = match depth.r.is_finite() {
+                true if range > 0.0 => (depth.r - near) / range,
+                true => 0.0,
+                false => 1.0,
+            };
+            Color::new(normalized, normalized, normalized)
+        })
+        .collect()
+}
+
+/// Distance from the camera to the focus point, where the image is sharpest
+fn focus_point_distance(settings: &RenderSettings) -> f32 {
+    (settings.focus_point - settings.camera_position).length() as f32
+}
+
+/// A focus distance that isn't positive would put the plane of focus behind the camera, which no
+/// lens can do. Such settings get the distance to the focus point instead.
+fn corrected_focus_distance(settings: &RenderSettings) -> Option<f32> {
+    (!(settings.focus_distance > 0.0 && settings.focus_distance.is_finite()))
+        .then(|| focus_point_distance(settings))
+}
+
+/// The focus distance and focus point are set separately, so can drift far apart. That isn't
+/// impossible, but it's rarely what was meant when there is any defocus blur.
+fn warn_inconsistent_focus(settings: &RenderSettings) {
+    const MAX_RATIO: f32 = 10.0;
+
+    let ratio = settings.focus_distance / focus_point_distance(settings);
+    if settings.defocus_angle > 0.0 && !(1.0 / MAX_RATIO..=MAX_RATIO).contains(&ratio) {
+        warn!(
+            "Focus distance {} is far from the distance to the focus point ({})",
+            settings.focus_distance,
+            focus_point_distance(settings)
+        );
+    }
+}
+
+/// Encodes a single pass in the format asked for by `settings.output_format`. An `alpha` pass
+/// makes PNG output transparent where it is below 1.
+fn encode_output(
+    pixels: &[Color],
+    alpha: Option<&[Color]>,
+    settings: &RenderSettings,
+) -> Result<Vec<u8>, RenderError> {
+    Ok(match settings.output_format {
+        OutputFormat::Png => match alpha {
+            Some(alpha) => encode_transparent_png(pixels, alpha, settings)?,
+            None => encode_image(pixels, settings, ImageOutputFormat::Png)?,
+        },
+        OutputFormat::Jpeg => encode_image(
+            pixels,
+            settings,
+            ImageOutputFormat::Jpeg(settings.jpeg_quality.clamp(1, 100)),
+        )?,
+        OutputFormat::Bmp => encode_image(pixels, settings, ImageOutputFormat::Bmp)?,
+        OutputFormat::Gif => encode_image(pixels, settings, ImageOutputFormat::Gif)?,
+        OutputFormat::Ppm => encode_image(
+            pixels,
+            settings,
+            ImageOutputFormat::Pnm(PnmSubtype::Pixmap(SampleEncoding::Binary)),
+        )?,
+        OutputFormat::Npy => encode_npy(pixels, settings.size.width, settings.size.height),
+        OutputFormat::Exr => encode_exr(
+            &[ExrLayer {
+                name: None,
+                channels: settings.pass.exr_channels(),
+                pixels,
+            }],
+            settings.size.width,
+            settings.size.height,
+        )?,
+    })
+}
+
+/// The part of the image to render as (x0, y0, x1, y1), exclusive of x1 and y1. Coordinates past
+/// the edges of the image are pulled in to them, and a region left empty after that renders the
+/// whole image instead.
+fn render_region(settings: &RenderSettings) -> (u32, u32, u32, u32) {
+    let (width, height) = (settings.size.width, settings.size.height);
+    let Some(region) = settings.region else {
+        return (0, 0, width, height);
+    };
+
+    let (x0, y0, x1, y1) = region;
+    let clamped = (x0.min(width), y0.min(height), x1.min(width), y1.min(height));
+    if clamped.0 >= clamped.2 || clamped.1 >= clamped.3 {
+        warn!(
+            "Region {:?} leaves nothing of the {}x{} image to render, rendering all of it instead",
+            region, width, height
+        );
+        return (0, 0, width, height);
+    }
+    if clamped != region {
+        warn!(
+            "Region {:?} reaches outside the {}x{} image, rendering {:?} instead",
+            region, width, height, clamped
+        );
+    }
+    clamped
+}
+
+/// Side of the square tiles the image is split into for rendering. Tiles at the right and bottom
+/// edges are cut short.
+const TILE_SIZE: u32 = 32;
+
+/// Tiles that panic while rendering are filled with this, so they stand out in the image
+pub const PANIC_MARKER_COLOR: Color = Color::MAGENTA;
+
+/// Renders every pixel of the image inside `settings.region`, returning one linear buffer per
+/// pass. Pixels outside the region are black. `on_pixel` is called from the render threads after
+/// each pixel is finished.
+fn render_buffers(
+    settings: &RenderSettings,
+    world: &World,
+    passes: &[RenderPass],
+    stats: &RenderStats,
+    on_pixel: impl Fn() + Sync,
+) -> Vec<Vec<Color>> {
+    let camera = Camera::new(settings);
+    let normal_basis = normal_basis(settings.normal_space, camera.basis());
+    let background = world
+        .background
+        .scaled(settings.environment_intensity as f64);
+    let bounds = world.object.bounding_box();
+
+    render_tiles(settings, passes.len(), |(left, top, right, bottom)| {
+        let tile_pixels = ((right - left) * (bottom - top)) as usize;
+        let mut tile = vec![Vec::with_capacity(tile_pixels); passes.len()];
+        for y in top..bottom {
+            for x in left..right {
+                let samples = sample_pixel(
+                    x,
+                    y,
+                    &camera,
+                    &world.object,
+                    &bounds,
+                    &background,
+                    &world.lights,
+                    &world.important,
+                    passes,
+                    &normal_basis,
+                    settings,
+                    stats,
                 );
-                context.request_repaint();
+                for (buffer, color) in tile.iter_mut().zip(samples) {
+                    buffer.push(color);
+                }
+                on_pixel();
             }
         }
+        tile
+    })
+}
+
+/// Splits the region of the image to render into tiles and renders them in parallel with
+/// `render_tile`, which returns one buffer per pass of the tile's pixels in rows. Tiles are
+/// (left, top, right, bottom), exclusive of right and bottom.
+///
+/// Each render thread takes whole tiles and fills a buffer of its own, so no lock is held while
+/// rendering; finished tiles are copied into the image afterwards. A tile that panics is logged
+/// and filled with `PANIC_MARKER_COLOR` so the rest of the image still gets rendered.
+fn render_tiles(
+    settings: &RenderSettings,
+    pass_count: usize,
+    render_tile: impl Fn((u32, u32, u32, u32)) -> Vec<Vec<Color>> + Sync,
+) -> Vec<Vec<Color>> {
+    let (width, height) = (settings.size.width, settings.size.height);
+
+    // tiles start from the corner of the region, and are cut short at its far edges
+    let (x0, y0, x1, y1) = render_region(settings);
+    let tiles: Vec<(u32, u32, u32, u32)> = (y0..y1)
+        .step_by(TILE_SIZE as usize)
+        .flat_map(|top| {
+            (x0..x1).step_by(TILE_SIZE as usize).map(move |left| {
+                let (right, bottom) = ((left + TILE_SIZE).min(x1), (top + TILE_SIZE).min(y1));
+                (left, top, right, bottom)
+            })
+        })
+        .collect();
+    let rendered: Vec<Vec<Vec<Color>>> = tiles
+        .par_iter()
+        .map(|&tile| {
+            // a panicking tile leaves nothing behind that the other tiles share, so carrying on
+            // after one can't see anything half-updated
+            panic::catch_unwind(AssertUnwindSafe(|| render_tile(tile))).unwrap_or_else(|payload| {
+                let (left, top, right, bottom) = tile;
+                error!(
+                    "Rendering the tile from ({}, {}) to ({}, {}) panicked: {}",
+                    left,
+                    top,
+                    right,
+                    bottom,
+                    panic_message(payload.as_ref())
+                );
+                let tile_pixels = ((right - left) * (bottom - top)) as usize;
+                vec![vec![PANIC_MARKER_COLOR; tile_pixels]; pass_count]
+            })
+        })
+        .collect();
+
+    let pixel_count = (width * height) as usize;
+    let mut buffers = vec![vec![Color::BLACK; pixel_count]; pass_count];
+    for (&(left, top, right, _), tile) in tiles.iter().zip(rendered) {
+        let tile_width = (right - left) as usize;
+        for (buffer, tile_buffer) in buffers.iter_mut().zip(tile) {
+            for (row, colors) in tile_buffer.chunks(tile_width).enumerate() {
+                let start = (top as usize + row) * width as usize + left as usize;
+                buffer[start..start + tile_width].copy_from_slice(colors);
+            }
+        }
+    }
+    buffers
+}
+
+/// The message a panic was started with, if it had one
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// Renders only the given (x, y) pixels of the image described by `settings`, in order, as the
+/// bytes they would have in the PNG output
+pub fn render_pixels(
+    settings: &RenderSettings,
+    world: &World,
+    pixels: &[(u32, u32)],
+) -> Vec<[u8; 3]> {
+    let camera = Camera::new(settings);
+    let normal_basis = normal_basis(settings.normal_space, camera.basis());
+    let background = world
+        .background
+        .scaled(settings.environment_intensity as f64);
+    let bounds = world.object.bounding_box();
+    let stats = RenderStats::default();
+
+    pixels
+        .par_iter()
+        .map(|&(x, y)| {
+            let color = sample_pixel(
+                x,
+                y,
+                &camera,
+                &world.object,
+                &bounds,
+                &background,
+                &world.lights,
+                &world.important,
+                &[settings.pass],
+                &normal_basis,
+                settings,
+                &stats,
+            )[0];
+            display_bytes(color, x, y, settings)
+        })
+        .collect()
+}
+
+/// Averages the samples of one pixel for each of the passes. Every pass sees the same rays, so
+/// the passes line up.
+#[allow(clippy::too_many_arguments)]
+fn sample_pixel(
+    x: u32,
+    y: u32,
+    camera: &Camera,
+    obj: &Object,
+    bounds: &Aabb,
+    background: &Background,
+    lights: &Lights,
+    important: &[Object],
+    passes: &[RenderPass],
+    normal_basis: &[Vector; 3],
+    settings: &RenderSettings,
+    stats: &RenderStats,
+) -> Vec<Color> {
+    // with nothing to hit, extra samples would only average the background over the pixel
+    if bounds.is_empty() {
+        stats.camera_rays.fetch_add(1, Ordering::Relaxed);
+        let ray = camera.get_center_ray(x, y);
+        return passes
+            .iter()
+            .map(|pass| {
+                pass_color(
+                    &ray,
+                    obj,
+                    background,
+                    lights,
+                    important,
+                    settings.max_depth,
+                    *pass,
+                    normal_basis,
+                )
+            })
+            .collect();
+    }
+
+    if let Some(seed) = settings.seed {
+        rng::seed_pixel(seed, x, y);
+    }
+    stats
+        .camera_rays
+        .fetch_add(settings.samples as u64, Ordering::Relaxed);
+
+    let mut samples = vec![Vec::with_capacity(settings.samples as usize); passes.len()];
+    for _ in 0..settings.samples {
+        let ray = camera.get_ray(x, y);
+        // a camera ray that misses the box around the whole scene can't hit anything in it
+        let culled = !bounds.hit(&ray, 0.001..f64::INFINITY);
+        if culled {
+            stats.culled_rays.fetch_add(1, Ordering::Relaxed);
+        }
+
+        for (pass, pass_samples) in passes.iter().zip(samples.iter_mut()) {
+            pass_samples.push(if culled {
+                pass.miss_color(&ray, background)
+            } else {
+                pass_color(
+                    &ray,
+                    obj,
+                    background,
+                    lights,
+                    important,
+                    settings.max_depth,
+                    *pass,
+                    normal_basis,
+                )
+            });
+        }
+    }
+    samples.into_iter().map(Color::from).collect()
+}
+
+/// Counters collected across the render threads
+#[derive(Default)]
+pub struct RenderStats {
+    camera_rays: AtomicU64,
+    culled_rays: AtomicU64,
+}
+
+impl RenderStats {
+    /// Number of rays cast from the camera, not counting the bounces that follow them
+    pub fn camera_rays(&self) -> u64 {
+        self.camera_rays.load(Ordering::Relaxed)
+    }
+
+    /// Number of camera rays that missed the scene's bounding box, so were never traced
+    pub fn culled_rays(&self) -> u64 {
+        self.culled_rays.load(Ordering::Relaxed)
+    }
+}
+
+/// Writes each pass as a layer named after it, e.g. `beauty` or `depth`
+fn encode_exr_layers(
+    passes: &[RenderPass],
+    buffers: &[Vec<Color>],
+    width: u32,
+    height: u32,
+) -> exr::error::Result<Vec<u8>> {
+    let names = passes
+        .iter()
+        .map(|pass| pass.to_string().to_lowercase())
+        .collect::<Vec<_>>();
+    let layers = passes
+        .iter()
+        .zip(&names)
+        .zip(buffers)
+        .map(|((pass, name), pixels)| ExrLayer {
+            name: Some(name),
+            channels: pass.exr_channels(),
+            pixels,
+        })
+        .collect::<Vec<_>>();
+    encode_exr(&layers, width, height)
+}
+
+/// Counts completed pixels across render threads and decides when the progress is worth reporting,
+/// so the UI isn't asked to repaint for every single pixel
+struct ProgressThrottle {
+    total: u32,
+    completed: AtomicU32,
+    pixel_interval: u32,
+    time_interval: Duration,
+    start: Instant,
+    // nanoseconds since `start`
+    last_update: AtomicU64,
+}
+
+impl ProgressThrottle {
+    fn new(total: u32, pixel_interval: u32, time_interval: Duration) -> Self {
+        ProgressThrottle {
+            total,
+            completed: AtomicU32::new(0),
+            pixel_interval: pixel_interval.max(1),
+            time_interval,
+            start: Instant::now(),
+            last_update: AtomicU64::new(0),
+        }
+    }
+
+    /// Records a finished pixel and returns the completed fraction if an update should be sent.
+    /// The last pixel always produces an update.
+    fn complete_pixel(&self, now: Instant) -> Option<f32> {
+        let completed = self.completed.fetch_add(1, Ordering::Relaxed) + 1;
+        let fraction = completed as f32 / self.total as f32;
+        if completed >= self.total {
+            return Some(fraction);
+        }
+
+        let elapsed = now.saturating_duration_since(self.start).as_nanos() as u64;
+        let last_update = self.last_update.load(Ordering::Relaxed);
+        let due = completed.is_multiple_of(self.pixel_interval)
+            || elapsed.saturating_sub(last_update) >= self.time_interval.as_nanos() as u64;
+
+        // only one thread gets to report a given interval
+        (due && self
+            .last_update
+            .compare_exchange(last_update, elapsed, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok())
+        .then_some(fraction)
+    }
+}
+
+/// Encodes color passes for display, quantizes, and annotates the pixels as an 8-bit image
+fn encode_image(
+    pixels: &[Color],
+    settings: &RenderSettings,
+    format: ImageOutputFormat,
+) -> image::ImageResult<Vec<u8>> {
+    write_image(
+        DynamicImage::ImageRgb8(display_image(pixels, settings)),
+        format,
+    )
+}
+
+/// Like `encode_image` for PNG, with the alpha pass as the transparency of each pixel
+fn encode_transparent_png(
+    pixels: &[Color],
+    alpha: &[Color],
+    settings: &RenderSettings,
+) -> image::ImageResult<Vec<u8>> {
+    let rgb = display_image(pixels, settings);
+    let image = RgbaImage::from_fn(settings.size.width, settings.size.height, |x, y| {
+        let [r, g, b] = rgb.get_pixel(x, y).0;
+        let coverage = alpha[(y * settings.size.width + x) as usize].r;
+        image::Rgba([r, g, b, (coverage.clamp(0.0, 1.0) * 255.0).round() as u8])
+    });
+    write_image(DynamicImage::ImageRgba8(image), ImageOutputFormat::Png)
+}
+
+fn display_image(pixels: &[Color], settings: &RenderSettings) -> RgbImage {
+    let mut image = RgbImage::from_fn(settings.size.width, settings.size.height, |x, y| {
+        let color = pixels[(y * settings.size.width + x) as usize];
+        image::Rgb(display_bytes(color, x, y, settings))
     });
+    annotate_render(&mut image, settings);
+    image
+}
 
+fn write_image(image: DynamicImage, format: ImageOutputFormat) -> image::ImageResult<Vec<u8>> {
     let mut buffer = Cursor::new(vec![]);
-    let result = image
-        .lock()
-        .unwrap()
-        .write_to(&mut buffer, ImageOutputFormat::Png);
+    image.write_to(&mut buffer, format)?;
+    Ok(buffer.into_inner())
+}
 
-    match result {
-        Ok(_) => buffer.into_inner(),
-        Err(e) => {
-            info!("Error writing image: {}", e);
-            vec![]
+/// Converts a linear pixel to the bytes written to an 8-bit image
+fn display_bytes(color: Color, x: u32, y: u32, settings: &RenderSettings) -> [u8; 3] {
+    let color = match (settings.pass.is_color(), settings.linear) {
+        (true, false) => settings
+            .color_space
+            .encode(settings.tone_map.apply(color), settings.gamma),
+        (true, true) => settings.tone_map.apply(color),
+        (false, _) => color,
+    };
+    quantize(color, x, y, settings.dither)
+}
+
+/// 4x4 ordered dithering thresholds, in sixteenths
+const BAYER_MATRIX: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Converts a display color to bytes. With `dither`, each pixel is nudged by up to half a step
+/// in a fixed pattern before quantizing, which breaks up banding in smooth gradients.
+fn quantize(color: Color, x: u32, y: u32, dither: bool) -> [u8; 3] {
+    let color = if dither {
+        let threshold = BAYER_MATRIX[(y % 4) as usize][(x % 4) as usize] as f64;
+        let offset = ((threshold + 0.5) / 16.0 - 0.5) / 255.0;
+        color + Color::new(offset, offset, offset)
+    } else {
+        color
+    };
+    color.clamp(0.0, 1.0).into()
+}
+
+/// Axes the normal pass projects normals onto
+fn normal_basis(space: NormalSpace, (u, v, w): (Vector, Vector, Vector)) -> [Vector; 3] {
+    match space {
+        NormalSpace::World => WORLD_AXES,
+        NormalSpace::View => [u, v, w],
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn pass_color(
+    ray: &Ray,
+    obj: &Object,
+    background: &Background,
+    lights: &Lights,
+    important: &[Object],
+    depth: u32,
+    pass: RenderPass,
+    normal_basis: &[Vector; 3],
+) -> Color {
+    match pass {
+        RenderPass::Beauty => ray_color(ray, obj, background, lights, important, depth),
+        RenderPass::Reflection => reflection_color(ray, obj, background, lights, important, depth),
+        RenderPass::Normal => normal_color(ray, obj, normal_basis),
+        RenderPass::Depth => depth_color(ray, obj),
+        RenderPass::Albedo => albedo_color(ray, obj),
+        RenderPass::CostHeatmap => {
+            let (_, tests) = count_intersection_tests(|| obj.hit(ray, 0.001..f64::INFINITY));
+            cost_color(tests)
         }
+        RenderPass::Alpha => match obj.hit(ray, 0.001..f64::INFINITY) {
+            Some(_) => Color::WHITE,
+            None => Color::BLACK,
+        },
     }
 }
 
-fn get_ray(
-    pixel_center: Point,
-    camera_position: Point,
-    pixel_du: Vector,
-    pixel_dv: Vector,
-    defocus_angle: f32,
-    defocus_u: Vector,
-    defocus_v: Vector,
-) -> Ray {
-    let pixel_sample = pixel_center + pixel_sample_square(pixel_du, pixel_dv);
+/// Number of intersection tests shown as the hottest color of the cost heatmap
+pub const COST_HEATMAP_MAX: u64 = 1024;
 
-    let ray_origin = if defocus_angle > 0.0 {
-        defocus_disk_sample(camera_position, defocus_u, defocus_v)
-    } else {
-        camera_position
+fn cost_color(tests: u64) -> Color {
+    let t = (tests as f64).ln_1p() / (COST_HEATMAP_MAX as f64).ln_1p();
+    Color::heat(t)
+}
+
+fn depth_color(ray: &Ray, obj: &Object) -> Color {
+    let depth = match obj.hit(ray, 0.001..f64::INFINITY) {
+        Some(hit) => hit.t * ray.direction.length(),
+        None => f64::INFINITY,
     };
-    let ray_direction = pixel_sample - ray_origin;
-    Ray::new(ray_origin, ray_direction)
+    Color::new(depth, depth, depth)
 }
 
-fn pixel_sample_square(du: Vector, dv: Vector) -> Vector {
-    let px = -0.5 + rand::random::<f64>();
-    let py = -0.5 + rand::random::<f64>();
-    px * du + py * dv
+/// The color a surface reflects (or emits, for lights) regardless of lighting. Misses are black.
+fn albedo_color(ray: &Ray, obj: &Object) -> Color {
+    let Some(hit) = obj.hit(ray, 0.001..f64::INFINITY) else {
+        return Color::BLACK;
+    };
+
+    match hit.material.deflect(ray, &hit) {
+        Some(deflection) => deflection.attenuation,
+        None => hit.material.emit(hit.u, hit.v, &hit.point),
+    }
 }
 
-fn defocus_disk_sample(camera_position: Point, defocus_u: Vector, defocus_v: Vector) -> Point {
-    let p = Vector::random_in_unit_disk();
-    return camera_position + (p.x * defocus_u) + (p.y * defocus_v);
+/// The normal of the first surface hit, facing the ray and encoded as a color. Misses are black.
+fn normal_color(ray: &Ray, obj: &Object, basis: &[Vector; 3]) -> Color {
+    let Some(hit) = obj.hit(ray, 0.001..f64::INFINITY) else {
+        return Color::BLACK;
+    };
+
+    let normal = hit.normal.normalize();
+    let [x, y, z] = basis.map(|axis| normal.dot(&axis));
+    Color::new(x, y, z) * 0.5 + Color::new(0.5, 0.5, 0.5)
 }
 
-fn ray_color(ray: &Ray, obj: &Object, background: &Color, depth: u32) -> Color {
+/// The specular part of the light leaving the first surface the ray hits. Diffuse scattering,
+/// emission, and rays that miss everything are black.
+fn reflection_color(
+    ray: &Ray,
+    obj: &Object,
+    background: &Background,
+    lights: &Lights,
+    important: &[Object],
+    depth: u32,
+) -> Color {
     if depth == 0 {
         return Color::BLACK;
     }
 
     let Some(hit) = obj.hit(ray, 0.001..f64::INFINITY) else {
-        return *background;
+        return Color::BLACK;
     };
 
-    let color_from_emission = hit.material.emit(hit.u, hit.v, &hit.point);
-    let Some(deflection) = hit.material.deflect(ray, &hit) else {
-        return color_from_emission;
-    };
+    match hit.material.deflect(ray, &hit) {
+        Some(deflection) if deflection.specular => {
+            deflection.attenuation
+                * ray_color(
+                    &deflection.ray,
+                    obj,
+                    background,
+                    lights,
+                    important,
+                    depth - 1,
+                )
+        }
+        _ => Color::BLACK,
+    }
+}
+
+/// Follows a path of up to `depth` bounces, adding up what each surface emits weighted by the
+/// throughput, the product of the attenuations of the bounces before it. Iterating instead of
+/// recursing keeps the stack flat however high `max_depth` goes.
+///
+/// Every diffuse bounce also casts shadow rays towards the `lights` and adds the direct light
+/// that gets through, so scenes lit by them converge without paths having to find them.
+///
+/// Cosine-scattering bounces send half their rays towards the `important` objects instead,
+/// weighting each by the ratio of the material's density to the mixture's so the estimate stays
+/// the same while the noise from rarely found lights drops.
+fn ray_color(
+    ray: &Ray,
+    obj: &Object,
+    background: &Background,
+    lights: &Lights,
+    important: &[Object],
+    depth: u32,
+) -> Color {
+    let mut color = Color::BLACK;
+    let mut throughput = Color::WHITE;
+    let mut ray = ray.clone();
+
+    for _ in 0..depth {
+        let Some(hit) = obj.hit(&ray, 0.001..f64::INFINITY) else {
+            return color + throughput * background.color(&ray.direction);
+        };
+
+        let emitted = finite_emission(hit.material.emit(hit.u, hit.v, &hit.point));
+        color += throughput * emitted;
+        let Some(deflection) = hit.material.deflect(&ray, &hit) else {
+            return color;
+        };
+
+        #[cfg(debug_assertions)]
+        check_energy_conservation(&deflection.attenuation);
+
+        // volumes scatter the same way in every direction, so they have no surface to light
+        let diffuse = !deflection.specular && !matches!(hit.material, Material::Isotropic(_));
+        if diffuse {
+            color += throughput * deflection.attenuation * lights.direct_light(obj, &hit);
+        }
+
+        // aim some bounces at the lights and the bright parts of the sky, if there are any
+        let origin = deflection.ray.origin;
+        let towards = (!important.is_empty()).then(|| HittablePdf::new(important, origin));
+        let sky = background.sampler();
+        let both = towards
+            .as_ref()
+            .zip(sky)
+            .map(|(towards, sky)| MixturePdf::new(towards, sky));
+        let light: Option<&dyn Pdf> = match (&both, &towards) {
+            (Some(both), _) => Some(both),
+            (None, Some(towards)) => Some(towards),
+            (None, None) => sky.map(|sky| sky as &dyn Pdf),
+        };
+        let Some(light) = light.filter(|_| hit.material.scatters_cosine()) else {
+            throughput = throughput * deflection.attenuation;
+            ray = deflection.ray;
+            continue;
+        };
+
+        let cosine = CosinePdf::new(&hit.normal);
+        let mixture = MixturePdf::new(&cosine, light);
+        let direction = mixture.generate();
+        let pdf = mixture.value(&direction);
+        if pdf <= 0.0 {
+            return color;
+        }
+
+        throughput = throughput * deflection.attenuation * (cosine.value(&direction) / pdf);
+        ray = Ray {
+            origin,
+            direction,
+            ..deflection.ray
+        };
+    }
+    color
+}
+
+#[cfg(debug_assertions)]
+thread_local! {
+    static ENERGY_VIOLATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Flags deflections that carry away more energy than they received. A path's throughput starts
+/// at 1.0 and is multiplied by the attenuation at every bounce, so it can only grow above 1.0 if
+/// some bounce amplifies. Warns once per process and keeps a per-thread count of violations.
+#[cfg(debug_assertions)]
+fn check_energy_conservation(attenuation: &Color) {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+
+    if attenuation.r <= 1.0 && attenuation.g <= 1.0 && attenuation.b <= 1.0 {
+        return;
+    }
+
+    ENERGY_VIOLATIONS.with(|count| count.set(count.get() + 1));
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        warn!(
+            "Material attenuation {:?} is greater than 1.0, the scene does not conserve energy",
+            attenuation
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Size;
+    use crate::material::{Lambertian, Light, Material, Metal};
+    use crate::object::{Collection, Sphere};
+    use crate::vector::Point;
+    use crate::world::{create_world, get_scene_camera, DirectionalLight, Scene};
+    use exr::prelude::{ReadChannels, ReadLayers};
+
+    #[test]
+    fn test_view_space_normal_facing_camera() {
+        let object = single_sphere(Material::Lambertian(Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5),
+        }));
+        let target = Point::new(0.0, 0.0, -1.0);
+
+        for camera_position in [
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(3.0, 0.0, -1.0),
+            Point::new(-2.0, 1.5, 1.0),
+        ] {
+            let camera = Camera::new(&RenderSettings {
+                camera_position,
+                focus_point: target,
+                ..Default::default()
+            });
+            let basis = normal_basis(NormalSpace::View, camera.basis());
+            let ray = Ray::new(camera_position, target - camera_position);
+
+            let color = pass_color(
+                &ray,
+                &object,
+                &Background::Solid(Color::BLACK),
+                &Lights::default(),
+                &[],
+                50,
+                RenderPass::Normal,
+                &basis,
+            );
+            assert!((color.r - 0.5).abs() < 1e-8, "{:?}", color);
+            assert!((color.g - 0.5).abs() < 1e-8, "{:?}", color);
+            assert!((color.b - 1.0).abs() < 1e-8, "{:?}", color);
+        }
+    }
+
+    #[test]
+    fn test_world_space_normal() {
+        let object = single_sphere(Material::Lambertian(Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5),
+        }));
+        // looking down at the top of the sphere
+        let ray = Ray::new(Point::new(0.0, 2.0, -1.0), Vector::new(0.0, -1.0, 0.0));
+        let camera = Camera::new(&RenderSettings::default());
+        let basis = normal_basis(NormalSpace::World, camera.basis());
+
+        let color = pass_color(
+            &ray,
+            &object,
+            &Background::Solid(Color::BLACK),
+            &Lights::default(),
+            &[],
+            50,
+            RenderPass::Normal,
+            &basis,
+        );
+        assert_eq!(color, Color::new(0.5, 1.0, 0.5));
+    }
+
+    #[test]
+    fn test_exr_layers() {
+        let buffers = EXR_LAYER_PASSES
+            .iter()
+            .map(|_| vec![Color::new(0.25, 0.5, 0.75); 4 * 3])
+            .collect::<Vec<_>>();
+        let bytes = encode_exr_layers(&EXR_LAYER_PASSES, &buffers, 4, 3).unwrap();
+
+        let image = exr::prelude::read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .all_layers()
+            .all_attributes()
+            .from_buffered(std::io::Cursor::new(bytes))
+            .unwrap();
+
+        let mut layers = image
+            .layer_data
+            .iter()
+            .map(|layer| {
+                let name = layer.attributes.layer_name.as_ref().unwrap().to_string();
+                let channels = layer
+                    .channel_data
+                    .list
+                    .iter()
+                    .map(|channel| channel.name.to_string())
+                    .collect::<Vec<_>>();
+                (name, channels)
+            })
+            .collect::<Vec<_>>();
+        layers.sort();
+
+        let rgb = vec!["B".to_string(), "G".to_string(), "R".to_string()];
+        assert_eq!(
+            layers,
+            vec![
+                ("albedo".to_string(), rgb.clone()),
+                ("beauty".to_string(), rgb.clone()),
+                ("depth".to_string(), vec!["Z".to_string()]),
+                ("normal".to_string(), rgb),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_depth_and_albedo_passes() {
+        let object = single_sphere(Material::Lambertian(Lambertian {
+            albedo: Color::new(0.1, 0.2, 0.3),
+        }));
+        let background = Background::Solid(Color::WHITE);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -2.0));
+
+        let depth = pass_color(
+            &ray,
+            &object,
+            &background,
+            &Lights::default(),
+            &[],
+            50,
+            RenderPass::Depth,
+            &WORLD_AXES,
+        );
+        assert!((depth.r - 0.5).abs() < 1e-8, "{:?}", depth);
+        let albedo = pass_color(
+            &ray,
+            &object,
+            &background,
+            &Lights::default(),
+            &[],
+            50,
+            RenderPass::Albedo,
+            &WORLD_AXES,
+        );
+        assert_eq!(albedo, Color::new(0.1, 0.2, 0.3));
+
+        let miss = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        let depth = pass_color(
+            &miss,
+            &object,
+            &background,
+            &Lights::default(),
+            &[],
+            50,
+            RenderPass::Depth,
+            &WORLD_AXES,
+        );
+        assert!(depth.r.is_infinite());
+    }
+
+    #[test]
+    fn test_cost_heatmap_is_hotter_over_dense_geometry() {
+        // a tight cluster of small spheres straight ahead, with nothing off to the side
+        let cluster = (0..125)
+            .map(|i| {
+                let offset = Vector::new((i % 5) as f64, (i / 5 % 5) as f64, (i / 25) as f64);
+                Object::Sphere(Sphere::new(
+                    Point::new(-1.0, -1.0, -8.0) + offset * 0.5,
+                    0.2,
+                    Material::Lambertian(Lambertian {
+                        albedo: Color::new(0.5, 0.5, 0.5),
+                    }),
+                ))
+            })
+            .collect();
+        let object = Object::Collection(Collection::with_bvh(cluster));
+        let background = Background::Solid(Color::WHITE);
+        let dense = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let empty = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+
+        let count =
+            |ray: &Ray| count_intersection_tests(|| object.hit(ray, 0.001..f64::INFINITY)).1;
+        assert!(
+            count(&dense) > count(&empty),
+            "{} <= {}",
+            count(&dense),
+            count(&empty)
+        );
+
+        let heat = |ray: &Ray| {
+            let pass = RenderPass::CostHeatmap;
+            pass_color(
+                ray,
+                &object,
+                &background,
+                &Lights::default(),
+                &[],
+                50,
+                pass,
+                &WORLD_AXES,
+            )
+        };
+        assert_eq!(heat(&empty), Color::heat(0.0));
+        assert_eq!(Color::heat(0.0), Color::new(0.0, 0.0, 1.0));
+        assert_eq!(Color::heat(1.0), Color::new(1.0, 0.0, 0.0));
+        // hotter moves away from blue, towards green and then red
+        assert!(heat(&dense).g > heat(&empty).g);
+        assert!(heat(&dense).b < heat(&empty).b);
+    }
+
+    #[test]
+    fn test_dithering_breaks_up_banding() {
+        // a gradient narrower than one step quantizes to a single flat band
+        let distinct_values = |dither: bool| {
+            let mut values = (0..16)
+                .flat_map(|y| (0..16).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    let value = (76.05 + 0.3 * x as f64 / 15.0) / 255.0;
+                    quantize(Color::new(value, value, value), x, y, dither)[0]
+                })
+                .collect::<Vec<_>>();
+            values.sort();
+            values.dedup();
+            values.len()
+        };
+
+        assert_eq!(distinct_values(false), 1);
+        assert!(distinct_values(true) > 1);
+    }
+
+    #[test]
+    fn test_environment_intensity() {
+        let sky = Color::new(0.7, 0.8, 1.0);
+        let background = Background::Solid(sky);
+        let miss = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        let object = single_sphere(Material::Light(Light::new(Color::new(4.0, 4.0, 4.0))));
+
+        let color = ray_color(
+            &miss,
+            &object,
+            &background.scaled(0.5),
+            &Lights::default(),
+            &[],
+            50,
+        );
+        assert_eq!(color, sky * 0.5);
+
+        // without the environment only the emitter lights the scene
+        let dark = background.scaled(0.0);
+        assert_eq!(
+            ray_color(&miss, &object, &dark, &Lights::default(), &[], 50),
+            Color::BLACK
+        );
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(
+            ray_color(&ray, &object, &dark, &Lights::default(), &[], 50),
+            Color::new(4.0, 4.0, 4.0)
+        );
+    }
+
+    #[test]
+    fn test_mirror_reflects_environment() {
+        use crate::texture::{Image, WrapMode};
+        use std::sync::Arc;
+
+        // an environment that is red behind the camera (+z) and blue everywhere else
+        let (width, height) = (8, 4);
+        let data = (0..width * height)
+            .map(|i| match i % width {
+                1 | 2 => Color::new(1.0, 0.0, 0.0),
+                _ => Color::new(0.0, 0.0, 1.0),
+            })
+            .collect();
+        let background = Background::environment(
+            Image {
+                data: Arc::new(data),
+                width,
+                height,
+                wrap: WrapMode::Clamp,
+            },
+            1.0,
+        );
+        let object = single_sphere(Material::Metal(Metal {
+            albedo: Color::new(0.5, 0.5, 0.5),
+            fuzz: 0.0,
+            anisotropy: 0.0,
+        }));
+
+        // straight at the mirror, the reflection comes back towards the camera
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(
+            ray_color(&ray, &object, &background, &Lights::default(), &[], 50),
+            Color::new(0.5, 0.0, 0.0)
+        );
+        let miss = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            ray_color(&miss, &object, &background, &Lights::default(), &[], 50),
+            Color::new(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_light_sampling_lowers_variance() {
+        let world = create_world(&Scene::CornellBoxEmpty);
+        let camera = Point::new(278.0, 278.0, -800.0);
+        // a spot on the floor, lit only by the small light in the ceiling
+        let ray = Ray::new(camera, Point::new(278.0, 0.0, 400.0) - camera);
+
+        let statistics = |important: &[Object]| {
+            let samples: Vec<f64> = rng::with_seed(7, || {
+                (0..4000)
+                    .map(|_| {
+                        let color = ray_color(
+                            &ray,
+                            &world.object,
+                            &world.background,
+                            &world.lights,
+                            important,
+                            50,
+                        );
+                        color.luminance()
+                    })
+                    .collect()
+            });
+            let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+            let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>()
+                / (samples.len() - 1) as f64;
+            (mean, variance)
+        };
+
+        let (cosine_mean, cosine_variance) = statistics(&[]);
+        let (mixture_mean, mixture_variance) = statistics(&world.important);
+        assert!(mixture_variance < cosine_variance / 4.0);
+        assert!((mixture_mean - cosine_mean).abs() < 0.2 * cosine_mean);
+    }
+
+    #[test]
+    fn test_environment_sampling_lowers_variance() {
+        use crate::environment::EnvironmentSampler;
+        use crate::object::Plane;
+        use crate::texture::{Image, WrapMode};
+        use std::f64::consts::PI;
+        use std::sync::Arc;
+
+        // dim sky with a small, very bright sun high above the ground
+        let (width, height) = (32, 16);
+        let mut data = vec![Color::new(0.05, 0.05, 0.05); (width * height) as usize];
+        data[(3 * width + 20) as usize] = Color::new(2000.0, 2000.0, 2000.0);
+        let image = Image {
+            data: Arc::new(data),
+            width,
+            height,
+            wrap: WrapMode::Clamp,
+        };
+        let ground = Object::Plane(Plane::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Material::Lambertian(Lambertian {
+                albedo: Color::new(0.5, 0.5, 0.5),
+            }),
+        ));
+        let ray = Ray::new(Point::new(0.0, 1.0, 0.0), Vector::new(0.3, -1.0, 0.2));
+
+        let statistics = |background: &Background| {
+            let samples: Vec<f64> = rng::with_seed(7, || {
+                (0..4000)
+                    .map(|_| {
+                        ray_color(&ray, &ground, background, &Lights::default(), &[], 50)
+                            .luminance()
+                    })
+                    .collect()
+            });
+            let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+            let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>()
+                / (samples.len() - 1) as f64;
+            (mean, variance)
+        };
+
+        // the ground only sees the upper half of the sky, each pixel weighted by the cosine
+        let irradiance: f64 = (0..height / 2)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let sin_squared = |row: u32| (row as f64 / height as f64 * PI).sin().powi(2);
+                let projected =
+                    (sin_squared(y + 1) - sin_squared(y)) / 2.0 * 2.0 * PI / width as f64;
+                image.data[(y * width + x) as usize].luminance() * projected
+            })
+            .sum();
+        let expected = 0.5 / PI * irradiance;
+
+        // a sampler that knows nothing about the sun, as a baseline
+        let flat = EnvironmentSampler::new(&[Color::WHITE; 32 * 16], width, height);
+        let (_, flat_variance) = statistics(&Background::Environment {
+            image: image.clone(),
+            intensity: 1.0,
+            sampler: Arc::new(flat),
+        });
+        let (mean, variance) = statistics(&Background::environment(image, 1.0));
+        assert!(variance < flat_variance / 20.0);
+        assert!(
+            (mean - expected).abs() < 0.05 * expected,
+            "{} != {}",
+            mean,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_sun_lights_diffuse_hits() {
+        let object = single_sphere(Material::Lambertian(Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5),
+        }));
+        let background = Background::Solid(Color::BLACK);
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let sun = |direction| Lights {
+            sun: Some(DirectionalLight {
+                direction,
+                radiance: Color::new(1.0, 1.0, 1.0),
+            }),
+            ..Default::default()
+        };
+        let behind_camera = sun(Vector::new(0.0, 0.0, -1.0));
+
+        // one bounce sees only the sun, the scattered ray is never followed
+        let color = ray_color(&ray, &object, &background, &behind_camera, &[], 1);
+        let expected = 0.5 / std::f64::consts::PI;
+        assert!((color.r - expected).abs() < 1e-9, "{:?}", color);
+        assert_eq!(
+            ray_color(&ray, &object, &background, &Lights::default(), &[], 1),
+            Color::BLACK
+        );
+
+        let behind_sphere = sun(Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            ray_color(&ray, &object, &background, &behind_sphere, &[], 1),
+            Color::BLACK
+        );
+
+        // mirrors only see the sun if a reflected path happens to hit it
+        let mirror = single_sphere(Material::Metal(Metal {
+            albedo: Color::new(0.5, 0.5, 0.5),
+            fuzz: 0.0,
+            anisotropy: 0.0,
+        }));
+        assert_eq!(
+            ray_color(&ray, &mirror, &background, &behind_camera, &[], 1),
+            Color::BLACK
+        );
+    }
+
+    #[test]
+    fn test_non_finite_emission_is_black() {
+        let light = Light::new(Color::new(f64::NAN, 4.0, f64::INFINITY));
+        let point = Point::new(0.0, 0.0, 0.0);
+        assert_eq!(light.emit(0.0, 0.0, &point), Color::BLACK);
+
+        let object = single_sphere(Material::Light(light));
+        let ray = Ray::new(point, Vector::new(0.0, 0.0, -1.0));
+        let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+        let color = ray_color(&ray, &object, &background, &Lights::default(), &[], 50);
+        assert!(color.is_finite(), "{:?}", color);
+        assert_eq!(color, Color::BLACK);
+    }
+
+    #[test]
+    fn test_render_pixels_matches_full_render() {
+        // seeded, so each pixel follows the same paths however many others are rendered with it
+        let settings = RenderSettings {
+            size: Size {
+                width: 40,
+                height: 30,
+            },
+            samples: 4,
+            max_depth: 8,
+            scene: Scene::OneSphere,
+            seed: Some(11),
+            ..Default::default()
+        };
+        let world = create_world(&settings.scene);
+        let pixels = [(20, 15), (10, 20), (0, 0), (39, 29), (31, 11)];
+
+        let sparse = render_pixels(&settings, &world, &pixels);
+
+        let buffers = render_buffers(
+            &settings,
+            &world,
+            &[RenderPass::Beauty],
+            &RenderStats::default(),
+            || {},
+        );
+        let full = display_image(&buffers[0], &settings);
+        for (&(x, y), bytes) in pixels.iter().zip(&sparse) {
+            assert_eq!(*bytes, full.get_pixel(x, y).0, "({}, {})", x, y);
+        }
+        assert_ne!(sparse[0], sparse[2]);
+    }
+
+    #[test]
+    fn test_rotated_plane_is_not_culled() {
+        use crate::object::{Plane, Rotate};
+        use crate::quaternion::Quaternion;
+
+        // a glowing wall ahead of the camera, tilted a little, and a sphere off to the side
+        let wall = Object::Rotate(Rotate::new(
+            Object::Plane(Plane::new(
+                Point::new(0.0, 0.0, -5.0),
+                Vector::new(0.0, 0.0, 1.0),
+                Material::Light(Light::new(Color::WHITE)),
+            )),
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), 0.2),
+        ));
+        let world = World {
+            object: Object::Collection(Collection {
+                objects: vec![
+                    wall,
+                    Object::Sphere(Sphere::new(
+                        Point::new(3.0, 0.0, -2.0),
+                        0.5,
+                        Material::Lambertian(Lambertian {
+                            albedo: Color::WHITE,
+                        }),
+                    )),
+                ],
+            }),
+            background: Background::Solid(Color::BLACK),
+            lights: Lights::default(),
+            important: Vec::new(),
+        };
+        let settings = RenderSettings {
+            size: Size {
+                width: 40,
+                height: 30,
+            },
+            samples: 1,
+            camera_position: Point::new(0.0, 0.0, 0.0),
+            focus_point: Point::new(0.0, 0.0, -1.0),
+            ..Default::default()
+        };
+
+        assert!(!world.object.bounding_box().is_empty());
+        assert_eq!(
+            render_pixels(&settings, &world, &[(20, 15)]),
+            vec![[255, 255, 255]]
+        );
+    }
+
+    #[test]
+    fn test_tiles_land_in_place() {
+        // a size that isn't a multiple of the tile size, so the last row and column are cut short
+        let settings = RenderSettings {
+            size: Size {
+                width: TILE_SIZE * 2 + 6,
+                height: TILE_SIZE + 13,
+            },
+            samples: 2,
+            seed: Some(3),
+            scene: Scene::ThreeSpheres,
+            ..Default::default()
+        };
+        let world = create_world(&settings.scene);
+        let stats = RenderStats::default();
+
+        let tiled = render_buffers(&settings, &world, &[settings.pass], &stats, || {}).remove(0);
+        let pixels: Vec<(u32, u32)> = (0..settings.size.height)
+            .flat_map(|y| (0..settings.size.width).map(move |x| (x, y)))
+            .collect();
+        let expected = render_pixels(&settings, &world, &pixels);
+        for (&(x, y), (color, bytes)) in pixels.iter().zip(tiled.iter().zip(&expected)) {
+            assert_eq!(
+                display_bytes(*color, x, y, &settings),
+                *bytes,
+                "({}, {})",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn test_alpha_is_transparent_around_sphere() {
+        let settings = RenderSettings {
+            size: Size {
+                width: 16,
+                height: 16,
+            },
+            samples: 4,
+            seed: Some(5),
+            alpha: true,
+            ..Default::default()
+        };
+        let world = World {
+            object: Object::Sphere(Sphere::new(
+                Point::new(0.0, 0.0, -1.0),
+                0.2,
+                Material::Lambertian(Lambertian {
+                    albedo: Color::new(0.5, 0.5, 0.5),
+                }),
+            )),
+            background: Background::Solid(Color::new(0.7, 0.8, 1.0)),
+            lights: Lights::default(),
+            important: Vec::new(),
+        };
+        let passes = [RenderPass::Beauty, RenderPass::Alpha];
+        let buffers = render_buffers(&settings, &world, &passes, &RenderStats::default(), || {});
+
+        let png = encode_output(&buffers[0], Some(&buffers[1]), &settings).unwrap();
+        let image = image::load_from_memory(&png).unwrap();
+        assert!(image.color().has_alpha());
+        let image = image.to_rgba8();
+        for (x, y) in [(0, 0), (15, 0), (0, 15), (15, 15)] {
+            let pixel = image.get_pixel(x, y).0;
+            assert_eq!(pixel[3], 0, "({}, {})", x, y);
+            // the background color is kept, so edges blend smoothly when composited
+            assert_ne!(pixel[..3], [0, 0, 0]);
+        }
+        assert_eq!(image.get_pixel(8, 8).0[3], 255);
+
+        let opaque = encode_output(&buffers[0], None, &settings).unwrap();
+        let image = image::load_from_memory(&opaque).unwrap();
+        assert!(!image.color().has_alpha());
+    }
+
+    #[test]
+    fn test_depth_aov_is_nearer_at_sphere_center() {
+        let scene_camera = get_scene_camera(&Scene::OneSphere);
+        let settings = RenderSettings {
+            size: Size {
+                width: 64,
+                height: 36,
+            },
+            samples: 4,
+            seed: Some(2),
+            scene: Scene::OneSphere,
+            camera_position: scene_camera.camera_position,
+            focus_point: scene_camera.focus_point,
+            up: scene_camera.up,
+            field_of_view: scene_camera.field_of_view,
+            ..Default::default()
+        };
+        let world = create_world(&settings.scene);
+        let buffers = render_buffers(
+            &settings,
+            &world,
+            &[RenderPass::Depth],
+            &RenderStats::default(),
+            || {},
+        );
+        let depth = normalize_depth(&buffers[0]);
+
+        // along the middle row, out from the center to the last pixel that only sees the sphere
+        let (width, height) = (settings.size.width as usize, settings.size.height as usize);
+        let row = &depth[width * (height / 2)..width * (height / 2 + 1)];
+        let center = row[width / 2].r;
+        let silhouette = row[width / 2..]
+            .iter()
+            .take_while(|depth| depth.r < 1.0)
+            .last()
+            .unwrap()
+            .r;
+        assert!(center < silhouette, "{} >= {}", center, silhouette);
+        assert!(depth.iter().all(|depth| (0.0..=1.0).contains(&depth.r)));
+    }
+
+    #[test]
+    fn test_gamma_one_keeps_linear_color() {
+        let settings = RenderSettings {
+            color_space: ColorSpace::Gamma,
+            gamma: 1.0,
+            ..Default::default()
+        };
+        let color = Color::new(0.2, 0.5, 0.8);
+        assert_eq!(display_bytes(color, 0, 0, &settings), [51, 128, 204]);
+
+        let gamma_2 = RenderSettings {
+            gamma: 2.0,
+            ..settings
+        };
+        let expected: [u8; 3] = color.gamma_correct(2.0).into();
+        assert_eq!(display_bytes(color, 0, 0, &gamma_2), expected);
+    }
+
+    #[test]
+    fn test_region_is_clamped_to_image() {
+        let settings = |region| RenderSettings {
+            size: Size {
+                width: 40,
+                height: 30,
+            },
+            region,
+            ..Default::default()
+        };
+        assert_eq!(render_region(&settings(None)), (0, 0, 40, 30));
+        assert_eq!(
+            render_region(&settings(Some((5, 6, 20, 25)))),
+            (5, 6, 20, 25)
+        );
+        assert_eq!(
+            render_region(&settings(Some((5, 6, 99, 99)))),
+            (5, 6, 40, 30)
+        );
+        // nothing left to render, or the corners swapped
+        assert_eq!(
+            render_region(&settings(Some((50, 0, 60, 30)))),
+            (0, 0, 40, 30)
+        );
+        assert_eq!(
+            render_region(&settings(Some((20, 25, 5, 6)))),
+            (0, 0, 40, 30)
+        );
+        assert_eq!(
+            render_region(&settings(Some((5, 6, 5, 25)))),
+            (0, 0, 40, 30)
+        );
+    }
+
+    #[test]
+    fn test_region_renders_only_inside() {
+        let full_settings = RenderSettings {
+            size: Size {
+                width: TILE_SIZE + 20,
+                height: 24,
+            },
+            samples: 2,
+            seed: Some(5),
+            scene: Scene::ThreeSpheres,
+            ..Default::default()
+        };
+        // straddles a tile boundary of the full image and runs off its bottom edge
+        let settings = RenderSettings {
+            region: Some((TILE_SIZE - 7, 5, TILE_SIZE + 9, 100)),
+            ..full_settings.clone()
+        };
+        let world = create_world(&settings.scene);
+        let stats = RenderStats::default();
+
+        let full =
+            render_buffers(&full_settings, &world, &[settings.pass], &stats, || {}).remove(0);
+        let rendered = AtomicU64::new(0);
+        let cropped = render_buffers(&settings, &world, &[settings.pass], &stats, || {
+            rendered.fetch_add(1, Ordering::Relaxed);
+        })
+        .remove(0);
 
-    let color_from_deflection =
-        deflection.attenuation * ray_color(&deflection.ray, obj, background, depth - 1);
-    color_from_emission + color_from_deflection
+        assert_eq!(cropped.len(), full.len());
+        assert_eq!(rendered.into_inner(), 16 * 19);
+        for y in 0..settings.size.height {
+            for x in 0..settings.size.width {
+                let index = (y * settings.size.width + x) as usize;
+                let inside = (TILE_SIZE - 7..TILE_SIZE + 9).contains(&x) && y >= 5;
+                let expected = if inside { full[index] } else { Color::BLACK };
+                assert_eq!(cropped[index], expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_panicking_tile_is_marked() {
+        let settings = RenderSettings {
+            size: Size {
+                width: TILE_SIZE * 2,
+                height: TILE_SIZE + 5,
+            },
+            ..Default::default()
+        };
+        let fill = Color::new(0.25, 0.5, 0.75);
+
+        let buffers = render_tiles(&settings, 2, |(left, top, right, bottom)| {
+            if (left, top) == (TILE_SIZE, 0) {
+                panic!("bad tile");
+            }
+            vec![vec![fill; ((right - left) * (bottom - top)) as usize]; 2]
+        });
+
+        assert_eq!(buffers.len(), 2);
+        for buffer in buffers {
+            for y in 0..settings.size.height {
+                for x in 0..settings.size.width {
+                    let expected = if x >= TILE_SIZE && y < TILE_SIZE {
+                        PANIC_MARKER_COLOR
+                    } else {
+                        fill
+                    };
+                    let index = (y * settings.size.width + x) as usize;
+                    assert_eq!(buffer[index], expected, "({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let message = |f: fn()| panic::catch_unwind(f).map_err(|p| panic_message(&*p).to_owned());
+        assert_eq!(message(|| panic!("literal")), Err("literal".to_owned()));
+        assert_eq!(
+            message(|| panic!("formatted {}", 1)),
+            Err("formatted 1".to_owned())
+        );
+        assert_eq!(message(|| {}), Ok(()));
+    }
+
+    #[test]
+    fn test_seeded_render_is_independent_of_thread_count() {
+        let settings = RenderSettings {
+            size: Size {
+                width: 24,
+                height: 16,
+            },
+            samples: 4,
+            scene: Scene::ThreeSpheres,
+            seed: Some(42),
+            ..Default::default()
+        };
+        let world = create_world(&settings.scene);
+        let render_with_threads = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let buffer = pool
+                .install(|| {
+                    render_buffers(
+                        &settings,
+                        &world,
+                        &[RenderPass::Beauty],
+                        &RenderStats::default(),
+                        || {},
+                    )
+                })
+                .remove(0);
+            buffer
+                .iter()
+                .enumerate()
+                .flat_map(|(index, &color)| {
+                    let (x, y) = (index as u32 % 24, index as u32 / 24);
+                    display_bytes(color, x, y, &settings)
+                })
+                .collect::<Vec<u8>>()
+        };
+
+        let single = render_with_threads(1);
+        assert_eq!(single, render_with_threads(8));
+        assert_eq!(single, render_with_threads(8));
+    }
+
+    #[test]
+    fn test_seeded_renders_are_identical() {
+        let render_png = |scene: Scene, seed: u64| {
+            let settings = RenderSettings {
+                size: Size {
+                    width: 32,
+                    height: 18,
+                },
+                samples: 4,
+                max_depth: 8,
+                scene,
+                seed: Some(seed),
+                ..Default::default()
+            };
+            // a fresh world each time, as a separate run would build
+            let world = create_world(&settings.scene);
+            let buffers = render_buffers(
+                &settings,
+                &world,
+                &[RenderPass::Beauty],
+                &RenderStats::default(),
+                || {},
+            );
+            encode_output(&buffers[0], None, &settings).unwrap()
+        };
+
+        let first = render_png(Scene::OneSphere, 9);
+        assert_eq!(first, render_png(Scene::OneSphere, 9));
+        assert_ne!(first, render_png(Scene::OneSphere, 10));
+        // scenes built from random numbers come out the same too
+        assert_eq!(
+            render_png(Scene::TwoPerlinSpheres, 9),
+            render_png(Scene::TwoPerlinSpheres, 9)
+        );
+    }
+
+    #[test]
+    fn test_render_to_image_size() {
+        let settings = RenderSettings {
+            size: Size {
+                width: 24,
+                height: 16,
+            },
+            samples: 4,
+            seed: Some(5),
+            scene: Scene::OneSphere,
+            ..Default::default()
+        };
+
+        let image = render_to_image(settings.clone()).unwrap();
+        assert_eq!(image.dimensions(), (24, 16));
+        // the same pixels `render` encodes
+        let png = render(settings, &WorldCache::default(), None)
+            .unwrap()
+            .image;
+        assert_eq!(image::load_from_memory(&png).unwrap().to_rgb8(), image);
+    }
+
+    #[test]
+    fn test_empty_image_is_an_error() {
+        let settings = RenderSettings {
+            size: Size {
+                width: 0,
+                height: 8,
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            render(settings, &WorldCache::default(), None),
+            Err(RenderError::EmptyImage {
+                width: 0,
+                height: 8
+            })
+        ));
+    }
+
+    #[test]
+    fn test_progress_increases_to_one() {
+        let settings = RenderSettings {
+            size: Size {
+                width: 96,
+                height: 64,
+            },
+            samples: 1,
+            scene: Scene::OneSphere,
+            ..Default::default()
+        };
+        let reports = Mutex::new(Vec::new());
+        let progress = |fraction| reports.lock().unwrap().push(fraction);
+
+        render(settings, &WorldCache::default(), Some(&progress)).unwrap();
+        let reports = reports.into_inner().unwrap();
+        assert!(
+            reports.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            reports
+        );
+        assert_eq!(reports.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn test_image_formats() {
+        let settings = RenderSettings {
+            size: Size {
+                width: 8,
+                height: 6,
+            },
+            ..Default::default()
+        };
+        let pixels = vec![Color::new(0.2, 0.4, 0.8); 48];
+        let encode = |output_format| {
+            encode_output(
+                &pixels,
+                None,
+                &RenderSettings {
+                    output_format,
+                    ..settings.clone()
+                },
+            )
+            .unwrap()
+        };
+
+        assert!(encode(OutputFormat::Png).starts_with(b"\x89PNG"));
+        assert!(encode(OutputFormat::Jpeg).starts_with(&[0xff, 0xd8, 0xff]));
+        assert!(encode(OutputFormat::Bmp).starts_with(b"BM"));
+        assert!(encode(OutputFormat::Ppm).starts_with(b"P6"));
+        assert!(encode(OutputFormat::Gif).starts_with(b"GIF89a"));
+    }
+
+    #[test]
+    fn test_negative_focus_distance_is_corrected() {
+        let mut settings = RenderSettings {
+            size: Size {
+                width: 16,
+                height: 12,
+            },
+            samples: 2,
+            scene: Scene::ThreeSpheres,
+            camera_position: Point::new(0.0, 0.0, 3.0),
+            focus_point: Point::new(0.0, 0.0, -1.0),
+            defocus_angle: 10.0,
+            focus_distance: -4.0,
+            ..Default::default()
+        };
+        assert_eq!(corrected_focus_distance(&settings), Some(4.0));
+        settings.focus_distance = f32::NAN;
+        assert_eq!(corrected_focus_distance(&settings), Some(4.0));
+
+        settings.focus_distance = 4.0;
+        assert_eq!(corrected_focus_distance(&settings), None);
+        let world = create_world(&settings.scene);
+        let buffer = render_buffers(
+            &settings,
+            &world,
+            &[RenderPass::Beauty],
+            &RenderStats::default(),
+            || {},
+        )
+        .remove(0);
+        assert!(buffer
+            .iter()
+            .all(|color| color.r.is_finite() && color.g.is_finite() && color.b.is_finite()));
+    }
+
+    #[test]
+    fn test_exr_keeps_high_dynamic_range() {
+        let camera = get_scene_camera(&Scene::SimpleLight);
+        let settings = RenderSettings {
+            size: Size {
+                width: 48,
+                height: 27,
+            },
+            samples: 2,
+            max_depth: 4,
+            scene: Scene::SimpleLight,
+            camera_position: camera.camera_position,
+            focus_point: camera.focus_point,
+            field_of_view: camera.field_of_view,
+            output_format: OutputFormat::Exr,
+            ..Default::default()
+        };
+        let world = create_world(&settings.scene);
+        let buffers = render_buffers(
+            &settings,
+            &world,
+            &[RenderPass::Beauty],
+            &RenderStats::default(),
+            || {},
+        );
+        let bytes = encode_output(&buffers[0], None, &settings).unwrap();
+
+        let image = exr::prelude::read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(std::io::Cursor::new(bytes))
+            .unwrap();
+        let brightest = image
+            .layer_data
+            .channel_data
+            .list
+            .iter()
+            .flat_map(|channel| match &channel.sample_data {
+                exr::prelude::FlatSamples::F32(samples) => samples.clone(),
+                _ => panic!("expected float samples"),
+            })
+            .fold(0.0, f32::max);
+        // the light itself has a brightness of 4
+        assert!(brightest > 1.0, "{}", brightest);
+    }
+
+    #[test]
+    fn test_empty_scene_shows_background() {
+        let settings = RenderSettings {
+            size: Size {
+                width: 16,
+                height: 12,
+            },
+            samples: 3,
+            scene: Scene::Empty,
+            ..Default::default()
+        };
+        let world = create_world(&settings.scene);
+        let stats = RenderStats::default();
+        let buffer =
+            render_buffers(&settings, &world, &[RenderPass::Beauty], &stats, || {}).remove(0);
+
+        // one ray through each pixel center, however many samples were asked for
+        assert_eq!(stats.camera_rays(), 16 * 12);
+        let camera = Camera::new(&settings);
+        for (index, color) in buffer.iter().enumerate() {
+            let (x, y) = (index as u32 % 16, index as u32 / 16);
+            let expected = world
+                .background
+                .color(&camera.get_center_ray(x, y).direction);
+            assert_eq!(*color, expected, "({}, {})", x, y);
+        }
+        assert_ne!(buffer[0], buffer[buffer.len() - 1]);
+    }
+
+    #[test]
+    fn test_stats_count_every_sample() {
+        let settings = RenderSettings {
+            size: Size {
+                width: 8,
+                height: 6,
+            },
+            samples: 3,
+            max_depth: 2,
+            ..Default::default()
+        };
+        let world = create_world(&settings.scene);
+        let stats = RenderStats::default();
+        render_buffers(&settings, &world, &[RenderPass::Beauty], &stats, || {});
+        assert_eq!(stats.camera_rays(), 8 * 6 * 3);
+    }
+
+    #[test]
+    fn test_camera_rays_missing_scene_are_culled() {
+        let settings = RenderSettings {
+            size: Size {
+                width: 16,
+                height: 12,
+            },
+            samples: 2,
+            ..Default::default()
+        };
+        let sky = Color::new(0.7, 0.8, 1.0);
+        let world = World {
+            object: Object::Sphere(Sphere::new(
+                Point::new(0.0, 0.0, -1.0),
+                0.3,
+                Material::Lambertian(Lambertian {
+                    albedo: Color::new(0.5, 0.5, 0.5),
+                }),
+            )),
+            background: Background::Solid(sky),
+            lights: Lights::default(),
+            important: Vec::new(),
+        };
+        let stats = RenderStats::default();
+        let buffers = render_buffers(
+            &settings,
+            &world,
+            &[RenderPass::Beauty, RenderPass::Depth],
+            &stats,
+            || {},
+        );
+
+        // the sphere only covers the middle of the image
+        assert!(stats.culled_rays() > stats.camera_rays() / 2);
+        assert!(stats.culled_rays() < stats.camera_rays());
+
+        // rays from the corner go nowhere near the sphere, so get the background straight away
+        assert_eq!(buffers[0][0], sky);
+        assert_eq!(buffers[1][0].r, f64::INFINITY);
+        // while rays through the middle go on to hit it
+        let center = (6 * 16 + 8) as usize;
+        assert_ne!(buffers[0][center], sky);
+        assert!(buffers[1][center].r < 1.0);
+    }
+
+    fn single_sphere(material: Material) -> Object {
+        Object::Collection(Collection {
+            objects: vec![Object::Sphere(Sphere::new(
+                Point::new(0.0, 0.0, -1.0),
+                0.5,
+                material,
+            ))],
+        })
+    }
+
+    #[test]
+    fn test_reflection_pass_ignores_diffuse() {
+        let object = single_sphere(Material::Lambertian(Lambertian {
+            albedo: Color::new(0.5, 0.5, 0.5),
+        }));
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+
+        let color = pass_color(
+            &ray,
+            &object,
+            &background,
+            &Lights::default(),
+            &[],
+            50,
+            RenderPass::Reflection,
+            &WORLD_AXES,
+        );
+        assert_eq!(color, Color::BLACK);
+    }
+
+    #[test]
+    fn test_reflection_pass_mirrors_environment() {
+        let object = single_sphere(Material::Metal(Metal {
+            albedo: Color::new(1.0, 1.0, 1.0),
+            fuzz: 0.0,
+            anisotropy: 0.0,
+        }));
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, -1.0));
+        let sky = Color::new(0.7, 0.8, 1.0);
+        let background = Background::Solid(sky);
+
+        let color = pass_color(
+            &ray,
+            &object,
+            &background,
+            &Lights::default(),
+            &[],
+            50,
+            RenderPass::Reflection,
+            &WORLD_AXES,
+        );
+        assert_eq!(color, sky);
+
+        let miss = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        let color = pass_color(
+            &miss,
+            &object,
+            &background,
+            &Lights::default(),
+            &[],
+            50,
+            RenderPass::Reflection,
+            &WORLD_AXES,
+        );
+        assert_eq!(color, Color::BLACK);
+    }
+
+    #[test]
+    fn test_progress_throttle_by_pixels() {
+        let progress = ProgressThrottle::new(10_000, 256, Duration::from_secs(3600));
+        let now = progress.start;
+
+        let updates = (0..10_000)
+            .filter_map(|_| progress.complete_pixel(now))
+            .collect::<Vec<_>>();
+
+        // one update per full interval, plus the final pixel
+        assert_eq!(updates.len(), 10_000 / 256 + 1);
+        assert_eq!(updates[0], 256.0 / 10_000.0);
+        assert_eq!(*updates.last().unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_progress_throttle_by_time() {
+        let progress = ProgressThrottle::new(1000, 1_000_000, Duration::from_millis(16));
+
+        // a pixel every 4ms means an update every fourth pixel
+        let updates = (1..=1000)
+            .filter_map(|i| progress.complete_pixel(progress.start + Duration::from_millis(4 * i)))
+            .count();
+
+        assert_eq!(updates, 1000 / 4);
+    }
+
+    /// The recursive form `ray_color` used to have, kept to check the loop against
+    fn recursive_ray_color(ray: &Ray, obj: &Object, background: &Background, depth: u32) -> Color {
+        if depth == 0 {
+            return Color::BLACK;
+        }
+
+        let Some(hit) = obj.hit(ray, 0.001..f64::INFINITY) else {
+            return background.color(&ray.direction);
+        };
+
+        let color_from_emission = hit.material.emit(hit.u, hit.v, &hit.point);
+        let Some(deflection) = hit.material.deflect(ray, &hit) else {
+            return color_from_emission;
+        };
+
+        let color_from_deflection = deflection.attenuation
+            * recursive_ray_color(&deflection.ray, obj, background, depth - 1);
+        color_from_emission + color_from_deflection
+    }
+
+    #[test]
+    fn test_iterative_ray_color_matches_recursive() {
+        for scene in [
+            Scene::ThreeSpheres,
+            Scene::GlassSpheres,
+            Scene::HollowGlassSphere,
+            Scene::MetalSpheres,
+            Scene::SimpleLight,
+        ] {
+            let settings = RenderSettings {
+                size: Size {
+                    width: 12,
+                    height: 8,
+                },
+                scene,
+                ..Default::default()
+            };
+            let world = create_world(&settings.scene);
+            let camera = Camera::new(&settings);
+
+            for (x, y) in (0..12).flat_map(|x| (0..8).map(move |y| (x, y))) {
+                // the same seed makes both follow the same path
+                let trace = |ray_color: fn(&Ray, &Object, &Background, u32) -> Color| {
+                    rng::seed_pixel(3, x, y);
+                    let ray = camera.get_ray(x, y);
+                    ray_color(&ray, &world.object, &world.background, settings.max_depth)
+                };
+                let iterative = trace(|ray, obj, background, depth| {
+                    ray_color(ray, obj, background, &Lights::default(), &[], depth)
+                });
+                let recursive = trace(recursive_ray_color);
+
+                // the sums are grouped differently, so allow for rounding
+                for (a, b) in [
+                    (iterative.r, recursive.r),
+                    (iterative.g, recursive.g),
+                    (iterative.b, recursive.b),
+                ] {
+                    assert!(
+                        (a - b).abs() <= 1e-12 * b.abs().max(1.0),
+                        "{:?} at ({}, {}): {:?} != {:?}",
+                        settings.scene,
+                        x,
+                        y,
+                        iterative,
+                        recursive
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_depth_does_not_grow_stack() {
+        // a ray trapped inside a perfect mirror bounces until it runs out of depth
+        let mirror = single_sphere(Material::Metal(Metal {
+            albedo: Color::WHITE,
+            fuzz: 0.0,
+            anisotropy: 0.0,
+        }));
+        let ray = Ray::new(Point::new(0.0, 0.0, -1.0), Vector::new(0.3, 0.2, 1.0));
+        let background = Background::Solid(Color::WHITE);
+
+        // far too small a stack to recurse 2048 times
+        let color = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(move || ray_color(&ray, &mirror, &background, &Lights::default(), &[], 2048))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(color, Color::BLACK);
+    }
+
+    #[cfg(debug_assertions)]
+    fn count_energy_violations(obj: &Object, background: &Background) -> usize {
+        ENERGY_VIOLATIONS.with(|count| count.set(0));
+        for _ in 0..1000 {
+            let direction = Vector::new(0.0, 0.0, -1.0) + Vector::random_in_unit_disk() * 0.5;
+            let ray = Ray::new(Point::new(0.0, 0.0, 0.0), direction);
+            ray_color(&ray, obj, background, &Lights::default(), &[], 50);
+        }
+        ENERGY_VIOLATIONS.with(|count| count.get())
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_plausible_scene_conserves_energy() {
+        for scene in [Scene::MetalSpheres, Scene::ThreeSpheres] {
+            let world = create_world(&scene);
+            assert_eq!(count_energy_violations(&world.object, &world.background), 0);
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_amplifying_metal_violates_energy_conservation() {
+        let object = Object::Collection(Collection {
+            objects: vec![
+                Object::Sphere(Sphere::new(
+                    Point::new(0.0, 0.0, -1.0),
+                    0.5,
+                    Material::Metal(Metal {
+                        albedo: Color::new(2.0, 2.0, 2.0),
+                        fuzz: 0.0,
+                        anisotropy: 0.0,
+                    }),
+                )),
+                Object::Sphere(Sphere::new(
+                    Point::new(0.0, -100.5, -1.0),
+                    100.0,
+                    Material::Lambertian(Lambertian {
+                        albedo: Color::new(0.5, 0.5, 0.5),
+                    }),
+                )),
+            ],
+        });
+        let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+
+        assert!(count_energy_violations(&object, &background) > 0);
+    }
 }
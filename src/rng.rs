@@ -0,0 +1,101 @@
+use rand::distributions::{Distribution, Standard};
+use rand::rngs::StdRng;
+use rand::{Error, Rng, RngCore, SeedableRng};
+use std::cell::RefCell;
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Handle to the render thread's random number generator. Unlike `rand::thread_rng`, it can be
+/// reseeded per pixel, so a seeded render draws the same numbers for each pixel no matter which
+/// thread renders it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderRng;
+
+impl RngCore for RenderRng {
+    fn next_u32(&mut self) -> u32 {
+        RNG.with(|rng| rng.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        RNG.with(|rng| rng.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RNG.with(|rng| rng.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        RNG.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+pub fn thread_rng() -> RenderRng {
+    RenderRng
+}
+
+/// Drop-in for `rand::random` that draws from the render thread's generator
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    RenderRng.gen()
+}
+
+/// Restarts the current thread's generator on the stream for pixel `(x, y)` of a render seeded
+/// with `seed`
+pub fn seed_pixel(seed: u64, x: u32, y: u32) {
+    let pixel = (x as u64) << 32 | y as u64;
+    let stream = split_mix(split_mix(seed) ^ pixel);
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(stream));
+}
+
+/// Runs `f` with the current thread's generator seeded from `seed`, then puts the previous
+/// generator back, so the numbers drawn inside `f` are the same on every run
+pub fn with_seed<T>(seed: u64, f: impl FnOnce() -> T) -> T {
+    let previous = RNG.with(|rng| rng.replace(StdRng::seed_from_u64(split_mix(seed))));
+    let result = f();
+    RNG.with(|rng| *rng.borrow_mut() = previous);
+    result
+}
+
+/// SplitMix64 finalizer, so nearby seeds and pixels get unrelated streams
+fn split_mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_pixel_repeats_stream() {
+        seed_pixel(7, 3, 4);
+        let first: Vec<f64> = (0..4).map(|_| random()).collect();
+        seed_pixel(7, 3, 4);
+        let second: Vec<f64> = (0..4).map(|_| random()).collect();
+        assert_eq!(first, second);
+
+        seed_pixel(7, 4, 3);
+        let swapped: Vec<f64> = (0..4).map(|_| random()).collect();
+        assert_ne!(first, swapped);
+    }
+
+    #[test]
+    fn test_with_seed_restores_generator() {
+        let draw = || (0..4).map(|_| random()).collect::<Vec<f64>>();
+        seed_pixel(1, 0, 0);
+        let expected = draw();
+
+        seed_pixel(1, 0, 0);
+        let inside = with_seed(5, draw);
+        assert_eq!(inside, with_seed(5, draw));
+        assert_ne!(inside, expected);
+        // the stream outside carries on as if nothing happened
+        assert_eq!(draw(), expected);
+    }
+}
@@ -1,7 +1,8 @@
 use crate::data::Size;
-use crate::vector::Point;
+use crate::output::OutputFormat;
+use crate::renderer::{ColorSpace, NormalSpace, RenderPass, ToneMap};
+use crate::vector::{Point, Vector};
 use crate::world::Scene;
-#[cfg(feature = "gui")]
 use log::info;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "gui")]
@@ -10,21 +11,61 @@ use std::error::Error;
 use std::io::{ErrorKind, Read, Write};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct RenderSettings {
     pub size: Size<u32>,
     pub samples: u32,
     pub max_depth: u32,
     pub camera_position: Point,
     pub focus_point: Point,
+    /// Direction that should point up in the image. It doesn't have to be perpendicular to the
+    /// view, and tilting it sideways rolls the camera.
+    pub up: Vector,
     pub field_of_view: f32,
     pub defocus_angle: f32,
     pub focus_distance: f32,
+    /// Strength of cat's-eye vignetting, from 0 (off) to 1. Clips the defocus aperture more the
+    /// further a pixel is from the center, so bokeh turns lens-shaped towards the corners.
+    pub cats_eye: f32,
+    /// Multiplier for the light coming from the background
+    pub environment_intensity: f32,
     pub scene: Scene,
+    pub annotate: bool,
+    /// Apply ordered dithering when quantizing to 8 bits per channel
+    pub dither: bool,
+    /// Make PNG output transparent where the camera sees the background, for compositing
+    pub alpha: bool,
+    /// Leave color passes linear instead of gamma correcting them, for renders used as data
+    pub linear: bool,
+    /// Applied to color passes before gamma correction when writing 8-bit images
+    pub tone_map: ToneMap,
+    /// How color passes are gamma corrected when writing 8-bit images
+    pub color_space: ColorSpace,
+    /// Exponent of the power curve used by `ColorSpace::Gamma`
+    pub gamma: f64,
+    pub output_format: OutputFormat,
+    /// Quality of JPEG output, from 1 to 100
+    pub jpeg_quality: u8,
+    pub pass: RenderPass,
+    pub normal_space: NormalSpace,
+    /// Write every pass in `EXR_LAYER_PASSES` into one multilayer OpenEXR file, ignoring `pass`
+    /// and `output_format`
+    pub exr_layers: bool,
+    /// Also produce normalized depth and normal images of the first hit of each camera ray, for
+    /// denoising and debugging
+    pub aov: bool,
+    /// Seeds every pixel's random numbers, so the render comes out the same each time regardless
+    /// of thread count or scheduling. Unseeded renders are different every time.
+    pub seed: Option<u64>,
+    /// Only render the pixels from (x0, y0) up to but not including (x1, y1), leaving the rest of
+    /// the image black. The image keeps its full size.
+    pub region: Option<(u32, u32, u32, u32)>,
 }
 
 pub struct CameraSettings {
     pub camera_position: Point,
     pub focus_point: Point,
+    pub up: Vector,
     pub field_of_view: f32,
 }
 
@@ -47,10 +88,32 @@ impl Default for RenderSettings {
                 y: 0.0,
                 z: -1.0,
             },
+            up: Vector {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
             field_of_view: 90.0,
             defocus_angle: 0.0,
             focus_distance: 10.0,
+            cats_eye: 0.0,
+            environment_intensity: 1.0,
             scene: Scene::OneSphere,
+            annotate: false,
+            dither: false,
+            alpha: false,
+            linear: false,
+            tone_map: ToneMap::None,
+            color_space: ColorSpace::Srgb,
+            gamma: 2.2,
+            output_format: OutputFormat::Png,
+            jpeg_quality: 90,
+            pass: RenderPass::Beauty,
+            normal_space: NormalSpace::World,
+            exr_layers: false,
+            aov: false,
+            seed: None,
+            region: None,
         }
     }
 }
@@ -81,8 +144,23 @@ pub fn load_settings() -> Result<RenderSettings, Box<dyn Error>> {
 
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
-    let settings: RenderSettings = toml::from_str(contents.as_str())?;
-    Ok(settings)
+    Ok(parse_settings(contents.as_str())?)
+}
+
+/// Parses a settings file, filling any fields it doesn't mention (e.g. settings added since the
+/// file was saved) from the defaults
+pub fn parse_settings(contents: &str) -> Result<RenderSettings, toml::de::Error> {
+    let table: toml::Table = toml::from_str(contents)?;
+    if let Ok(toml::Value::Table(defaults)) = toml::Value::try_from(RenderSettings::default()) {
+        for field in defaults.keys().filter(|field| !table.contains_key(*field)) {
+            info!(
+                "Setting `{}` not found in settings file, using default",
+                field
+            );
+        }
+    }
+
+    RenderSettings::deserialize(table)
 }
 
 #[cfg(feature = "gui")]
@@ -92,3 +170,41 @@ fn get_settings_path() -> std::path::PathBuf {
     path.push("settings.toml");
     path
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_settings_fills_missing_fields() {
+        let mut table = toml::Table::try_from(RenderSettings {
+            samples: 42,
+            defocus_angle: 3.0,
+            ..Default::default()
+        })
+        .unwrap();
+        table.remove("defocus_angle");
+        let contents = toml::to_string(&table).unwrap();
+
+        let settings = parse_settings(&contents).unwrap();
+        assert_eq!(settings.samples, 42);
+        assert_eq!(
+            settings.defocus_angle,
+            RenderSettings::default().defocus_angle
+        );
+    }
+
+    #[test]
+    fn test_parse_settings_round_trip() {
+        let settings = RenderSettings {
+            samples: 7,
+            annotate: true,
+            ..Default::default()
+        };
+        let contents = toml::to_string(&settings).unwrap();
+
+        let parsed = parse_settings(&contents).unwrap();
+        assert_eq!(parsed.samples, 7);
+        assert!(parsed.annotate);
+    }
+}
@@ -1,9 +1,14 @@
 use crate::color::Color;
 use crate::perlin::Perlin;
-use crate::vector::Point;
+use crate::vector::{Point, Vector};
 use enum_dispatch::enum_dispatch;
+use image::codecs::hdr::HdrDecoder;
+use image::DynamicImage;
 use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 #[enum_dispatch]
 #[derive(Clone)]
@@ -12,6 +17,25 @@ pub enum Texture {
     Checker,
     Image,
     Noise,
+    Marble,
+    Gradient,
+    Transform,
+}
+
+impl Texture {
+    /// Bytes allocated by the texture outside of itself. Image data shared between clones is
+    /// counted by each of them.
+    pub fn heap_size(&self) -> usize {
+        match self {
+            Texture::Image(image) => image.data.capacity() * std::mem::size_of::<Color>(),
+            Texture::Noise(noise) => noise.perlin.heap_size(),
+            Texture::Marble(marble) => marble.perlin.heap_size(),
+            Texture::Transform(transform) => {
+                std::mem::size_of::<Texture>() + transform.inner.heap_size()
+            }
+            Texture::Solid(_) | Texture::Checker(_) | Texture::Gradient(_) => 0,
+        }
+    }
 }
 
 #[enum_dispatch(Texture)]
@@ -30,11 +54,22 @@ impl ColorAt for Solid {
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CheckerMode {
+    /// Checkered in world space, as if the object were carved from a checkered block
+    #[default]
+    Solid,
+    /// Checkered in texture (u, v) space, so the squares follow the surface
+    Surface,
+}
+
 #[derive(Clone)]
 pub struct Checker {
     even: Color,
     odd: Color,
     inverse_scale: f64,
+    mode: CheckerMode,
+    duty_cycle: f64,
 }
 
 impl Checker {
@@ -43,30 +78,90 @@ impl Checker {
             even,
             odd,
             inverse_scale: 1.0 / scale,
+            mode: CheckerMode::default(),
+            duty_cycle: 0.5,
+        }
+    }
+
+    /// In `Surface` mode the scale is the size of a square in (u, v) space
+    pub fn with_mode(self, mode: CheckerMode) -> Checker {
+        Checker { mode, ..self }
+    }
+
+    /// Fraction of each axis' period, two squares long, that is `odd`. The default of 0.5 gives
+    /// equal squares; small values give thin `odd` lines on an `even` background, with the
+    /// crossings of lines on different axes flipping back to `even`.
+    pub fn with_duty_cycle(self, duty_cycle: f64) -> Checker {
+        Checker {
+            duty_cycle: duty_cycle.clamp(0.0, 1.0),
+            ..self
         }
     }
+
+    /// Whether a coordinate falls in the `odd` part of its axis' period
+    fn is_odd(&self, coordinate: f64) -> bool {
+        let scaled = self.inverse_scale * coordinate;
+        let cell = scaled.floor();
+        // the position within the period, counted from the start of its even square
+        let phase = (cell.rem_euclid(2.0) + (scaled - cell)) / 2.0;
+        phase >= 1.0 - self.duty_cycle
+    }
 }
 
 impl ColorAt for Checker {
-    fn color_at(&self, _u: f64, _v: f64, point: &Point) -> Color {
-        let x = (self.inverse_scale * point.x).floor() as i32;
-        let y = (self.inverse_scale * point.y).floor() as i32;
-        let z = (self.inverse_scale * point.z).floor() as i32;
+    fn color_at(&self, u: f64, v: f64, point: &Point) -> Color {
+        let is_odd = match self.mode {
+            CheckerMode::Solid => {
+                self.is_odd(point.x) ^ self.is_odd(point.y) ^ self.is_odd(point.z)
+            }
+            CheckerMode::Surface => self.is_odd(u) ^ self.is_odd(v),
+        };
 
-        let is_even = (x + y + z) % 2 == 0;
-        if is_even {
-            self.even
-        } else {
+        if is_odd {
             self.odd
+        } else {
+            self.even
         }
     }
 }
 
+/// How an image texture is looked up outside of (u, v) coordinates 0 to 1
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    /// Stretch the edge pixels out forever
+    #[default]
+    Clamp,
+    /// Tile the image
+    Repeat,
+    /// Tile the image, flipping every other copy so the tiles meet seamlessly
+    Mirror,
+}
+
+impl WrapMode {
+    /// Brings a texture coordinate into 0 to 1
+    fn apply(self, coordinate: f64) -> f64 {
+        match self {
+            WrapMode::Clamp => coordinate.clamp(0.0, 1.0),
+            WrapMode::Repeat => coordinate.rem_euclid(1.0),
+            WrapMode::Mirror => {
+                let coordinate = coordinate.rem_euclid(2.0);
+                if coordinate > 1.0 {
+                    2.0 - coordinate
+                } else {
+                    coordinate
+                }
+            }
+        }
+    }
+}
+
+/// Clones share the pixel data, so one texture can be used by many objects without copying it
 #[derive(Clone)]
 pub struct Image {
-    pub data: Vec<Color>,
+    pub data: Arc<Vec<Color>>,
     pub width: u32,
     pub height: u32,
+    pub wrap: WrapMode,
 }
 
 #[derive(Debug)]
@@ -85,25 +180,95 @@ impl std::fmt::Display for TextureError {
 }
 
 impl Image {
+    pub fn with_wrap(self, wrap: WrapMode) -> Image {
+        Image { wrap, ..self }
+    }
+
+    /// Loads a color image. 8-bit images are taken to be sRGB, like photos and paintings, and are
+    /// decoded to linear light.
     pub fn load(path: PathBuf) -> Result<Image, Box<dyn Error>> {
+        Image::load_with(path, true)
+    }
+
+    /// Loads an image of data, like a normal map, whose values are used as they are
+    pub fn load_data(path: PathBuf) -> Result<Image, Box<dyn Error>> {
+        Image::load_with(path, false)
+    }
+
+    fn load_with(path: PathBuf, srgb: bool) -> Result<Image, Box<dyn Error>> {
+        let is_hdr = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+        if is_hdr {
+            return Image::load_hdr(path);
+        }
+
         let img = image::open(path.clone())?;
-        let data = img
-            .to_rgb8()
-            .into_raw()
-            .chunks_exact(3)
-            .map(Color::from)
-            .collect();
+        let data = match img {
+            // keep the full range of HDR images, whose bright spots go well above 1.0
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => img
+                .to_rgb32f()
+                .pixels()
+                .map(|pixel| {
+                    let [r, g, b] = pixel.0.map(f64::from);
+                    Color::new(r, g, b)
+                })
+                .collect(),
+            _ => img
+                .to_rgb8()
+                .into_raw()
+                .chunks_exact(3)
+                .map(Color::from)
+                .map(|color| if srgb { color.srgb_decode() } else { color })
+                .collect(),
+        };
         let width = img.width();
         let height = img.height();
         if width == 0 || height == 0 {
             return Err(Box::new(TextureError));
         }
         Ok(Image {
-            data,
+            data: Arc::new(data),
             width,
             height,
+            wrap: WrapMode::Clamp,
         })
     }
+
+    /// Radiance HDR files go through their own decoder, since `image::open` squeezes them into
+    /// 8 bits and loses everything brighter than 1.0
+    fn load_hdr(path: PathBuf) -> Result<Image, Box<dyn Error>> {
+        let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+        let metadata = decoder.metadata();
+        let (width, height) = (metadata.width, metadata.height);
+        if width == 0 || height == 0 {
+            return Err(Box::new(TextureError));
+        }
+        let data = decoder
+            .read_image_hdr()?
+            .into_iter()
+            .map(|pixel| {
+                let [r, g, b] = pixel.0.map(f64::from);
+                Color::new(r, g, b)
+            })
+            .collect();
+        Ok(Image {
+            data: Arc::new(data),
+            width,
+            height,
+            wrap: WrapMode::Clamp,
+        })
+    }
+}
+
+impl std::fmt::Debug for Image {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Image")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("wrap", &self.wrap)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Default for Image {
@@ -123,20 +288,22 @@ impl Default for Image {
         }
 
         Image {
-            data,
+            data: Arc::new(data),
             width: GRID_SIZE,
             height: GRID_SIZE,
+            wrap: WrapMode::Clamp,
         }
     }
 }
 
 impl ColorAt for Image {
     fn color_at(&self, u: f64, v: f64, _point: &Point) -> Color {
-        let u = u.clamp(0.0, 1.0);
-        let v = 1.0 - v.clamp(0.0, 1.0);
+        let u = self.wrap.apply(u);
+        let v = 1.0 - self.wrap.apply(v);
 
-        let x = (u * self.width as f64) as u32;
-        let y = (v * self.height as f64) as u32;
+        // u or v of exactly 1.0 would land one pixel past the edge
+        let x = ((u * self.width as f64) as u32).min(self.width - 1);
+        let y = ((v * self.height as f64) as u32).min(self.height - 1);
 
         let index = (y * self.width + x) as usize;
         self.data[index]
@@ -147,20 +314,405 @@ impl ColorAt for Image {
 pub struct Noise {
     perlin: Perlin,
     scale: f64,
+    time: f64,
+    /// Octaves of noise summed into the turbulence, `Perlin::turbulence`'s default if `None`
+    turbulence_depth: Option<u32>,
 }
 
 impl Noise {
+    /// Direction the sample point drifts along as time advances. Perlin noise is only 3D, so this
+    /// stands in for a fourth dimension; it is deliberately not axis-aligned to avoid the pattern
+    /// visibly sliding along a single axis.
+    const TIME_AXIS: Vector = Vector {
+        x: 0.57,
+        y: 0.71,
+        z: 0.41,
+    };
+
     pub fn new(scale: f64) -> Noise {
         Noise {
             perlin: Perlin::new(),
             scale,
+            time: 0.0,
+            turbulence_depth: None,
+        }
+    }
+
+    /// Sets the animation time, so the same noise can shimmer across the frames of an animation
+    pub fn with_time(self, time: f64) -> Noise {
+        Noise { time, ..self }
+    }
+
+    /// Sets how many octaves of noise make up the turbulence. Fewer give smoother, blotchier
+    /// noise, more add finer detail.
+    pub fn with_turbulence_depth(self, depth: u32) -> Noise {
+        Noise {
+            turbulence_depth: Some(depth),
+            ..self
         }
     }
 }
 
 impl ColorAt for Noise {
     fn color_at(&self, _u: f64, _v: f64, point: &Point) -> Color {
-        let s = *point * self.scale;
-        Color::new(1.0, 1.0, 1.0) * self.perlin.turbulence(&s, None)
+        let s = *point * self.scale + Self::TIME_AXIS * self.time;
+        let depth = self.turbulence_depth.map(|depth| depth as usize);
+        Color::new(1.0, 1.0, 1.0) * self.perlin.turbulence(&s, depth)
+    }
+}
+
+/// Veins of marble: stripes across the z axis, `scale` to a unit of distance, bent by turbulence
+#[derive(Clone)]
+pub struct Marble {
+    perlin: Perlin,
+    scale: f64,
+}
+
+impl Marble {
+    pub fn new(scale: f64) -> Marble {
+        Marble {
+            perlin: Perlin::new(),
+            scale,
+        }
+    }
+}
+
+impl ColorAt for Marble {
+    fn color_at(&self, _u: f64, _v: f64, point: &Point) -> Color {
+        let phase = self.scale * point.z + 10.0 * self.perlin.turbulence(point, None);
+        Color::new(1.0, 1.0, 1.0) * (0.5 * (1.0 + phase.sin()))
+    }
+}
+
+/// The coordinate a gradient runs along
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum GradientAxis {
+    /// Across the texture
+    U,
+    /// Up the texture
+    #[default]
+    V,
+    /// Along the world's axes, for gradients that ignore how the surface is mapped
+    X,
+    Y,
+    Z,
+}
+
+/// Blends from `start` at 0 to `end` at 1 along `axis`, for backdrops. Past either end the color
+/// stays at that end's.
+#[derive(Clone)]
+pub struct Gradient {
+    pub start: Color,
+    pub end: Color,
+    pub axis: GradientAxis,
+}
+
+impl ColorAt for Gradient {
+    fn color_at(&self, u: f64, v: f64, point: &Point) -> Color {
+        let t = match self.axis {
+            GradientAxis::U => u,
+            GradientAxis::V => v,
+            GradientAxis::X => point.x,
+            GradientAxis::Y => point.y,
+            GradientAxis::Z => point.z,
+        };
+        let t = t.clamp(0.0, 1.0);
+        self.start * (1.0 - t) + self.end * t
+    }
+}
+
+/// Scales then offsets the (u, v) coordinates another texture is looked up with, to tile or shift
+/// it. Textures that only look at the hit point, like solid checkers and noise, are unaffected.
+#[derive(Clone)]
+pub struct Transform {
+    pub inner: Box<Texture>,
+    pub scale: (f64, f64),
+    pub offset: (f64, f64),
+}
+
+impl ColorAt for Transform {
+    fn color_at(&self, u: f64, v: f64, point: &Point) -> Color {
+        self.inner.color_at(
+            u * self.scale.0 + self.offset.0,
+            v * self.scale.1 + self.offset.1,
+            point,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_clones_share_data() {
+        let image = Image {
+            data: Arc::new(vec![
+                Color::BLACK,
+                Color::WHITE,
+                Color::MAGENTA,
+                Color::BLACK,
+            ]),
+            width: 2,
+            height: 2,
+            wrap: WrapMode::Clamp,
+        };
+        let copy = image.clone();
+        assert!(Arc::ptr_eq(&image.data, &copy.data));
+
+        // v = 1 is the top row
+        let point = Point::new(0.0, 0.0, 0.0);
+        assert_eq!(copy.color_at(0.75, 0.75, &point), Color::WHITE);
+        assert_eq!(copy.color_at(0.25, 0.25, &point), Color::MAGENTA);
+    }
+
+    #[test]
+    fn test_load_decodes_srgb() {
+        let color = Image::load("res/earth.jpg".into()).unwrap();
+        let data = Image::load_data("res/earth.jpg".into()).unwrap();
+        assert_eq!(color.data.len(), data.data.len());
+        for (linear, encoded) in color.data.iter().zip(data.data.iter()).step_by(997) {
+            assert_eq!(*linear, encoded.srgb_decode());
+        }
+    }
+
+    #[test]
+    fn test_image_wrap_modes() {
+        // one row of four texels
+        let colors = [
+            Color::BLACK,
+            Color::new(0.25, 0.25, 0.25),
+            Color::new(0.5, 0.5, 0.5),
+            Color::WHITE,
+        ];
+        let image = Image {
+            data: Arc::new(colors.to_vec()),
+            width: 4,
+            height: 1,
+            wrap: WrapMode::default(),
+        };
+        let point = Point::new(0.0, 0.0, 0.0);
+        let texel = |image: &Image, u| image.color_at(u, 0.5, &point);
+
+        assert_eq!(image.wrap, WrapMode::Clamp);
+        assert_eq!(texel(&image, 1.25), colors[3]);
+        assert_eq!(texel(&image, 2.3), colors[3]);
+        assert_eq!(texel(&image, -0.6), colors[0]);
+
+        let repeat = image.clone().with_wrap(WrapMode::Repeat);
+        assert_eq!(texel(&repeat, 1.25), texel(&repeat, 0.25));
+        assert_eq!(texel(&repeat, 1.25), colors[1]);
+        assert_eq!(texel(&repeat, 2.3), colors[1]);
+        assert_eq!(texel(&repeat, -0.6), colors[1]);
+
+        let mirror = image.with_wrap(WrapMode::Mirror);
+        assert_eq!(texel(&mirror, 0.25), colors[1]);
+        assert_eq!(texel(&mirror, 1.1), colors[3]);
+        assert_eq!(texel(&mirror, 1.25), texel(&mirror, 0.75));
+        assert_eq!(texel(&mirror, 2.3), colors[1]);
+        assert_eq!(texel(&mirror, -0.1), colors[0]);
+    }
+
+    fn checker() -> Checker {
+        Checker::new(Color::WHITE, Color::BLACK, 0.5)
+    }
+
+    #[test]
+    fn test_checker_solid_mode() {
+        let checker = checker();
+        assert_eq!(checker.mode, CheckerMode::Solid);
+
+        // the texture coordinates are ignored in 3D
+        for (u, v) in [(0.1, 0.1), (0.6, 0.1), (0.9, 0.7)] {
+            assert_eq!(
+                checker.color_at(u, v, &Point::new(0.1, 0.2, 0.3)),
+                Color::WHITE
+            );
+            assert_eq!(
+                checker.color_at(u, v, &Point::new(0.6, 0.2, 0.3)),
+                Color::BLACK
+            );
+            assert_eq!(
+                checker.color_at(u, v, &Point::new(0.6, -0.2, 0.3)),
+                Color::WHITE
+            );
+        }
+    }
+
+    #[test]
+    fn test_checker_surface_mode() {
+        let checker = checker().with_mode(CheckerMode::Surface);
+        let point = Point::new(0.1, 0.2, 0.3);
+
+        assert_eq!(checker.color_at(0.25, 0.25, &point), Color::WHITE);
+        assert_eq!(checker.color_at(0.75, 0.25, &point), Color::BLACK);
+        assert_eq!(checker.color_at(0.25, 0.75, &point), Color::BLACK);
+        assert_eq!(checker.color_at(0.75, 0.75, &point), Color::WHITE);
+
+        // the hit point is ignored on the surface
+        assert_eq!(
+            checker.color_at(0.25, 0.25, &Point::new(0.6, 0.2, 0.3)),
+            Color::WHITE
+        );
+    }
+
+    #[test]
+    fn test_checker_duty_cycle() {
+        // the parity of the summed cells, as the checker worked before duty cycles
+        let parity = |point: &Point| {
+            let cell = |coordinate: f64| (2.0 * coordinate).floor() as i32;
+            if (cell(point.x) + cell(point.y) + cell(point.z)) % 2 == 0 {
+                Color::WHITE
+            } else {
+                Color::BLACK
+            }
+        };
+        let half = checker().with_duty_cycle(0.5);
+        for i in -50..50 {
+            let point = Point::new(i as f64 * 0.173, i as f64 * -0.291 + 0.05, i as f64 * 0.067);
+            assert_eq!(half.color_at(0.0, 0.0, &point), parity(&point));
+        }
+
+        // a period is two squares, so 1.0 in texture space here
+        let thin = checker()
+            .with_mode(CheckerMode::Surface)
+            .with_duty_cycle(0.1);
+        let point = Point::new(0.0, 0.0, 0.0);
+        let steps = 1000;
+        let odd_fraction = |color_at: &dyn Fn(f64) -> Color| {
+            (0..steps)
+                .filter(|&i| color_at((i as f64 + 0.5) / steps as f64 - 3.0) == Color::BLACK)
+                .count() as f64
+                / steps as f64
+        };
+        let along_u = odd_fraction(&|u| thin.color_at(u, 0.25, &point));
+        let along_v = odd_fraction(&|v| thin.color_at(0.25, v, &point));
+        assert!((along_u - 0.1).abs() < 0.002, "{}", along_u);
+        assert!((along_v - 0.1).abs() < 0.002, "{}", along_v);
+    }
+
+    #[test]
+    fn test_transform_scales_checker() {
+        let checker = checker().with_mode(CheckerMode::Surface);
+        let tiled = Transform {
+            inner: Box::new(Texture::Checker(checker.clone())),
+            scale: (2.0, 2.0),
+            offset: (0.0, 0.0),
+        };
+        let point = Point::new(0.0, 0.0, 0.0);
+
+        // squares are 0.5 across, and a quarter once scaled
+        let changes = |texture: &dyn ColorAt| {
+            (1..100)
+                .filter(|&i| {
+                    let (u, previous) = (i as f64 / 100.0 + 0.005, (i - 1) as f64 / 100.0 + 0.005);
+                    texture.color_at(u, 0.1, &point) != texture.color_at(previous, 0.1, &point)
+                })
+                .count()
+        };
+        assert_eq!(changes(&checker), 1);
+        assert_eq!(changes(&tiled), 3);
+        assert_eq!(tiled.color_at(0.3, 0.1, &point), Color::BLACK);
+        assert_eq!(checker.color_at(0.3, 0.1, &point), Color::WHITE);
+
+        let shifted = Transform {
+            offset: (0.5, 0.0),
+            ..tiled
+        };
+        assert_eq!(shifted.color_at(0.3, 0.1, &point), Color::WHITE);
+        assert_eq!(
+            Texture::Transform(shifted).heap_size(),
+            std::mem::size_of::<Texture>()
+        );
+    }
+
+    #[test]
+    fn test_noise_changes_over_time() {
+        let noise = Noise::new(4.0);
+        let later = noise.clone().with_time(0.5);
+        let point = Point::new(0.3, 1.2, -0.7);
+
+        let before = noise.color_at(0.0, 0.0, &point);
+        let after = later.color_at(0.0, 0.0, &point);
+        assert_ne!(before, after);
+        assert_eq!(before, noise.with_time(0.0).color_at(0.0, 0.0, &point));
+    }
+
+    #[test]
+    fn test_gradient_endpoints() {
+        let start = Color::new(0.9, 0.6, 0.3);
+        let end = Color::new(0.1, 0.2, 0.7);
+        let gradient = |axis| Gradient { start, end, axis };
+        let origin = Point::new(0.0, 0.0, 0.0);
+
+        let along_v = gradient(GradientAxis::V);
+        assert_eq!(along_v.color_at(0.3, 0.0, &origin), start);
+        assert_eq!(along_v.color_at(0.3, 1.0, &origin), end);
+        assert_eq!(along_v.color_at(0.3, 0.5, &origin), (start + end) * 0.5);
+        // clamped past the ends
+        assert_eq!(along_v.color_at(0.3, -2.0, &origin), start);
+        assert_eq!(along_v.color_at(0.3, 3.0, &origin), end);
+
+        let along_u = gradient(GradientAxis::U);
+        assert_eq!(along_u.color_at(0.0, 0.7, &origin), start);
+        assert_eq!(along_u.color_at(1.0, 0.7, &origin), end);
+
+        let along_y = gradient(GradientAxis::Y);
+        assert_eq!(along_y.color_at(0.5, 0.5, &origin), start);
+        assert_eq!(along_y.color_at(0.5, 0.5, &Point::new(4.0, 1.0, -2.0)), end);
+
+        // a transform can squeeze it into the right half of the surface
+        let repeated = Transform {
+            inner: Box::new(Texture::Gradient(along_u)),
+            scale: (2.0, 1.0),
+            offset: (-1.0, 0.0),
+        };
+        assert_eq!(repeated.color_at(0.5, 0.0, &origin), start);
+        assert_eq!(repeated.color_at(1.0, 0.0, &origin), end);
+    }
+
+    #[test]
+    fn test_turbulence_depth_adds_detail() {
+        let noise = Noise::new(4.0);
+        let patch = |noise: &Noise| {
+            (0..16)
+                .flat_map(|x| {
+                    (0..16).map(move |y| Point::new(x as f64 * 0.05, y as f64 * 0.05, 0.3))
+                })
+                .map(|point| noise.color_at(0.0, 0.0, &point).r)
+                .collect::<Vec<_>>()
+        };
+
+        let smooth = patch(&noise.clone().with_turbulence_depth(1));
+        let detailed = patch(&noise.clone().with_turbulence_depth(7));
+        assert_ne!(smooth, detailed);
+        // neighbouring samples differ less without the finer octaves
+        let variation = |samples: &[f64]| {
+            samples
+                .windows(2)
+                .map(|pair| (pair[1] - pair[0]).abs())
+                .sum::<f64>()
+        };
+        assert!(variation(&smooth) < variation(&detailed));
+        // the default is the full depth
+        assert_eq!(detailed, patch(&noise));
+    }
+
+    #[test]
+    fn test_marble_stays_in_unit_range() {
+        let marble = Marble::new(4.0);
+        let mut levels = Vec::new();
+        for _ in 0..10_000 {
+            let point = Vector::random_in_unit_sphere() * 20.0;
+            let color = marble.color_at(0.0, 0.0, &point);
+            assert!((0.0..=1.0).contains(&color.r), "{:?} at {:?}", color, point);
+            assert!(color.r == color.g && color.g == color.b);
+            levels.push(color.r);
+        }
+
+        // the full range of the sine is reached, not just the middle of it
+        let darkest = levels.iter().copied().fold(f64::INFINITY, f64::min);
+        let brightest = levels.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        assert!(darkest < 0.05 && brightest > 0.95);
     }
 }
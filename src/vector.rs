@@ -1,3 +1,4 @@
+use crate::rng;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::ops::Range;
@@ -24,15 +25,17 @@ impl Vector {
     }
 
     pub fn dot(&self, other: &Self) -> f64 {
-        self.x * other.x + self.y * other.y + self.z * other.z
+        #[cfg(feature = "simd")]
+        return simd::dot(self, other);
+        #[cfg(not(feature = "simd"))]
+        return scalar::dot(self, other);
     }
 
     pub fn cross(&self, other: &Self) -> Self {
-        Self {
-            x: self.y * other.z - self.z * other.y,
-            y: self.z * other.x - self.x * other.z,
-            z: self.x * other.y - self.y * other.x,
-        }
+        #[cfg(feature = "simd")]
+        return simd::cross(self, other);
+        #[cfg(not(feature = "simd"))]
+        return scalar::cross(self, other);
     }
 
     pub fn length(&self) -> f64 {
@@ -40,7 +43,7 @@ impl Vector {
     }
 
     pub fn length_squared(&self) -> f64 {
-        self.x * self.x + self.y * self.y + self.z * self.z
+        self.dot(self)
     }
 
     pub fn normalize(&self) -> Self {
@@ -53,7 +56,7 @@ impl Vector {
     }
 
     pub fn random() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = rng::thread_rng();
         Self {
             x: rng.gen(),
             y: rng.gen(),
@@ -62,7 +65,7 @@ impl Vector {
     }
 
     pub fn random_with_range(range: Range<f64>) -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = rng::thread_rng();
         Self {
             x: rng.gen_range(range.clone()),
             y: rng.gen_range(range.clone()),
@@ -71,19 +74,22 @@ impl Vector {
     }
 
     pub fn random_in_unit_sphere() -> Self {
-        // TODO: Rejection sampling is slow, use a better method
-        // should be better to generate a random angle and figure out where it lands on the sphere
-        // since this vector is currently always normalized afterward, that would remove the necessity for that step
-        loop {
-            let p = Self::random_with_range(-1.0..1.0);
-            if p.length_squared() < 1.0 {
-                return p;
-            }
-        }
+        // the volume inside radius r grows as r³, so the cube root spreads points out evenly
+        let radius = rng::thread_rng().gen::<f64>().cbrt();
+        Self::random_unit_vector() * radius
     }
 
     pub fn random_unit_vector() -> Self {
-        Self::random_in_unit_sphere().normalize()
+        // by Archimedes' hat-box theorem, the height of a uniform point on the sphere is uniform
+        let mut rng = rng::thread_rng();
+        let z: f64 = rng.gen_range(-1.0..1.0);
+        let phi = rng.gen_range(0.0..std::f64::consts::TAU);
+        let r = (1.0 - z * z).sqrt();
+        Self {
+            x: r * phi.cos(),
+            y: r * phi.sin(),
+            z,
+        }
     }
 
     pub fn random_in_hemisphere(normal: &Vector) -> Self {
@@ -96,17 +102,14 @@ impl Vector {
     }
 
     pub fn random_in_unit_disk() -> Self {
-        // TODO: same as for random_in_unit_sphere
-        let mut rng = rand::thread_rng();
-        loop {
-            let p = Self {
-                x: rng.gen_range(-1.0..1.0),
-                y: rng.gen_range(-1.0..1.0),
-                z: 0.0,
-            };
-            if p.length_squared() < 1.0 {
-                return p;
-            }
+        // the area inside radius r grows as r², so the square root spreads points out evenly
+        let mut rng = rng::thread_rng();
+        let r = rng.gen::<f64>().sqrt();
+        let theta = rng.gen_range(0.0..std::f64::consts::TAU);
+        Self {
+            x: r * theta.cos(),
+            y: r * theta.sin(),
+            z: 0.0,
         }
     }
 
@@ -114,11 +117,17 @@ impl Vector {
         self - (2.0 * self.dot(normal) * *normal)
     }
 
-    pub fn refract(self, normal: &Vector, etai_over_etat: f64) -> Self {
+    /// Refracts a unit vector through a surface with the given normal, or returns `None` if the
+    /// angle is past the critical angle and the ray is totally internally reflected
+    pub fn refract(self, normal: &Vector, etai_over_etat: f64) -> Option<Self> {
         let cos_theta = (-self).dot(normal).min(1.0);
         let r_out_perp = etai_over_etat * (self + cos_theta * *normal);
-        let r_out_parallel = -(1.0 - r_out_perp.length_squared()).abs().sqrt() * *normal;
-        r_out_perp + r_out_parallel
+        let parallel_squared = 1.0 - r_out_perp.length_squared();
+        if parallel_squared < 0.0 {
+            return None;
+        }
+        let r_out_parallel = -parallel_squared.sqrt() * *normal;
+        Some(r_out_perp + r_out_parallel)
     }
 
     pub fn is_near_zero(&self) -> bool {
@@ -127,6 +136,49 @@ impl Vector {
     }
 }
 
+#[cfg_attr(feature = "simd", allow(dead_code))]
+mod scalar {
+    use super::Vector;
+
+    pub fn dot(a: &Vector, b: &Vector) -> f64 {
+        a.x * b.x + a.y * b.y + a.z * b.z
+    }
+
+    pub fn cross(a: &Vector, b: &Vector) -> Vector {
+        Vector {
+            x: a.y * b.z - a.z * b.y,
+            y: a.z * b.x - a.x * b.z,
+            z: a.x * b.y - a.y * b.x,
+        }
+    }
+}
+
+/// The components are packed into the first three lanes of an `f64x4`, with the last lane zero
+#[cfg(feature = "simd")]
+mod simd {
+    use super::Vector;
+    use std::simd::num::SimdFloat;
+    use std::simd::{f64x4, simd_swizzle};
+
+    fn pack(v: &Vector) -> f64x4 {
+        f64x4::from_array([v.x, v.y, v.z, 0.0])
+    }
+
+    pub fn dot(a: &Vector, b: &Vector) -> f64 {
+        (pack(a) * pack(b)).reduce_sum()
+    }
+
+    pub fn cross(a: &Vector, b: &Vector) -> Vector {
+        let (a, b) = (pack(a), pack(b));
+        let a_yzx = simd_swizzle!(a, [1, 2, 0, 3]);
+        let a_zxy = simd_swizzle!(a, [2, 0, 1, 3]);
+        let b_yzx = simd_swizzle!(b, [1, 2, 0, 3]);
+        let b_zxy = simd_swizzle!(b, [2, 0, 1, 3]);
+        let [x, y, z, _] = (a_yzx * b_zxy - a_zxy * b_yzx).to_array();
+        Vector { x, y, z }
+    }
+}
+
 impl std::ops::Add for Vector {
     type Output = Self;
 
@@ -219,6 +271,25 @@ mod tests {
         assert_eq!(a.cross(&b), Vector::new(-3.0, 6.0, -3.0));
     }
 
+    #[cfg(feature = "simd")]
+    #[test]
+    fn test_simd_matches_scalar() {
+        for _ in 0..1000 {
+            let a = Vector::random_with_range(-100.0..100.0);
+            let b = Vector::random_with_range(-100.0..100.0);
+
+            let (simd_dot, scalar_dot) = (simd::dot(&a, &b), scalar::dot(&a, &b));
+            assert!(
+                (simd_dot - scalar_dot).abs() <= 1e-9 * scalar_dot.abs().max(1.0),
+                "{} != {}",
+                simd_dot,
+                scalar_dot
+            );
+            let difference = simd::cross(&a, &b) - scalar::cross(&a, &b);
+            assert!(difference.length() <= 1e-9, "{:?}", difference);
+        }
+    }
+
     #[test]
     fn test_length() {
         let a = Vector::new(1.0, 2.0, 3.0);
@@ -294,6 +365,53 @@ mod tests {
         }
     }
 
+    /// Checks that many samples stay within the unit ball and average out to the origin, with the
+    /// mean squared length of a uniform distribution
+    fn assert_uniform(sample: fn() -> Vector, expected_length_squared: f64) {
+        const SAMPLES: usize = 100_000;
+        let mut sum = Vector::ZERO;
+        let mut sum_length_squared = 0.0;
+        for _ in 0..SAMPLES {
+            let a = sample();
+            assert!(a.length() <= 1.0 + 1e-12, "{:?}", a);
+            sum = sum + a;
+            sum_length_squared += a.length_squared();
+        }
+        let mean = sum / SAMPLES as f64;
+        let mean_length_squared = sum_length_squared / SAMPLES as f64;
+        assert!(mean.length() < 0.01, "mean: {:?}", mean);
+        assert!(
+            (mean_length_squared - expected_length_squared).abs() < 0.01,
+            "mean length squared: {}",
+            mean_length_squared
+        );
+    }
+
+    #[test]
+    fn test_random_distributions_are_uniform() {
+        rng::with_seed(7, || {
+            assert_uniform(Vector::random_unit_vector, 1.0);
+            // a uniform ball has E[r²] = 3/5, a uniform disk 1/2
+            assert_uniform(Vector::random_in_unit_sphere, 0.6);
+            assert_uniform(Vector::random_in_unit_disk, 0.5);
+
+            // equal amounts of the sphere in every octant
+            let mut octants = [0; 8];
+            for _ in 0..80_000 {
+                let a = Vector::random_unit_vector();
+                let octant = [a.x, a.y, a.z]
+                    .iter()
+                    .enumerate()
+                    .map(|(axis, value)| ((*value > 0.0) as usize) << axis)
+                    .sum::<usize>();
+                octants[octant] += 1;
+            }
+            for count in octants {
+                assert!((count as f64 - 10_000.0).abs() < 500.0, "{:?}", octants);
+            }
+        });
+    }
+
     #[test]
     fn test_reflect() {
         let v = Vector::new(1.0, -1.0, 0.0);
@@ -301,6 +419,26 @@ mod tests {
         assert_eq!(v.reflect(&n), Vector::new(1.0, 1.0, 0.0));
     }
 
+    #[test]
+    fn test_refract() {
+        // 45 degrees from air into glass, so sin(theta_t) = sin(45) / 1.5
+        let v = Vector::new(1.0, -1.0, 0.0).normalize();
+        let n = Vector::new(0.0, 1.0, 0.0);
+        let refracted = v.refract(&n, 1.0 / 1.5).unwrap();
+
+        let sin_t = std::f64::consts::FRAC_1_SQRT_2 / 1.5;
+        let expected = Vector::new(sin_t, -(1.0 - sin_t * sin_t).sqrt(), 0.0);
+        assert!((refracted - expected).is_near_zero(), "{:?}", refracted);
+    }
+
+    #[test]
+    fn test_refract_total_internal_reflection() {
+        // 60 degrees from glass into air is past the ~41.8 degree critical angle
+        let v = Vector::new(3.0_f64.sqrt() / 2.0, -0.5, 0.0);
+        let n = Vector::new(0.0, 1.0, 0.0);
+        assert_eq!(v.refract(&n, 1.5), None);
+    }
+
     #[test]
     fn test_is_near_zero() {
         let a = Vector::new(1e-9, 1e-9, 1e-9);
@@ -1,11 +1,21 @@
 use crate::color::Color;
-use crate::material::{Dielectric, Lambertian, Light, Material, Metal, Simple};
-use crate::object::{build_cuboid, Collection, Object, Quad, Sphere};
+use crate::environment::EnvironmentSampler;
+use crate::material::{Dielectric, Lambertian, Light, Material, Metal, NormalMapped, Simple};
+use crate::mesh::Mesh;
+use crate::object::{
+    build_cuboid, Collection, Collision, ConstantMedium, Disk, Hit, MemoryEstimate, Object, Plane,
+    Quad, Sphere,
+};
+use crate::quaternion::Quaternion;
+use crate::ray::Ray;
+use crate::rng;
 use crate::settings::CameraSettings;
-use crate::texture::{Image, Noise, Texture};
+use crate::texture::{Checker, ColorAt, Image, Marble, Noise, Solid, Texture};
 use crate::vector::{Point, Vector};
+use log::warn;
 use serde::{Deserialize, Serialize};
-use crate::quaternion::Quaternion;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 #[derive(
 Debug, Default, Clone, PartialEq, Deserialize, Serialize, strum_macros::Display, clap::ValueEnum,
@@ -25,28 +35,369 @@ pub enum Scene {
     RedAndBlue,
     #[strum(to_string = "Many Spheres")]
     ManySpheres,
+    #[strum(to_string = "Bouncing Spheres")]
+    BouncingSpheres,
     #[strum(to_string = "Earth")]
     Earth,
+    #[strum(to_string = "Teapot")]
+    Teapot,
     #[strum(to_string = "Two Perlin Spheres")]
     TwoPerlinSpheres,
+    /// The two Perlin spheres in marble, with the turbulence bending stripes instead of shading
+    #[strum(to_string = "Marble Spheres")]
+    MarbleSpheres,
     #[strum(to_string = "Quads")]
     Quads,
     #[strum(to_string = "Simple Light")]
     SimpleLight,
+    #[strum(to_string = "Disk Light")]
+    DiskLight,
+    /// Mirror, glass, and diffuse spheres lit only by an image of the sky
+    #[strum(to_string = "Skybox")]
+    Skybox,
+    /// Green glass spheres of different sizes, deeper in color the more glass light goes through
+    #[strum(to_string = "Colored Glass")]
+    ColoredGlass,
+    /// A plain sphere next to one given bumps by a normal map
+    #[strum(to_string = "Normal Map")]
+    NormalMap,
+    /// A dark room lit only by a glowing picture of the earth on the back wall
+    #[strum(to_string = "Glowing Picture")]
+    GlowingPicture,
+    /// Spheres under a low sun that casts sharp shadows across the ground
+    #[strum(to_string = "Sunlit")]
+    Sunlit,
     #[strum(to_string = "Cornell Box (Empty)")]
     CornellBoxEmpty,
     #[default]
     #[strum(to_string = "Cornell Box (Two boxes)")]
     CornellBoxTwoBoxes,
+    #[strum(to_string = "Cornell Box (Mirror and glass)")]
+    CornellBoxMirrorGlass,
+    #[strum(to_string = "Cornell Box (Smoke)")]
+    CornellBoxSmoke,
+    /// The Cornell box with the ceiling light swapped for a spotlight shining past a sphere
+    #[strum(to_string = "Cornell Box (Spotlight)")]
+    CornellBoxSpotlight,
+    /// No objects, only a gradient background, for measuring the cost of everything but geometry
+    #[strum(to_string = "Empty")]
+    Empty,
 }
 
 pub struct World {
     pub object: Object,
-    pub background: Color,
+    pub background: Background,
+    /// Lights without a shape, which only reach the scene through shadow rays
+    pub lights: Lights,
+    /// Copies of small, bright objects in the scene, like the lights, that diffuse bounces
+    /// send rays towards on purpose instead of hoping to find them by chance
+    pub important: Vec<Object>,
+}
+
+/// Lights sampled directly at every diffuse bounce, on top of whatever light the paths find
+#[derive(Debug, Clone, Default)]
+pub struct Lights {
+    pub sun: Option<DirectionalLight>,
+    pub spotlights: Vec<Spotlight>,
+}
+
+impl Lights {
+    /// Light from all the lights reflected by a diffuse surface at `hit`, before the surface's
+    /// attenuation
+    pub fn direct_light(&self, obj: &Object, hit: &Collision) -> Color {
+        let sun = match &self.sun {
+            Some(sun) => sun.direct_light(obj, hit),
+            None => Color::BLACK,
+        };
+        self.spotlights.iter().fold(sun, |total, spotlight| {
+            total + spotlight.direct_light(obj, hit)
+        })
+    }
+}
+
+/// A light infinitely far away, like the sun, shining evenly along one direction. It has no
+/// shape for rays to hit, so it only lights the scene through shadow rays sent towards it from
+/// diffuse surfaces.
+#[derive(Debug, Clone)]
+pub struct DirectionalLight {
+    /// The way the light travels, from the light into the scene
+    pub direction: Vector,
+    /// Light falling on a surface facing straight into it
+    pub radiance: Color,
+}
+
+impl DirectionalLight {
+    /// Light from the sun reflected by a diffuse surface at `hit`, before the surface's
+    /// attenuation. Zero when the surface faces away from the sun or something is in the way.
+    pub fn direct_light(&self, obj: &Object, hit: &Collision) -> Color {
+        let towards_light = -self.direction.normalize();
+        let cos_theta = hit.normal.normalize().dot(&towards_light);
+        if cos_theta <= 0.0 {
+            return Color::BLACK;
+        }
+
+        let shadow_ray = Ray::new(hit.point, towards_light);
+        if obj.hit(&shadow_ray, 0.001..f64::INFINITY).is_some() {
+            return Color::BLACK;
+        }
+        // a Lambertian surface reflects cos θ / π of the light per unit solid angle
+        self.radiance * (cos_theta / std::f64::consts::PI)
+    }
+}
+
+/// A point light shining a cone of light, like a stage light. Full brightness inside the inner
+/// cone fades smoothly to nothing at the outer cone.
+#[derive(Debug, Clone)]
+pub struct Spotlight {
+    pub position: Point,
+    /// The axis of the cone, from the light into the scene
+    pub direction: Vector,
+    /// Half-angle of the fully lit cone, in degrees
+    pub inner_angle: f64,
+    /// Half-angle past which nothing is lit, in degrees
+    pub outer_angle: f64,
+    /// Light per unit solid angle along the axis, falling off with the square of the distance
+    pub intensity: Color,
+}
+
+impl Spotlight {
+    /// How much of the light reaches `point` given where it lies in the cone, from 1.0 inside
+    /// the inner cone to 0.0 outside the outer cone
+    pub fn falloff(&self, point: &Point) -> f64 {
+        let cos_angle = self
+            .direction
+            .normalize()
+            .dot(&(*point - self.position).normalize());
+        let cos_inner = self.inner_angle.to_radians().cos();
+        let cos_outer = self.outer_angle.to_radians().cos();
+        if cos_angle <= cos_outer {
+            return 0.0;
+        }
+        if cos_angle >= cos_inner {
+            return 1.0;
+        }
+
+        let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    /// Light from the spotlight reflected by a diffuse surface at `hit`, before the surface's
+    /// attenuation. Zero outside the cone, facing away, or when something is in the way.
+    pub fn direct_light(&self, obj: &Object, hit: &Collision) -> Color {
+        let falloff = self.falloff(&hit.point);
+        if falloff <= 0.0 {
+            return Color::BLACK;
+        }
+
+        let towards_light = self.position - hit.point;
+        let distance_squared = towards_light.length_squared();
+        let cos_theta = hit.normal.normalize().dot(&towards_light.normalize());
+        if cos_theta <= 0.0 {
+            return Color::BLACK;
+        }
+
+        // the light is at t = 1, anything hit before it casts a shadow
+        let shadow_ray = Ray::new(hit.point, towards_light);
+        if obj.hit(&shadow_ray, 0.001..1.0 - 1e-6).is_some() {
+            return Color::BLACK;
+        }
+        self.intensity * (falloff * cos_theta / (distance_squared * std::f64::consts::PI))
+    }
+}
+
+/// What a ray sees when it doesn't hit anything
+#[derive(Debug, Clone)]
+pub enum Background {
+    Solid(Color),
+    /// Blends from `bottom` straight down to `top` straight up, evenly by elevation angle
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+    /// An equirectangular image wrapped around the scene, with +y at the top edge. `sampler`
+    /// lets diffuse bounces aim at its bright parts; `Background::environment` builds it.
+    Environment {
+        image: Image,
+        intensity: f64,
+        sampler: Arc<EnvironmentSampler>,
+    },
+}
+
+impl Background {
+    pub fn environment(image: Image, intensity: f64) -> Background {
+        let sampler = Arc::new(EnvironmentSampler::for_image(&image));
+        Background::Environment {
+            image,
+            intensity,
+            sampler,
+        }
+    }
+
+    pub fn color(&self, direction: &Vector) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                // blending by the raw y component would bunch the gradient up near the horizon
+                let elevation = direction.normalize().y.clamp(-1.0, 1.0).asin();
+                let t = (elevation / std::f64::consts::PI + 0.5).clamp(0.0, 1.0);
+                (1.0 - t) * *bottom + t * *top
+            }
+            Background::Environment {
+                image, intensity, ..
+            } => {
+                let (u, v) = equirectangular_uv(&direction.normalize());
+                image.color_at(u, v, direction) * *intensity
+            }
+        }
+    }
+
+    /// The same background, with its brightness multiplied by `intensity`
+    pub fn scaled(&self, intensity: f64) -> Background {
+        match self {
+            Background::Solid(color) => Background::Solid(*color * intensity),
+            Background::Gradient { bottom, top } => Background::Gradient {
+                bottom: *bottom * intensity,
+                top: *top * intensity,
+            },
+            Background::Environment {
+                image,
+                intensity: scale,
+                sampler,
+            } => Background::Environment {
+                image: image.clone(),
+                intensity: scale * intensity,
+                sampler: sampler.clone(),
+            },
+        }
+    }
+
+    /// Distribution of directions towards the bright parts of an environment image
+    pub fn sampler(&self) -> Option<&EnvironmentSampler> {
+        match self {
+            Background::Environment { sampler, .. } => Some(sampler),
+            _ => None,
+        }
+    }
+}
+
+/// Texture coordinates of a unit direction on an equirectangular image: `u` goes once around the
+/// horizon starting from +x towards +z, and `v` runs from 0 straight down to 1 straight up
+fn equirectangular_uv(direction: &Vector) -> (f64, f64) {
+    let phi = direction
+        .z
+        .atan2(direction.x)
+        .rem_euclid(2.0 * std::f64::consts::PI);
+    let theta = direction.y.clamp(-1.0, 1.0).acos();
+    (
+        phi / (2.0 * std::f64::consts::PI),
+        1.0 - theta / std::f64::consts::PI,
+    )
+}
+
+impl World {
+    /// Approximate number of bytes used by the scene's geometry and textures
+    pub fn memory_estimate(&self) -> usize {
+        std::mem::size_of::<Self>() - std::mem::size_of::<Object>() + self.object.memory_estimate()
+    }
+
+    /// Materials of every shape in the scene, depth first. A shape's position in this list is its
+    /// index for `set_material` and `pick`.
+    pub fn materials(&self) -> Vec<&Material> {
+        let mut materials = vec![];
+        let mut stack = vec![&self.object];
+        while let Some(object) = stack.pop() {
+            match object.material() {
+                Some(material) => materials.push(material),
+                // reversed, so the first child comes off the stack first
+                None => stack.extend(object.children().into_iter().rev()),
+            }
+        }
+        materials
+    }
+
+    /// Swaps the material of the shape at `index` for `material`, returning the old one, or
+    /// `None` if there are fewer shapes than that
+    pub fn set_material(&mut self, index: usize, material: Material) -> Option<Material> {
+        let mut remaining = index;
+        nth_material_mut(&mut self.object, &mut remaining)
+            .map(|slot| std::mem::replace(slot, material))
+    }
+
+    /// Index of the shape the ray hits first
+    pub fn pick(&self, ray: &Ray) -> Option<usize> {
+        let hit = self.object.hit(ray, 0.001..f64::INFINITY)?;
+        // every shape owns its material, so the hit's material identifies the shape
+        self.materials()
+            .into_iter()
+            .position(|material| std::ptr::eq(material, hit.material))
+    }
+}
+
+fn nth_material_mut<'a>(object: &'a mut Object, remaining: &mut usize) -> Option<&'a mut Material> {
+    if object.material().is_some() {
+        if *remaining == 0 {
+            return object.material_mut();
+        }
+        *remaining -= 1;
+        return None;
+    }
+    object
+        .children_mut()
+        .into_iter()
+        .find_map(|child| nth_material_mut(child, remaining))
 }
 
+/// Keeps the most recently built world around, so renders that only change the camera or sampling
+/// settings don't have to build the scene again
+#[derive(Default)]
+pub struct WorldCache {
+    entry: Mutex<Option<(Scene, Arc<World>)>>,
+    builds: AtomicUsize,
+}
+
+impl WorldCache {
+    pub fn get(&self, scene: &Scene) -> Arc<World> {
+        let mut entry = self.entry.lock().unwrap();
+        match entry.as_ref() {
+            Some((cached_scene, world)) if cached_scene == scene => world.clone(),
+            _ => {
+                let world = Arc::new(create_world(scene));
+                self.builds.fetch_add(1, Ordering::Relaxed);
+                *entry = Some((scene.clone(), world.clone()));
+                world
+            }
+        }
+    }
+
+    /// Overrides the material of one shape in the cached world for `scene`, without building it
+    /// again. Fails if a different scene is cached, or a render still holds on to the world.
+    pub fn set_material(&self, scene: &Scene, index: usize, material: Material) -> bool {
+        let mut entry = self.entry.lock().unwrap();
+        match entry.as_mut() {
+            Some((cached_scene, world)) if cached_scene == scene => Arc::get_mut(world)
+                .and_then(|world| world.set_material(index, material))
+                .is_some(),
+            _ => false,
+        }
+    }
+
+    /// Number of times a world has been built, rather than served from the cache
+    pub fn builds(&self) -> usize {
+        self.builds.load(Ordering::Relaxed)
+    }
+}
+
+/// Seeds the random layouts and textures of scenes like Many Spheres, so every build of a scene
+/// is the same
+const SCENE_SEED: u64 = 0x5eed;
+
 #[cfg(not(tarpaulin_include))]
 pub fn create_world(scene: &Scene) -> World {
+    rng::with_seed(SCENE_SEED, || build_world(scene))
+}
+
+#[cfg(not(tarpaulin_include))]
+fn build_world(scene: &Scene) -> World {
     match scene {
         Scene::OneSphere => create_scene_one_sphere(),
         Scene::MetalSpheres => create_scene_metal_spheres(),
@@ -55,12 +406,25 @@ pub fn create_world(scene: &Scene) -> World {
         Scene::HollowGlassSphere => create_scene_hollow_glass_sphere(),
         Scene::RedAndBlue => create_scene_red_and_blue(),
         Scene::ManySpheres => create_scene_many_spheres(),
+        Scene::BouncingSpheres => create_scene_bouncing_spheres(),
         Scene::Earth => create_scene_earth(),
+        Scene::Teapot => create_scene_teapot(),
         Scene::TwoPerlinSpheres => create_scene_two_perlin_spheres(),
+        Scene::MarbleSpheres => create_scene_marble_spheres(),
         Scene::Quads => create_scene_quads(),
         Scene::SimpleLight => create_scene_simple_light(),
+        Scene::DiskLight => create_scene_disk_light(),
+        Scene::Skybox => create_scene_skybox(),
+        Scene::ColoredGlass => create_scene_colored_glass(),
+        Scene::NormalMap => create_scene_normal_map(),
+        Scene::GlowingPicture => create_scene_glowing_picture(),
+        Scene::Sunlit => create_scene_sunlit(),
         Scene::CornellBoxEmpty => create_scene_cornell_box_empty(),
         Scene::CornellBoxTwoBoxes => create_scene_cornell_box_two_boxes(),
+        Scene::CornellBoxMirrorGlass => create_scene_cornell_box_mirror_glass(),
+        Scene::CornellBoxSmoke => create_scene_cornell_box_smoke(),
+        Scene::CornellBoxSpotlight => create_scene_cornell_box_spotlight(),
+        Scene::Empty => create_scene_empty(),
     }
 }
 
@@ -70,68 +434,131 @@ pub fn get_scene_camera(scene: &Scene) -> CameraSettings {
         Scene::OneSphere => CameraSettings {
             camera_position: Point::new(0.0, 0.0, 0.0),
             focus_point: Point::new(0.0, 0.0, -1.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 90.0,
         },
         Scene::MetalSpheres => CameraSettings {
             camera_position: Point::new(0.0, 0.0, 0.0),
             focus_point: Point::new(0.0, 0.0, -1.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 90.0,
         },
         Scene::GlassSpheres => CameraSettings {
             camera_position: Point::new(0.0, 0.0, 0.0),
             focus_point: Point::new(0.0, 0.0, -1.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 90.0,
         },
         Scene::ThreeSpheres => CameraSettings {
             camera_position: Point::new(0.0, 0.0, 0.0),
             focus_point: Point::new(0.0, 0.0, -1.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 90.0,
         },
         Scene::HollowGlassSphere => CameraSettings {
             camera_position: Point::new(0.0, 0.0, 0.0),
             focus_point: Point::new(0.0, 0.0, -1.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 90.0,
         },
         Scene::RedAndBlue => CameraSettings {
             camera_position: Point::new(0.0, 0.0, 0.0),
             focus_point: Point::new(0.0, 0.0, -1.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 90.0,
         },
-        Scene::ManySpheres => CameraSettings {
+        Scene::ManySpheres | Scene::BouncingSpheres => CameraSettings {
             camera_position: Point::new(13.0, 2.0, 3.0),
             focus_point: Point::new(0.0, 0.0, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 20.0,
         },
+        Scene::Teapot => CameraSettings {
+            camera_position: Point::new(0.0, 3.5, 9.0),
+            focus_point: Point::new(0.0, 1.0, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
+            field_of_view: 35.0,
+        },
         Scene::Earth => CameraSettings {
             camera_position: Point::new(0.0, 0.0, 12.0),
             focus_point: Point::new(0.0, 0.0, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 20.0,
         },
-        Scene::TwoPerlinSpheres => CameraSettings {
+        Scene::TwoPerlinSpheres | Scene::MarbleSpheres => CameraSettings {
             camera_position: Point::new(13.0, 2.0, 3.0),
             focus_point: Point::new(0.0, 0.0, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 20.0,
         },
         Scene::Quads => CameraSettings {
             camera_position: Point::new(0.0, 0.0, 9.0),
             focus_point: Point::new(0.0, 0.0, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 80.0,
         },
-        Scene::SimpleLight => CameraSettings {
+        Scene::SimpleLight | Scene::DiskLight => CameraSettings {
             camera_position: Point::new(26.0, 3.0, 6.0),
             focus_point: Point::new(0.0, 2.0, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 20.0,
         },
+        Scene::Skybox => CameraSettings {
+            camera_position: Point::new(0.0, 1.5, 8.0),
+            focus_point: Point::new(0.0, 1.0, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
+            field_of_view: 35.0,
+        },
+        Scene::ColoredGlass => CameraSettings {
+            camera_position: Point::new(0.0, 2.0, 9.0),
+            focus_point: Point::new(0.0, 0.8, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
+            field_of_view: 30.0,
+        },
+        Scene::NormalMap => CameraSettings {
+            camera_position: Point::new(0.0, 1.5, 8.0),
+            focus_point: Point::new(0.0, 1.0, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
+            field_of_view: 30.0,
+        },
+        Scene::GlowingPicture => CameraSettings {
+            camera_position: Point::new(0.0, 1.5, 7.0),
+            focus_point: Point::new(0.0, 1.5, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
+            field_of_view: 40.0,
+        },
+        Scene::Sunlit => CameraSettings {
+            camera_position: Point::new(0.0, 2.0, 9.0),
+            focus_point: Point::new(0.0, 0.8, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
+            field_of_view: 35.0,
+        },
         Scene::CornellBoxEmpty => CameraSettings {
             camera_position: Point::new(278.0, 278.0, -800.0),
             focus_point: Point::new(278.0, 278.0, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 40.0,
         },
         Scene::CornellBoxTwoBoxes => CameraSettings {
             camera_position: Point::new(278.0, 278.0, -800.0),
             focus_point: Point::new(278.0, 278.0, 0.0),
+            up: Vector::new(0.0, 1.0, 0.0),
             field_of_view: 40.0,
         },
+        Scene::CornellBoxMirrorGlass | Scene::CornellBoxSmoke | Scene::CornellBoxSpotlight => {
+            CameraSettings {
+                camera_position: Point::new(278.0, 278.0, -800.0),
+                focus_point: Point::new(278.0, 278.0, 0.0),
+                up: Vector::new(0.0, 1.0, 0.0),
+                field_of_view: 40.0,
+            }
+        }
+        Scene::Empty => CameraSettings {
+            camera_position: Point::new(0.0, 0.0, 0.0),
+            focus_point: Point::new(0.0, 0.0, -1.0),
+            up: Vector::new(0.0, 1.0, 0.0),
+            field_of_view: 90.0,
+        },
     }
 }
 
@@ -139,24 +566,29 @@ pub fn get_scene_camera(scene: &Scene) -> CameraSettings {
 fn create_scene_one_sphere() -> World {
     let object = Object::Collection(Collection {
         objects: vec![
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, 0.0, -1.0),
-                radius: 0.5,
-                material: Material::Lambertian(Lambertian {
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, 0.0, -1.0),
+                0.5,
+                Material::Lambertian(Lambertian {
                     albedo: Color::new(0.1, 0.2, 0.5),
                 }),
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, -100.5, -1.0),
-                radius: 100.0,
-                material: Material::Lambertian(Lambertian {
+            )),
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, -100.5, -1.0),
+                100.0,
+                Material::Lambertian(Lambertian {
                     albedo: Color::new(0.1, 0.2, 0.5),
                 }),
-            }),
+            )),
         ],
     });
-    let background = Color::new(0.7, 0.8, 1.0);
-    World { object, background }
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -170,38 +602,37 @@ fn create_scene_metal_spheres() -> World {
     let material_left = Material::Metal(Metal {
         albedo: Color::new(0.8, 0.8, 0.8),
         fuzz: 0.3,
+        anisotropy: 0.0,
     });
     let material_right = Material::Metal(Metal {
         albedo: Color::new(0.8, 0.6, 0.2),
         fuzz: 1.0,
+        anisotropy: 0.0,
     });
 
     let object = Object::Collection(Collection {
         objects: vec![
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, -100.5, -1.0),
-                radius: 100.0,
-                material: material_ground,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_center,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(-1.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_left,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(1.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_right,
-            }),
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, -100.5, -1.0),
+                100.0,
+                material_ground,
+            )),
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, 0.0, -1.0),
+                0.5,
+                material_center,
+            )),
+            Object::Sphere(Sphere::new(Point::new(-1.0, 0.0, -1.0), 0.5, material_left)),
+            Object::Sphere(Sphere::new(Point::new(1.0, 0.0, -1.0), 0.5, material_right)),
         ],
     });
-    let background = Color::new(0.7, 0.8, 1.0);
-    World { object, background }
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -211,41 +642,43 @@ fn create_scene_glass_spheres() -> World {
     });
     let material_center = Material::Dielectric(Dielectric {
         refraction_index: 1.5,
+        absorption: None,
+        fuzz: 0.0,
     });
     let material_left = Material::Dielectric(Dielectric {
         refraction_index: 1.5,
+        absorption: None,
+        fuzz: 0.0,
     });
     let material_right = Material::Metal(Metal {
         albedo: Color::new(0.8, 0.6, 0.2),
         fuzz: 1.0,
+        anisotropy: 0.0,
     });
 
     let object = Object::Collection(Collection {
         objects: vec![
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, -100.5, -1.0),
-                radius: 100.0,
-                material: material_ground,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_center,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(-1.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_left,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(1.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_right,
-            }),
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, -100.5, -1.0),
+                100.0,
+                material_ground,
+            )),
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, 0.0, -1.0),
+                0.5,
+                material_center,
+            )),
+            Object::Sphere(Sphere::new(Point::new(-1.0, 0.0, -1.0), 0.5, material_left)),
+            Object::Sphere(Sphere::new(Point::new(1.0, 0.0, -1.0), 0.5, material_right)),
         ],
     });
-    let background = Color::new(0.7, 0.8, 1.0);
-    World { object, background }
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -258,38 +691,38 @@ fn create_scene_three_spheres() -> World {
     });
     let material_left = Material::Dielectric(Dielectric {
         refraction_index: 1.5,
+        absorption: None,
+        fuzz: 0.0,
     });
     let material_right = Material::Metal(Metal {
         albedo: Color::new(0.8, 0.6, 0.2),
         fuzz: 0.0,
+        anisotropy: 0.0,
     });
 
     let object = Object::Collection(Collection {
         objects: vec![
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, -100.5, -1.0),
-                radius: 100.0,
-                material: material_ground,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_center,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(-1.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_left,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(1.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_right,
-            }),
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, -100.5, -1.0),
+                100.0,
+                material_ground,
+            )),
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, 0.0, -1.0),
+                0.5,
+                material_center,
+            )),
+            Object::Sphere(Sphere::new(Point::new(-1.0, 0.0, -1.0), 0.5, material_left)),
+            Object::Sphere(Sphere::new(Point::new(1.0, 0.0, -1.0), 0.5, material_right)),
         ],
     });
-    let background = Color::new(0.7, 0.8, 1.0);
-    World { object, background }
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -302,43 +735,47 @@ fn create_scene_hollow_glass_sphere() -> World {
     });
     let material_left = Material::Dielectric(Dielectric {
         refraction_index: 1.5,
+        absorption: None,
+        fuzz: 0.0,
     });
     let material_right = Material::Metal(Metal {
         albedo: Color::new(0.8, 0.6, 0.2),
         fuzz: 0.0,
+        anisotropy: 0.0,
     });
 
     let object = Object::Collection(Collection {
         objects: vec![
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, -100.5, -1.0),
-                radius: 100.0,
-                material: material_ground,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_center,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(-1.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_left.clone(),
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(-1.0, 0.0, -1.0),
-                radius: -0.4,
-                material: material_left,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(1.0, 0.0, -1.0),
-                radius: 0.5,
-                material: material_right,
-            }),
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, -100.5, -1.0),
+                100.0,
+                material_ground,
+            )),
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, 0.0, -1.0),
+                0.5,
+                material_center,
+            )),
+            Object::Sphere(Sphere::new(
+                Point::new(-1.0, 0.0, -1.0),
+                0.5,
+                material_left.clone(),
+            )),
+            Object::Sphere(Sphere::new(
+                Point::new(-1.0, 0.0, -1.0),
+                -0.4,
+                material_left,
+            )),
+            Object::Sphere(Sphere::new(Point::new(1.0, 0.0, -1.0), 0.5, material_right)),
         ],
     });
-    let background = Color::new(0.7, 0.8, 1.0);
-    World { object, background }
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -354,73 +791,98 @@ fn create_scene_red_and_blue() -> World {
 
     let object = Object::Collection(Collection {
         objects: vec![
-            Object::Sphere(Sphere {
-                center: Point::new(-r, 0.0, -1.0),
-                radius: r,
-                material: material_left,
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(r, 0.0, -1.0),
-                radius: r,
-                material: material_right,
-            }),
+            Object::Sphere(Sphere::new(Point::new(-r, 0.0, -1.0), r, material_left)),
+            Object::Sphere(Sphere::new(Point::new(r, 0.0, -1.0), r, material_right)),
         ],
     });
-    let background = Color::new(0.7, 0.8, 1.0);
-    World { object, background }
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
 fn create_scene_many_spheres() -> World {
-    let mut objects = vec![];
+    random_spheres(false)
+}
+
+/// Many Spheres with the small diffuse spheres bouncing upwards while the shutter is open
+#[cfg(not(tarpaulin_include))]
+fn create_scene_bouncing_spheres() -> World {
+    random_spheres(true)
+}
 
+#[cfg(not(tarpaulin_include))]
+fn random_spheres(bouncing: bool) -> World {
     let ground_material = Material::Lambertian(Lambertian {
         albedo: Color::new(0.5, 0.5, 0.5),
     });
-    objects.push(Object::Sphere(Sphere {
-        center: Point::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: ground_material,
-    }));
+    let ground = Object::Plane(Plane::new(
+        Point::new(0.0, 0.0, 0.0),
+        Vector::new(0.0, 1.0, 0.0),
+        ground_material,
+    ));
+    let objects = random_sphere_objects(bouncing);
+
+    // the ground is unbounded, so it stays out of the BVH
+    let object = Object::Collection(Collection {
+        objects: vec![ground, Object::Collection(Collection::with_bvh(objects))],
+    });
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
+}
+
+/// The small random spheres and three big ones of the Many Spheres scenes, without the ground
+fn random_sphere_objects(bouncing: bool) -> Vec<Object> {
+    let mut objects = vec![];
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = rand::random::<f64>();
+            let choose_mat = rng::random::<f64>();
             let center = Point::new(
-                a as f64 + 0.9 * rand::random::<f64>(),
+                a as f64 + 0.9 * rng::random::<f64>(),
                 0.2,
-                b as f64 + 0.9 * rand::random::<f64>(),
+                b as f64 + 0.9 * rng::random::<f64>(),
             );
             if (center - Point::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 if choose_mat < 0.65 {
                     // diffuse
                     let albedo = Color::random() * Color::random();
                     let sphere_material = Material::Lambertian(Lambertian { albedo });
-                    objects.push(Object::Sphere(Sphere {
-                        center,
-                        radius: 0.2,
-                        material: sphere_material,
-                    }));
+                    let sphere = Sphere::new(center, 0.2, sphere_material);
+                    let sphere = if bouncing {
+                        let bounce = Vector::new(0.0, rng::random::<f64>() * 0.5, 0.0);
+                        sphere.with_motion(center + bounce)
+                    } else {
+                        sphere
+                    };
+                    objects.push(Object::Sphere(sphere));
                 } else if choose_mat < 0.80 {
                     // metal
                     let albedo = Color::random_with_range(0.5..1.0);
-                    let fuzz = rand::random::<f64>() * 0.5;
-                    let sphere_material = Material::Metal(Metal { albedo, fuzz });
-                    objects.push(Object::Sphere(Sphere {
-                        center,
-                        radius: 0.2,
-                        material: sphere_material,
-                    }));
+                    let fuzz = rng::random::<f64>() * 0.5;
+                    let sphere_material = Material::Metal(Metal {
+                        albedo,
+                        fuzz,
+                        anisotropy: 0.0,
+                    });
+                    objects.push(Object::Sphere(Sphere::new(center, 0.2, sphere_material)));
                 } else {
                     // glass
                     let sphere_material = Material::Dielectric(Dielectric {
                         refraction_index: 1.5,
+                        absorption: None,
+                        fuzz: 0.0,
                     });
-                    objects.push(Object::Sphere(Sphere {
-                        center,
-                        radius: 0.2,
-                        material: sphere_material,
-                    }));
+                    objects.push(Object::Sphere(Sphere::new(center, 0.2, sphere_material)));
                 }
             }
         }
@@ -428,35 +890,36 @@ fn create_scene_many_spheres() -> World {
 
     let material_1 = Material::Dielectric(Dielectric {
         refraction_index: 1.5,
+        absorption: None,
+        fuzz: 0.0,
     });
-    objects.push(Object::Sphere(Sphere {
-        center: Point::new(0.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material_1,
-    }));
+    objects.push(Object::Sphere(Sphere::new(
+        Point::new(0.0, 1.0, 0.0),
+        1.0,
+        material_1,
+    )));
 
     let material_2 = Material::Lambertian(Lambertian {
         albedo: Color::new(0.4, 0.2, 0.1),
     });
-    objects.push(Object::Sphere(Sphere {
-        center: Point::new(-4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material_2,
-    }));
+    objects.push(Object::Sphere(Sphere::new(
+        Point::new(-4.0, 1.0, 0.0),
+        1.0,
+        material_2,
+    )));
 
     let material_3 = Material::Metal(Metal {
         albedo: Color::new(0.7, 0.6, 0.5),
         fuzz: 0.0,
+        anisotropy: 0.0,
     });
-    objects.push(Object::Sphere(Sphere {
-        center: Point::new(4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: material_3,
-    }));
+    objects.push(Object::Sphere(Sphere::new(
+        Point::new(4.0, 1.0, 0.0),
+        1.0,
+        material_3,
+    )));
 
-    let object = Object::Collection(Collection { objects });
-    let background = Color::new(0.7, 0.8, 1.0);
-    World { object, background }
+    objects
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -466,13 +929,50 @@ fn create_scene_earth() -> World {
         texture: earth_texture,
     });
 
-    let object = Object::Sphere(Sphere {
-        center: Point::new(0.0, 0.0, -12.0),
-        radius: 2.0,
-        material: earth_material,
+    let object = Object::Sphere(Sphere::new(
+        Point::new(0.0, 0.0, -12.0),
+        2.0,
+        earth_material,
+    ));
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_teapot() -> World {
+    let teapot_material = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.7, 0.15, 0.1),
+    });
+    let teapot = Mesh::load("res/teapot.obj".into(), teapot_material).unwrap_or_else(|e| {
+        warn!("Failed to load teapot mesh: {}", e);
+        Collection { objects: vec![] }
+    });
+
+    let ground_material = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.5, 0.5, 0.5),
     });
-    let background = Color::new(0.7, 0.8, 1.0);
-    World { object, background }
+    let objects = vec![
+        Object::Sphere(Sphere::new(
+            Point::new(0.0, -1000.0, 0.0),
+            1000.0,
+            ground_material,
+        )),
+        Object::Collection(teapot),
+    ];
+
+    let object = Object::Collection(Collection { objects });
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -484,20 +984,46 @@ fn create_scene_two_perlin_spheres() -> World {
 
     let object = Object::Collection(Collection {
         objects: vec![
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, -1000.0, 0.0),
-                radius: 1000.0,
-                material: perlin_material.clone(),
-            }),
-            Object::Sphere(Sphere {
-                center: Point::new(0.0, 2.0, 0.0),
-                radius: 2.0,
-                material: perlin_material,
-            }),
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, -1000.0, 0.0),
+                1000.0,
+                perlin_material.clone(),
+            )),
+            Object::Sphere(Sphere::new(Point::new(0.0, 2.0, 0.0), 2.0, perlin_material)),
+        ],
+    });
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_marble_spheres() -> World {
+    let marble_material = Material::Simple(Simple {
+        texture: Texture::Marble(Marble::new(4.0)),
+    });
+
+    let object = Object::Collection(Collection {
+        objects: vec![
+            Object::Sphere(Sphere::new(
+                Point::new(0.0, -1000.0, 0.0),
+                1000.0,
+                marble_material.clone(),
+            )),
+            Object::Sphere(Sphere::new(Point::new(0.0, 2.0, 0.0), 2.0, marble_material)),
         ],
     });
-    let background = Color::new(0.7, 0.8, 1.0);
-    World { object, background }
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -612,32 +1138,35 @@ fn create_scene_quads() -> World {
             )),
         ],
     });
-    let background = Color::new(0.7, 0.8, 1.0);
-    World { object, background }
+    let background = Background::Solid(Color::new(0.7, 0.8, 1.0));
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
 fn create_scene_simple_light() -> World {
     let mut objects = Vec::new();
     let perlin_texture = Texture::Noise(Noise::new(4.0));
-    objects.push(Object::Sphere(Sphere {
-        center: Point::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: Material::Simple(Simple {
+    objects.push(Object::Sphere(Sphere::new(
+        Point::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Material::Simple(Simple {
             texture: perlin_texture.clone(),
         }),
-    }));
-    objects.push(Object::Sphere(Sphere {
-        center: Point::new(0.0, 2.0, 0.0),
-        radius: 2.0,
-        material: Material::Simple(Simple {
+    )));
+    objects.push(Object::Sphere(Sphere::new(
+        Point::new(0.0, 2.0, 0.0),
+        2.0,
+        Material::Simple(Simple {
             texture: perlin_texture,
         }),
-    }));
+    )));
 
-    let light = Material::Light(Light {
-        color: Color::new(4.0, 4.0, 4.0),
-    });
+    let light = Material::Light(Light::new(Color::new(4.0, 4.0, 4.0)));
     objects.push(Object::Quad(Quad::new(
         Point {
             x: 3.0,
@@ -658,13 +1187,39 @@ fn create_scene_simple_light() -> World {
     )));
 
     let object = Object::Collection(Collection { objects });
-    let background = Color::new(0.0, 0.0, 0.0);
+    let background = Background::Solid(Color::new(0.0, 0.0, 0.0));
 
-    World { object, background }
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
-fn create_scene_cornell_box_empty() -> World {
+fn cornell_box_walls() -> Vec<Object> {
+    let mut objects = cornell_box_room();
+    objects.push(Object::Quad(cornell_box_light()));
+
+    objects
+}
+
+/// The light in the ceiling of the Cornell box
+#[cfg(not(tarpaulin_include))]
+fn cornell_box_light() -> Quad {
+    let light = Material::Light(Light::new(Color::new(15.0, 15.0, 15.0)));
+    Quad::new(
+        Point::new(343.0, 554.0, 332.0),
+        Vector::new(-130.0, 0.0, 0.0),
+        Vector::new(0.0, 0.0, -105.0),
+        light,
+    )
+}
+
+/// The walls, floor, and ceiling of the Cornell box, without the light in the ceiling
+#[cfg(not(tarpaulin_include))]
+fn cornell_box_room() -> Vec<Object> {
     let mut objects = Vec::new();
 
     let red = Material::Lambertian(Lambertian {
@@ -676,11 +1231,8 @@ fn create_scene_cornell_box_empty() -> World {
     let green = Material::Lambertian(Lambertian {
         albedo: Color::new(0.12, 0.45, 0.15),
     });
-    let light = Material::Light(Light {
-        color: Color::new(15.0, 15.0, 15.0),
-    });
 
-    objects.push(Object::Quad(Quad::new(
+    objects.push(Object::Quad(overlapping_wall(
         Point {
             x: 555.0,
             y: 0.0,
@@ -698,7 +1250,7 @@ fn create_scene_cornell_box_empty() -> World {
         },
         green,
     )));
-    objects.push(Object::Quad(Quad::new(
+    objects.push(Object::Quad(overlapping_wall(
         Point {
             x: 0.0,
             y: 0.0,
@@ -716,25 +1268,7 @@ fn create_scene_cornell_box_empty() -> World {
         },
         red,
     )));
-    objects.push(Object::Quad(Quad::new(
-        Point {
-            x: 343.0,
-            y: 554.0,
-            z: 332.0,
-        },
-        Vector {
-            x: -130.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        Vector {
-            x: 0.0,
-            y: 0.0,
-            z: -105.0,
-        },
-        light,
-    )));
-    objects.push(Object::Quad(Quad::new(
+    objects.push(Object::Quad(overlapping_wall(
         Point {
             x: 0.0,
             y: 0.0,
@@ -752,7 +1286,7 @@ fn create_scene_cornell_box_empty() -> World {
         },
         white.clone(),
     )));
-    objects.push(Object::Quad(Quad::new(
+    objects.push(Object::Quad(overlapping_wall(
         Point {
             x: 555.0,
             y: 555.0,
@@ -770,7 +1304,7 @@ fn create_scene_cornell_box_empty() -> World {
         },
         white.clone(),
     )));
-    objects.push(Object::Quad(Quad::new(
+    objects.push(Object::Quad(overlapping_wall(
         Point {
             x: 0.0,
             y: 0.0,
@@ -789,137 +1323,331 @@ fn create_scene_cornell_box_empty() -> World {
         white.clone(),
     )));
 
-    let object = Object::Collection(Collection { objects });
-    let background = Color::new(0.0, 0.0, 0.0);
+    objects
+}
+
+/// How far each Cornell box wall reaches past its edges, into the walls it meets
+const WALL_OVERLAP: f64 = 0.5;
 
-    World { object, background }
+/// A quad grown by `WALL_OVERLAP` on every side, so rounding can't open a gap along the seam
+/// where two walls meet and let the background leak through
+fn overlapping_wall(q: Point, u: Vector, v: Vector, material: Material) -> Quad {
+    let grow_u = u.normalize() * WALL_OVERLAP;
+    let grow_v = v.normalize() * WALL_OVERLAP;
+    Quad::new(
+        q - grow_u - grow_v,
+        u + grow_u * 2.0,
+        v + grow_v * 2.0,
+        material,
+    )
 }
 
 #[cfg(not(tarpaulin_include))]
-fn create_scene_cornell_box_two_boxes() -> World {
-    let mut objects = Vec::new();
+fn create_scene_disk_light() -> World {
+    let perlin_texture = Texture::Noise(Noise::new(4.0));
+    let light = Material::Light(Light::new(Color::new(4.0, 4.0, 4.0)));
 
-    let red = Material::Lambertian(Lambertian {
-        albedo: Color::new(0.65, 0.05, 0.05),
+    let objects = vec![
+        Object::Plane(Plane::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Material::Simple(Simple {
+                texture: perlin_texture.clone(),
+            }),
+        )),
+        Object::Sphere(Sphere::new(
+            Point::new(0.0, 2.0, 0.0),
+            2.0,
+            Material::Simple(Simple {
+                texture: perlin_texture,
+            }),
+        )),
+        // a round lamp overhead, and one standing behind the sphere
+        Object::Disk(Disk::new(
+            Point::new(0.0, 5.5, 0.0),
+            Vector::new(0.0, -1.0, 0.0),
+            1.5,
+            light.clone(),
+        )),
+        Object::Disk(Disk::new(
+            Point::new(4.0, 2.0, -3.0),
+            Vector::new(0.0, 0.0, 1.0),
+            1.0,
+            light,
+        )),
+    ];
+
+    let object = Object::Collection(Collection { objects });
+    let background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_skybox() -> World {
+    let mirror = Material::Metal(Metal {
+        albedo: Color::new(0.9, 0.9, 0.9),
+        fuzz: 0.0,
+        anisotropy: 0.0,
     });
-    let white = Material::Lambertian(Lambertian {
-        albedo: Color::new(0.73, 0.73, 0.73),
+    let glass = Material::Dielectric(Dielectric {
+        refraction_index: 1.5,
+        absorption: None,
+        fuzz: 0.0,
     });
-    let green = Material::Lambertian(Lambertian {
-        albedo: Color::new(0.12, 0.45, 0.15),
+    let diffuse = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.7, 0.3, 0.2),
     });
-    let light = Material::Light(Light {
-        color: Color::new(15.0, 15.0, 15.0),
+    let ground = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.5, 0.5, 0.5),
     });
 
-    objects.push(Object::Quad(Quad::new(
-        Point {
-            x: 555.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        Vector {
-            x: 0.0,
-            y: 555.0,
-            z: 0.0,
-        },
-        Vector {
-            x: 0.0,
-            y: 0.0,
-            z: 555.0,
-        },
-        green,
-    )));
-    objects.push(Object::Quad(Quad::new(
-        Point {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        Vector {
-            x: 0.0,
-            y: 555.0,
-            z: 0.0,
-        },
-        Vector {
-            x: 0.0,
-            y: 0.0,
-            z: 555.0,
-        },
-        red,
-    )));
-    objects.push(Object::Quad(Quad::new(
-        Point {
-            x: 343.0,
-            y: 554.0,
-            z: 332.0,
-        },
-        Vector {
-            x: -130.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        Vector {
-            x: 0.0,
-            y: 0.0,
-            z: -105.0,
-        },
-        light,
-    )));
-    objects.push(Object::Quad(Quad::new(
-        Point {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        Vector {
-            x: 555.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        Vector {
-            x: 0.0,
-            y: 0.0,
-            z: 555.0,
-        },
-        white.clone(),
-    )));
-    objects.push(Object::Quad(Quad::new(
-        Point {
-            x: 555.0,
-            y: 555.0,
-            z: 555.0,
-        },
-        Vector {
-            x: -555.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        Vector {
-            x: 0.0,
-            y: 0.0,
-            z: -555.0,
-        },
-        white.clone(),
-    )));
-    objects.push(Object::Quad(Quad::new(
-        Point {
-            x: 0.0,
-            y: 0.0,
-            z: 555.0,
-        },
-        Vector {
-            x: 555.0,
-            y: 0.0,
-            z: 0.0,
-        },
-        Vector {
-            x: 0.0,
-            y: 555.0,
-            z: 0.0,
+    let objects = vec![
+        Object::Plane(Plane::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            ground,
+        )),
+        Object::Sphere(Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, mirror)),
+        Object::Sphere(Sphere::new(Point::new(-2.2, 1.0, 0.0), 1.0, glass)),
+        Object::Sphere(Sphere::new(Point::new(2.2, 1.0, 0.0), 1.0, diffuse)),
+    ];
+
+    let object = Object::Collection(Collection { objects });
+    let background = match Image::load("res/sky.hdr".into()) {
+        Ok(image) => Background::environment(image, 1.0),
+        Err(e) => {
+            warn!("Failed to load sky image: {}", e);
+            Background::Gradient {
+                bottom: Color::WHITE,
+                top: Color::new(0.5, 0.7, 1.0),
+            }
+        }
+    };
+
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_colored_glass() -> World {
+    let ground = Material::Simple(Simple {
+        texture: Texture::Checker(Checker::new(
+            Color::new(0.2, 0.2, 0.2),
+            Color::new(0.9, 0.9, 0.9),
+            0.5,
+        )),
+    });
+    // absorbs red and blue much faster than green
+    let green_glass = Material::Dielectric(Dielectric {
+        refraction_index: 1.5,
+        absorption: Some(Color::new(1.2, 0.15, 1.2)),
+        fuzz: 0.0,
+    });
+
+    let mut objects = vec![Object::Plane(Plane::new(
+        Point::new(0.0, 0.0, 0.0),
+        Vector::new(0.0, 1.0, 0.0),
+        ground,
+    ))];
+    for (x, radius) in [(-3.0, 0.35), (-1.8, 0.65), (0.1, 1.0), (2.6, 1.35)] {
+        objects.push(Object::Sphere(Sphere::new(
+            Point::new(x, radius, 0.0),
+            radius,
+            green_glass.clone(),
+        )));
+    }
+
+    let object = Object::Collection(Collection { objects });
+    let background = Background::Gradient {
+        bottom: Color::WHITE,
+        top: Color::new(0.5, 0.7, 1.0),
+    };
+
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_normal_map() -> World {
+    let clay = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.8, 0.5, 0.3),
+    });
+    let bumpy = match Image::load_data("res/bumps.png".into()) {
+        Ok(image) => Material::NormalMapped(NormalMapped {
+            material: Box::new(clay.clone()),
+            normal_map: Texture::Image(image),
+        }),
+        Err(e) => {
+            warn!("Failed to load normal map: {}", e);
+            clay.clone()
+        }
+    };
+    let ground = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.5, 0.5, 0.5),
+    });
+    // off to the side, so the bumps cast their shading one way
+    let light = Material::Light(Light::new(Color::new(6.0, 6.0, 6.0)));
+
+    let objects = vec![
+        Object::Plane(Plane::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            ground,
+        )),
+        Object::Sphere(Sphere::new(Point::new(-1.2, 1.0, 0.0), 1.0, clay)),
+        Object::Sphere(Sphere::new(Point::new(1.2, 1.0, 0.0), 1.0, bumpy)),
+        Object::Sphere(Sphere::new(Point::new(-6.0, 5.0, 4.0), 1.5, light)),
+    ];
+
+    let object = Object::Collection(Collection { objects });
+    let background = Background::Gradient {
+        bottom: Color::new(0.1, 0.1, 0.1),
+        top: Color::new(0.2, 0.25, 0.35),
+    };
+
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_glowing_picture() -> World {
+    let picture = Material::Light(Light {
+        texture: Texture::Image(Image::load("res/earth.jpg".into()).unwrap_or_default()),
+        intensity: 3.0,
+    });
+    let floor = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.6, 0.6, 0.6),
+    });
+    let white = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.8, 0.8, 0.8),
+    });
+    let mirror = Material::Metal(Metal {
+        albedo: Color::new(0.9, 0.9, 0.9),
+        fuzz: 0.05,
+        anisotropy: 0.0,
+    });
+
+    let objects = vec![
+        Object::Plane(Plane::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            floor,
+        )),
+        // 2:1 like the image, hanging above the floor
+        Object::Quad(Quad::new(
+            Point::new(-2.0, 0.8, -2.0),
+            Vector::new(4.0, 0.0, 0.0),
+            Vector::new(0.0, 2.0, 0.0),
+            picture,
+        )),
+        Object::Sphere(Sphere::new(Point::new(-1.2, 0.6, 0.0), 0.6, white)),
+        Object::Sphere(Sphere::new(Point::new(1.2, 0.6, 0.0), 0.6, mirror)),
+    ];
+
+    let object = Object::Collection(Collection { objects });
+    let background = Background::Solid(Color::BLACK);
+
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_sunlit() -> World {
+    let ground = Material::Simple(Simple {
+        texture: Texture::Checker(Checker::new(
+            Color::new(0.2, 0.3, 0.1),
+            Color::new(0.8, 0.8, 0.8),
+            1.0,
+        )),
+    });
+    let red = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.7, 0.2, 0.2),
+    });
+    let white = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.8, 0.8, 0.8),
+    });
+    let gold = Material::Metal(Metal {
+        albedo: Color::new(0.8, 0.6, 0.2),
+        fuzz: 0.1,
+        anisotropy: 0.0,
+    });
+
+    let objects = vec![
+        Object::Plane(Plane::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            ground,
+        )),
+        Object::Sphere(Sphere::new(Point::new(-2.2, 0.8, 0.0), 0.8, red)),
+        Object::Sphere(Sphere::new(Point::new(0.0, 1.0, -0.5), 1.0, white)),
+        Object::Sphere(Sphere::new(Point::new(2.2, 0.8, 0.0), 0.8, gold)),
+    ];
+
+    let object = Object::Collection(Collection { objects });
+    let background = Background::Gradient {
+        bottom: Color::new(0.3, 0.35, 0.4),
+        top: Color::new(0.1, 0.2, 0.45),
+    };
+
+    World {
+        object,
+        background,
+        lights: Lights {
+            // low in the sky to the left, for long shadows towards the camera
+            sun: Some(DirectionalLight {
+                direction: Vector::new(1.0, -0.6, 0.5),
+                radiance: Color::new(8.0, 7.5, 6.5),
+            }),
+            ..Default::default()
         },
-        white.clone(),
-    )));
+        important: Vec::new(),
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_cornell_box_empty() -> World {
+    let objects = cornell_box_walls();
+
+    let object = Object::Collection(Collection { objects });
+    let background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: vec![Object::Quad(cornell_box_light())],
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_cornell_box_two_boxes() -> World {
+    let mut objects = cornell_box_walls();
+
+    let white = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.73, 0.73, 0.73),
+    });
 
     // for quad in build_cuboid(Point::new(130.0, 0.0, 65.0), Point::new(295.0, 165.0, 230.0), Quaternion::new(0.0, 0.0, 0.0, 0.0), white.clone()) {
     //     objects.push(Object::Quad(quad));
@@ -930,7 +1658,594 @@ fn create_scene_cornell_box_two_boxes() -> World {
 
 
     let object = Object::Collection(Collection { objects });
-    let background = Color::new(0.0, 0.0, 0.0);
+    let background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: vec![Object::Quad(cornell_box_light())],
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_cornell_box_mirror_glass() -> World {
+    let mut objects = cornell_box_walls();
+
+    let mirror = Material::Metal(Metal {
+        albedo: Color::new(0.8, 0.85, 0.88),
+        fuzz: 0.0,
+        anisotropy: 0.0,
+    });
+    let glass = Material::Dielectric(Dielectric {
+        refraction_index: 1.5,
+        absorption: None,
+        fuzz: 0.0,
+    });
+
+    let y_axis = Vector::new(0.0, 1.0, 0.0);
+    for quad in build_cuboid(
+        Point::new(265.0, 0.0, 295.0),
+        Point::new(430.0, 330.0, 460.0),
+        Quaternion::from_axis_angle(y_axis, 15.0_f64.to_radians()),
+        mirror,
+    ) {
+        objects.push(Object::Quad(quad));
+    }
+    for quad in build_cuboid(
+        Point::new(130.0, 0.0, 65.0),
+        Point::new(295.0, 165.0, 230.0),
+        Quaternion::from_axis_angle(y_axis, -18.0_f64.to_radians()),
+        glass,
+    ) {
+        objects.push(Object::Quad(quad));
+    }
+
+    let object = Object::Collection(Collection { objects });
+    let background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: vec![Object::Quad(cornell_box_light())],
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_cornell_box_smoke() -> World {
+    let mut objects = cornell_box_walls();
+
+    let white = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.73, 0.73, 0.73),
+    });
+
+    let y_axis = Vector::new(0.0, 1.0, 0.0);
+    let tall_box = build_cuboid(
+        Point::new(265.0, 0.0, 295.0),
+        Point::new(430.0, 330.0, 460.0),
+        Quaternion::from_axis_angle(y_axis, 15.0_f64.to_radians()),
+        white.clone(),
+    );
+    let short_box = build_cuboid(
+        Point::new(130.0, 0.0, 65.0),
+        Point::new(295.0, 165.0, 230.0),
+        Quaternion::from_axis_angle(y_axis, -18.0_f64.to_radians()),
+        white,
+    );
+
+    for (cuboid, color) in [(tall_box, Color::BLACK), (short_box, Color::WHITE)] {
+        let boundary = Object::Collection(Collection {
+            objects: cuboid.into_iter().map(Object::Quad).collect(),
+        });
+        objects.push(Object::ConstantMedium(ConstantMedium::new(
+            boundary,
+            0.01,
+            Texture::Solid(Solid { color }),
+        )));
+    }
+
+    let object = Object::Collection(Collection { objects });
+    let background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: vec![Object::Quad(cornell_box_light())],
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_cornell_box_spotlight() -> World {
+    let mut objects = cornell_box_room();
+
+    let white = Material::Lambertian(Lambertian {
+        albedo: Color::new(0.73, 0.73, 0.73),
+    });
+    objects.push(Object::Sphere(Sphere::new(
+        Point::new(330.0, 90.0, 330.0),
+        90.0,
+        white,
+    )));
+
+    let object = Object::Collection(Collection { objects });
+    let background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+
+    // high in the front left corner, aimed past the sphere so its shadow falls across the floor
+    let position = Point::new(120.0, 540.0, 150.0);
+    let spotlight = Spotlight {
+        position,
+        direction: Point::new(320.0, 0.0, 340.0) - position,
+        inner_angle: 20.0,
+        outer_angle: 30.0,
+        intensity: Color::new(1.5e6, 1.4e6, 1.2e6),
+    };
+
+    World {
+        object,
+        background,
+        lights: Lights {
+            spotlights: vec![spotlight],
+            ..Default::default()
+        },
+        important: Vec::new(),
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn create_scene_empty() -> World {
+    let object = Object::Collection(Collection { objects: vec![] });
+    let background = Background::Gradient {
+        bottom: Color::WHITE,
+        top: Color::new(0.5, 0.7, 1.0),
+    };
+    World {
+        object,
+        background,
+        lights: Lights::default(),
+        important: Vec::new(),
+    }
+}
 
-    World { object, background }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::{Image, WrapMode};
+
+    #[test]
+    fn test_world_cache_builds_once_per_scene() {
+        let cache = WorldCache::default();
+
+        let first = cache.get(&Scene::ThreeSpheres);
+        let second = cache.get(&Scene::ThreeSpheres);
+        assert_eq!(cache.builds(), 1);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        cache.get(&Scene::OneSphere);
+        assert_eq!(cache.builds(), 2);
+        cache.get(&Scene::ThreeSpheres);
+        assert_eq!(cache.builds(), 3);
+    }
+
+    #[test]
+    fn test_material_override_changes_only_that_shape() {
+        use crate::material::Deflect;
+
+        let mut world = create_world(&Scene::ThreeSpheres);
+        assert_eq!(world.materials().len(), 4);
+
+        let origin = Point::new(0.0, 0.0, 0.0);
+        let center = Ray::new(origin, Vector::new(0.0, 0.0, -1.0));
+        let right = Ray::new(origin, Vector::new(1.0, 0.0, -1.0));
+        let attenuation = |world: &World, ray: &Ray| {
+            let hit = world.object.hit(ray, 0.001..f64::INFINITY).unwrap();
+            hit.material.deflect(ray, &hit).unwrap().attenuation
+        };
+        assert_eq!(attenuation(&world, &center), Color::new(0.1, 0.2, 0.5));
+        assert_eq!(attenuation(&world, &right), Color::new(0.8, 0.6, 0.2));
+
+        let index = world.pick(&center).unwrap();
+        assert_ne!(world.pick(&right), Some(index));
+        let red = Material::Lambertian(Lambertian {
+            albedo: Color::new(0.9, 0.1, 0.1),
+        });
+        let old = world.set_material(index, red.clone());
+        assert!(matches!(old, Some(Material::Lambertian(_))));
+
+        assert_eq!(attenuation(&world, &center), Color::new(0.9, 0.1, 0.1));
+        assert_eq!(attenuation(&world, &right), Color::new(0.8, 0.6, 0.2));
+        assert!(world.set_material(4, red).is_none());
+    }
+
+    #[test]
+    fn test_world_cache_material_override() {
+        let cache = WorldCache::default();
+        let scene = Scene::ThreeSpheres;
+        let gray = || {
+            Material::Lambertian(Lambertian {
+                albedo: Color::new(0.5, 0.5, 0.5),
+            })
+        };
+
+        // a render still using the world keeps it from changing underneath it
+        let world = cache.get(&scene);
+        assert!(!cache.set_material(&scene, 0, gray()));
+        drop(world);
+        assert!(cache.set_material(&scene, 0, gray()));
+        assert!(!cache.set_material(&Scene::OneSphere, 0, gray()));
+
+        let world = cache.get(&scene);
+        assert_eq!(cache.builds(), 1);
+        let Material::Lambertian(lambertian) = world.materials()[0] else {
+            panic!("Expected a Lambertian material");
+        };
+        assert_eq!(lambertian.albedo, Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_gradient_background_ends() {
+        let (bottom, top) = (Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0));
+        let background = Background::Gradient { bottom, top };
+
+        // directions don't have to be normalized
+        assert_eq!(background.color(&Vector::new(0.0, 3.0, 0.0)), top);
+        assert_eq!(background.color(&Vector::new(0.0, -0.2, 0.0)), bottom);
+        assert_eq!(
+            background.scaled(2.0).color(&Vector::new(0.0, 1.0, 0.0)),
+            top * 2.0
+        );
+    }
+
+    #[test]
+    fn test_gradient_background_blends_by_elevation() {
+        let background = Background::Gradient {
+            bottom: Color::BLACK,
+            top: Color::WHITE,
+        };
+        let at = |x: f64, y: f64| background.color(&Vector::new(x, y, 0.0)).r;
+
+        // the midpoint is at the horizon
+        assert!((at(1.0, 0.0) - 0.5).abs() < 1e-8);
+        assert!((at(0.0, 1.0) - 1.0).abs() < 1e-8);
+        assert!((at(0.0, -1.0) - 0.0).abs() < 1e-8);
+
+        // 30 degrees up is a third of the way from the horizon to the top, even though y = 0.5
+        let elevation = 30.0_f64.to_radians();
+        assert!((at(elevation.cos(), elevation.sin()) - 2.0 / 3.0).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_environment_background_wraps_image() {
+        // the top row is white and the bottom one black; columns go red, green, blue, black
+        let data = [
+            Color::WHITE,
+            Color::WHITE,
+            Color::WHITE,
+            Color::WHITE,
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+            Color::BLACK,
+            Color::BLACK,
+            Color::BLACK,
+            Color::BLACK,
+            Color::BLACK,
+        ];
+        let background = Background::environment(
+            Image {
+                data: Arc::new(data.to_vec()),
+                width: 4,
+                height: 3,
+                wrap: WrapMode::Clamp,
+            },
+            2.0,
+        );
+
+        assert_eq!(
+            background.color(&Vector::new(0.0, 1.0, 0.0)),
+            Color::WHITE * 2.0
+        );
+        assert_eq!(background.color(&Vector::new(0.0, -1.0, 0.0)), Color::BLACK);
+        // around the horizon, starting at +x and turning towards +z
+        let horizon = [
+            Vector::new(1.0, 0.1, 0.1),
+            Vector::new(-0.1, 0.1, 1.0),
+            Vector::new(-1.0, 0.1, -0.1),
+            Vector::new(0.1, 0.1, -1.0),
+        ];
+        for (direction, expected) in horizon.iter().zip(&data[4..8]) {
+            assert_eq!(background.color(direction), *expected * 2.0);
+        }
+
+        assert_eq!(
+            background.scaled(0.5).color(&Vector::new(0.0, 1.0, 0.0)),
+            Color::WHITE
+        );
+    }
+
+    #[test]
+    fn test_bundled_sky_is_high_dynamic_range() {
+        let sky = Image::load("res/sky.hdr".into()).unwrap();
+        let brightest = sky
+            .data
+            .iter()
+            .map(|color| color.luminance())
+            .fold(0.0, f64::max);
+        assert!(brightest > 5.0, "{}", brightest);
+    }
+
+    #[test]
+    fn test_disk_light_emits() {
+        use crate::material::{Deflect, Emit};
+        use crate::object::Hit;
+        use crate::ray::Ray;
+
+        let world = create_world(&Scene::DiskLight);
+        // from the side, up to the middle of the overhead lamp, passing above the sphere
+        let ray = Ray::new(Point::new(5.0, 5.0, 0.0), Vector::new(-5.0, 0.5, 0.0));
+        let hit = world.object.hit(&ray, 0.001..f64::INFINITY).unwrap();
+
+        assert!((hit.point - Point::new(0.0, 5.5, 0.0)).is_near_zero());
+        assert_eq!(
+            hit.material.emit(hit.u, hit.v, &hit.point),
+            Color::new(4.0, 4.0, 4.0)
+        );
+        assert!(hit.material.deflect(&ray, &hit).is_none());
+    }
+
+    #[test]
+    fn test_cornell_box_seams_do_not_leak() {
+        use crate::object::Hit;
+        use crate::ray::Ray;
+
+        let walls = Collection {
+            objects: cornell_box_walls(),
+        };
+        // the default camera looks in through the open front, but a lens or glass can bend rays
+        // from anywhere inside, so aim from a few points in the box
+        let eyes = [
+            Point::new(278.0, 278.0, 0.5),
+            Point::new(277.5, 277.5, 277.5),
+            Point::new(100.0, 450.0, 400.0),
+        ];
+
+        // points along the edges where two walls meet, behind the open front
+        let corners = [0.0, 555.0];
+        let mut seams = vec![];
+        for i in 1..=1000 {
+            // an irrational step keeps the points off any round numbers
+            let along = 555.0 * (i as f64 * std::f64::consts::FRAC_1_SQRT_2).fract();
+            for a in corners {
+                for b in corners {
+                    seams.push(Point::new(a, b, along));
+                    seams.push(Point::new(a, along, b));
+                    seams.push(Point::new(along, a, b));
+                }
+            }
+        }
+
+        let mut leaks = 0;
+        for eye in eyes {
+            for seam in &seams {
+                let ray = Ray::new(eye, *seam - eye);
+                if walls.hit(&ray, 0.001..f64::INFINITY).is_none() {
+                    leaks += 1;
+                }
+            }
+        }
+        assert_eq!(leaks, 0);
+    }
+
+    #[test]
+    fn test_memory_estimate_includes_image_texture() {
+        let image = Image {
+            data: Arc::new(vec![Color::BLACK; 100 * 100]),
+            width: 100,
+            height: 100,
+            wrap: WrapMode::Clamp,
+        };
+        let materials = [
+            Material::Simple(Simple {
+                texture: Texture::Image(image.clone()),
+            }),
+            // a glowing picture
+            Material::Light(Light {
+                texture: Texture::Image(image),
+                intensity: 1.0,
+            }),
+        ];
+        for material in materials {
+            let world = World {
+                object: Object::Collection(Collection {
+                    objects: vec![Object::Sphere(Sphere::new(
+                        Point::new(0.0, 0.0, 0.0),
+                        1.0,
+                        material,
+                    ))],
+                }),
+                background: Background::Solid(Color::BLACK),
+                lights: Lights::default(),
+                important: Vec::new(),
+            };
+
+            assert!(world.memory_estimate() >= 100 * 100 * std::mem::size_of::<Color>());
+        }
+    }
+
+    #[test]
+    fn test_cornell_box_mirror_glass() {
+        let world = create_world(&Scene::CornellBoxMirrorGlass);
+        let Object::Collection(collection) = world.object else {
+            panic!("Expected a collection");
+        };
+
+        let quads: Vec<&Quad> = collection
+            .objects
+            .iter()
+            .filter_map(|object| match object {
+                Object::Quad(quad) => Some(quad),
+                _ => None,
+            })
+            .collect();
+        // five walls and the light, plus two boxes of six faces each
+        assert_eq!(quads.len(), 18);
+        assert_eq!(collection.objects.len(), quads.len());
+
+        let has_metal = quads
+            .iter()
+            .any(|quad| matches!(quad.material(), Material::Metal(_)));
+        let has_dielectric = quads
+            .iter()
+            .any(|quad| matches!(quad.material(), Material::Dielectric(_)));
+        assert!(has_metal);
+        assert!(has_dielectric);
+    }
+
+    #[test]
+    fn test_sun_is_blocked_by_occluder() {
+        let grey = || {
+            Material::Lambertian(Lambertian {
+                albedo: Color::new(0.5, 0.5, 0.5),
+            })
+        };
+        let object = Object::Collection(Collection {
+            objects: vec![
+                Object::Plane(Plane::new(
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                    grey(),
+                )),
+                Object::Sphere(Sphere::new(Point::new(0.0, 2.0, 0.0), 0.5, grey())),
+            ],
+        });
+        let sun = DirectionalLight {
+            direction: Vector::new(0.0, -1.0, 0.0),
+            radiance: Color::new(2.0, 2.0, 2.0),
+        };
+        let ground_hit = |x: f64| {
+            let ray = Ray::new(Point::new(x, 1.0, 3.0), Vector::new(0.0, -1.0, -3.0));
+            object.hit(&ray, 0.001..f64::INFINITY).unwrap()
+        };
+
+        // straight below the sphere
+        let shadowed = ground_hit(0.0);
+        assert!(shadowed.point.length() < 1e-9);
+        assert_eq!(sun.direct_light(&object, &shadowed), Color::BLACK);
+
+        let lit = ground_hit(5.0);
+        let expected = 2.0 / std::f64::consts::PI;
+        let light = sun.direct_light(&object, &lit);
+        assert!((light.r - expected).abs() < 1e-9, "{:?}", light);
+    }
+
+    #[test]
+    fn test_spotlight_falloff_at_cone_edges() {
+        let spotlight = Spotlight {
+            position: Point::new(0.0, 10.0, 0.0),
+            direction: Vector::new(0.0, -2.0, 0.0),
+            inner_angle: 20.0,
+            outer_angle: 30.0,
+            intensity: Color::new(100.0, 100.0, 100.0),
+        };
+        // a point on the floor seen from the light `degrees` off the axis
+        let at_angle = |degrees: f64| Point::new(10.0 * degrees.to_radians().tan(), 0.0, 0.0);
+
+        assert_eq!(spotlight.falloff(&at_angle(0.0)), 1.0);
+        assert!((spotlight.falloff(&at_angle(19.999)) - 1.0).abs() < 1e-6);
+        assert!(spotlight.falloff(&at_angle(20.001)) < 1.0);
+        assert!(spotlight.falloff(&at_angle(29.999)) > 0.0);
+        assert!(spotlight.falloff(&at_angle(29.999)) < 1e-6);
+        assert_eq!(spotlight.falloff(&at_angle(30.001)), 0.0);
+        assert_eq!(spotlight.falloff(&at_angle(90.0)), 0.0);
+        // smoothstep is symmetric, so halfway between the cosines is half lit
+        let cos_mid = (20.0_f64.to_radians().cos() + 30.0_f64.to_radians().cos()) / 2.0;
+        let halfway = at_angle(cos_mid.acos().to_degrees());
+        assert!((spotlight.falloff(&halfway) - 0.5).abs() < 1e-9);
+
+        let ground = Object::Plane(Plane::new(
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Material::Lambertian(Lambertian {
+                albedo: Color::new(0.5, 0.5, 0.5),
+            }),
+        ));
+        let ground_hit = |point: Point| {
+            let ray = Ray::new(
+                point + Vector::new(0.0, 1.0, 0.0),
+                Vector::new(0.0, -1.0, 0.0),
+            );
+            ground.hit(&ray, 0.001..f64::INFINITY).unwrap()
+        };
+        let below = spotlight.direct_light(&ground, &ground_hit(at_angle(0.0)));
+        let expected = 100.0 / (100.0 * std::f64::consts::PI);
+        assert!((below.r - expected).abs() < 1e-9, "{:?}", below);
+        assert_eq!(
+            spotlight.direct_light(&ground, &ground_hit(at_angle(45.0))),
+            Color::BLACK
+        );
+    }
+
+    #[test]
+    fn test_grid_collection_matches_flat_collection() {
+        use crate::object::{GridCollection, Hit};
+        use crate::ray::Ray;
+
+        let ground = || {
+            Object::Plane(Plane::new(
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+                Material::Lambertian(Lambertian {
+                    albedo: Color::new(0.5, 0.5, 0.5),
+                }),
+            ))
+        };
+        let scene = || {
+            let mut objects = rng::with_seed(SCENE_SEED, || random_sphere_objects(false));
+            objects.push(ground());
+            objects
+        };
+        let flat = Collection { objects: scene() };
+        let grid = GridCollection::new(scene());
+
+        let origins = [
+            Point::new(13.0, 2.0, 3.0),
+            Point::new(0.0, 0.5, 0.0),
+            Point::new(-15.0, 0.2, -15.0),
+        ];
+        let mut hits = 0;
+        rng::with_seed(1, || {
+            for origin in origins {
+                for _ in 0..2000 {
+                    let direction = Vector::random_unit_vector();
+                    let ray = Ray::new(origin, direction);
+                    let expected = flat.hit(&ray, 0.001..f64::INFINITY);
+                    let actual = grid.hit(&ray, 0.001..f64::INFINITY);
+                    match (expected, actual) {
+                        (Some(expected), Some(actual)) => {
+                            assert_eq!(expected.t, actual.t, "{:?}", ray);
+                            assert_eq!(expected.point, actual.point);
+                            hits += 1;
+                        }
+                        (None, None) => {}
+                        (expected, actual) => panic!(
+                            "{:?}: flat hit {:?}, grid hit {:?}",
+                            ray,
+                            expected.map(|hit| hit.t),
+                            actual.map(|hit| hit.t)
+                        ),
+                    }
+                }
+            }
+        });
+        assert!(hits > 3000, "{}", hits);
+
+        // rays along the grid axes step through one row of cells
+        let ray = Ray::new(Point::new(-20.0, 0.2, 0.3), Vector::new(1.0, 0.0, 0.0));
+        let expected = flat.hit(&ray, 0.001..f64::INFINITY).map(|hit| hit.t);
+        assert_eq!(
+            grid.hit(&ray, 0.001..f64::INFINITY).map(|hit| hit.t),
+            expected
+        );
+    }
 }